@@ -7,7 +7,32 @@ label-avg-rtt = Avg Rtt
 label-max = Max
 label-min = Min
 label-jitter = Jitter
+label-stddev = Stddev
 label-loss = Loss
+label-mos = MOS
+label-burst-ratio = Burst
+label-dns = DNS
+label-connect = Connect
+label-tls = TLS
+label-ttfb = TTFB
+label-status = Status
+label-rcode = Rcode
+label-answers = Answers
+label-duplicates = Dup
+label-pmtu = PMTU
+label-packet = Packet
+label-source = Source
+label-af = AF
+label-healthy = healthy
+label-median = Median
+label-reordered = Reorder
+label-late = Late
+label-unreachable = Unreach
+label-hop = Hop
+label-host = Host
+label-sent = Sent
+label-best = Best
+label-worst = Worst
 
 ## Table View
 table-view-title = PingWatch Table View
@@ -25,6 +50,36 @@ sparkline-view-note = Blank area means timeout or error
 ## Graph View
 graph-view-title = PingWatch Graph View
 
+## Hops View
+hops-view-title = PingWatch Hops View
+
+## Sweep View
+sweep-view-title = PingWatch Sweep View
+sweep-hosts-up = Hosts up
+sweep-answered = Answered
+sweep-silent = Silent
+sweep-waiting = Waiting
+sweep-more = +{$count} more
+
+## Target Prompt
+prompt-add-target = Add target:
+
+## Outage Log
+outages-title = Outages
+outages-none = No outages so far
+outage-start = Start
+outage-end = End
+outage-duration = Duration
+outage-lost = Lost
+outage-ongoing = ongoing
+
+## Loss Bursts
+bursts-title = Loss bursts
+bursts-note = burst ratio 1 = random loss, above 1 = bursty
+burst-count = Bursts
+burst-longest = Longest
+burst-mean = Mean
+
 ## Error Messages
 error-target-required = Error: target IP address or hostname is required
 error-output-exists = Output file already exists: {$path}
@@ -54,7 +109,7 @@ arg-count-help = Number of pings to send
 arg-interval-help = Interval in seconds between pings
 arg-ipv6-help = Force using IPv6
 arg-multiple-help = Specify the maximum number of target addresses, Only works on one target address
-arg-view-help = View mode graph/table/point/sparkline
+arg-view-help = View mode graph/table/point/sparkline/hops/sweep
 arg-output-help = Output file to save ping results
 
 ## Rank Medals
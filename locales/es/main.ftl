@@ -7,7 +7,32 @@ label-avg-rtt = Avg RTT
 label-max = Máx
 label-min = Mín
 label-jitter = Jitter
+label-stddev = Desv. est.
 label-loss = Pérdida
+label-mos = MOS
+label-burst-ratio = Ráfaga
+label-dns = DNS
+label-connect = Conexión
+label-tls = TLS
+label-ttfb = TTFB
+label-status = Estado
+label-rcode = Rcode
+label-answers = Respuestas
+label-duplicates = Dup
+label-pmtu = PMTU
+label-packet = Paquete
+label-source = Origen
+label-af = AF
+label-healthy = sanos
+label-median = Mediana
+label-reordered = Reord
+label-late = Tardías
+label-unreachable = Inalc
+label-hop = Salto
+label-host = Host
+label-sent = Enviados
+label-best = Mejor
+label-worst = Peor
 
 ## Vista de Tabla
 table-view-title = Vista de Tabla de PingWatch
@@ -25,6 +50,36 @@ sparkline-view-note = El área en blanco significa timeout o error
 ## Vista de Gráfico
 graph-view-title = Vista de Gráfico de PingWatch
 
+## Vista de Saltos
+hops-view-title = Vista de Saltos de PingWatch
+
+## Vista de Barrido
+sweep-view-title = Vista de Barrido de PingWatch
+sweep-hosts-up = Hosts activos
+sweep-answered = Respondieron
+sweep-silent = Silenciosos
+sweep-waiting = Esperando
+sweep-more = +{$count} más
+
+## Target Prompt
+prompt-add-target = Añadir objetivo:
+
+## Outage Log
+outages-title = Cortes
+outages-none = Sin cortes hasta ahora
+outage-start = Inicio
+outage-end = Fin
+outage-duration = Duración
+outage-lost = Perdidos
+outage-ongoing = en curso
+
+## Loss Bursts
+bursts-title = Ráfagas de pérdida
+bursts-note = índice de ráfaga 1 = pérdida aleatoria, mayor que 1 = en ráfagas
+burst-count = Ráfagas
+burst-longest = Más larga
+burst-mean = Media
+
 ## Mensajes de Error
 error-target-required = Error: se requiere dirección IP objetivo o nombre de host
 error-output-exists = Archivo de salida ya existe: {$path}
@@ -54,7 +109,7 @@ arg-count-help = Número de pings a enviar
 arg-interval-help = Intervalo en segundos entre pings
 arg-ipv6-help = Forzar el uso de IPv6
 arg-multiple-help = Especificar el número máximo de direcciones objetivo, solo funciona en una dirección objetivo
-arg-view-help = Modo de vista graph/table/point/sparkline
+arg-view-help = Modo de vista graph/table/point/sparkline/hops/sweep
 arg-output-help = Archivo de salida para guardar resultados de ping
 
 ## Medallas de Rango
@@ -7,7 +7,32 @@ label-avg-rtt = Média RTT
 label-max = Máx
 label-min = Mín
 label-jitter = Jitter
+label-stddev = Desvio pad.
 label-loss = Perda
+label-mos = MOS
+label-burst-ratio = Rajada
+label-dns = DNS
+label-connect = Conexão
+label-tls = TLS
+label-ttfb = TTFB
+label-status = Status
+label-rcode = Rcode
+label-answers = Respostas
+label-duplicates = Dup
+label-pmtu = PMTU
+label-packet = Pacote
+label-source = Origem
+label-af = AF
+label-healthy = saudáveis
+label-median = Mediana
+label-reordered = Reord
+label-late = Atrasadas
+label-unreachable = Inalc
+label-hop = Salto
+label-host = Host
+label-sent = Enviados
+label-best = Melhor
+label-worst = Pior
 
 ## Visualização Tabela
 table-view-title = Visualização em Tabela do PingWatch
@@ -25,6 +50,36 @@ sparkline-view-note = Área em branco significa timeout ou erro
 ## Visualização Gráfico
 graph-view-title = Visualização em Gráfico do PingWatch
 
+## Visualização de Saltos
+hops-view-title = Visualização de Saltos do PingWatch
+
+## Visualização de Varredura
+sweep-view-title = Visualização de Varredura do PingWatch
+sweep-hosts-up = Hosts ativos
+sweep-answered = Responderam
+sweep-silent = Silenciosos
+sweep-waiting = Aguardando
+sweep-more = +{$count} outros
+
+## Target Prompt
+prompt-add-target = Adicionar alvo:
+
+## Outage Log
+outages-title = Quedas
+outages-none = Nenhuma queda até agora
+outage-start = Início
+outage-end = Fim
+outage-duration = Duração
+outage-lost = Perdidos
+outage-ongoing = em andamento
+
+## Loss Bursts
+bursts-title = Rajadas de perda
+bursts-note = índice de rajada 1 = perda aleatória, acima de 1 = em rajadas
+burst-count = Rajadas
+burst-longest = Mais longa
+burst-mean = Média
+
 ## Mensagens de Erro
 error-target-required = Erro: endereço IP alvo ou nome de host é obrigatório
 error-output-exists = Arquivo de saída já existe: {$path}
@@ -54,7 +109,7 @@ arg-count-help = Número de pings a enviar
 arg-interval-help = Intervalo em segundos entre pings
 arg-ipv6-help = Forçar uso de IPv6
 arg-multiple-help = Especificar o número máximo de endereços alvo, funciona apenas em um endereço alvo
-arg-view-help = Modo de visualização graph/table/point/sparkline
+arg-view-help = Modo de visualização graph/table/point/sparkline/hops/sweep
 arg-output-help = Arquivo de saída para salvar resultados de ping
 
 ## Medalhas de Rank
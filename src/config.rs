@@ -0,0 +1,517 @@
+use std::collections::HashSet;
+use std::fmt;
+use anyhow::anyhow;
+use serde::Deserialize;
+use toml::Spanned;
+
+use crate::network;
+use crate::probe;
+use crate::stats;
+
+/// Contents of a `pingwatch.toml`. Every setting is optional, flags given on the
+/// command line take precedence over the file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub count: Option<usize>,
+    /// seconds between pings, like `--interval`
+    pub interval: Option<Spanned<i32>>,
+    pub ipv6: Option<bool>,
+    pub view: Option<String>,
+    pub lang: Option<String>,
+    /// tag key the table groups targets by, like `--group-by`
+    pub group_by: Option<String>,
+    /// percentile columns of the table, like `--percentiles`
+    pub percentiles: Option<Vec<Spanned<String>>>,
+    /// loss burst ratio column of the table, like `--burst-column`
+    pub burst_column: Option<bool>,
+    pub probe: ProbeConfig,
+    /// defaults for every target, targets can override single values
+    pub thresholds: ThresholdsConfig,
+    pub output: OutputConfig,
+    pub exporter: ExporterConfig,
+    pub targets: Vec<TargetConfig>,
+
+    // kept to turn byte offsets into line/column positions
+    #[serde(skip)]
+    path: String,
+    #[serde(skip)]
+    text: String,
+}
+
+/// `[probe]`, the file counterpart of the probe flags
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProbeConfig {
+    pub expect_status: Option<Vec<u16>>,
+    pub expect_body: Option<String>,
+    pub max_hops: Option<u8>,
+    pub trace_interval: Option<u64>,
+    pub pmtu: Option<bool>,
+    pub pmtu_interval: Option<u64>,
+    pub resolve_interval: Option<u64>,
+    pub dual_stack: Option<bool>,
+    pub size: Option<usize>,
+    pub ttl: Option<Spanned<u32>>,
+    pub dscp: Option<Spanned<Dscp>>,
+    pub df: Option<bool>,
+    pub sources: Option<Vec<String>>,
+    pub interfaces: Option<Vec<String>>,
+    pub outage_after: Option<Spanned<u32>>,
+    pub outage_recovery: Option<Spanned<u32>>,
+}
+
+/// A DSCP written as number or class name
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Dscp {
+    Code(u8),
+    Class(String),
+}
+
+impl fmt::Display for Dscp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dscp::Code(code) => write!(f, "{}", code),
+            Dscp::Class(class) => write!(f, "{}", class),
+        }
+    }
+}
+
+/// `[output]`, where ping results are written to
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// file every result is appended to, like `--output`
+    pub file: Option<String>,
+}
+
+/// `[exporter]`, settings of `pingwatch exporter`
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExporterConfig {
+    pub port: Option<u16>,
+    /// seconds between pings, falls back to the top level `interval`
+    pub interval: Option<Spanned<i32>>,
+}
+
+/// `[thresholds]` or the thresholds of a single target, unset values are inherited
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThresholdsConfig {
+    pub loss_warn: Option<Spanned<f64>>,
+    pub loss_crit: Option<Spanned<f64>>,
+    pub rtt_warn: Option<Spanned<f64>>,
+    pub rtt_crit: Option<Spanned<f64>>,
+}
+
+/// When a target counts as degraded (warn) or failing (crit)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+    /// loss in percent
+    pub loss_warn: f64,
+    pub loss_crit: f64,
+    /// average rtt in ms, no rtt limit when unset
+    pub rtt_warn: Option<f64>,
+    pub rtt_crit: Option<f64>,
+}
+
+impl Default for Thresholds {
+    // any loss is a warning, more than half of the probes lost is critical
+    fn default() -> Self {
+        Self {
+            loss_warn: 0.0,
+            loss_crit: 50.0,
+            rtt_warn: None,
+            rtt_crit: None,
+        }
+    }
+}
+
+impl Thresholds {
+    pub fn is_critical(&self, loss: f64, avg_rtt: f64) -> bool {
+        loss > self.loss_crit || self.rtt_crit.is_some_and(|crit| avg_rtt > crit)
+    }
+
+    pub fn is_warning(&self, loss: f64, avg_rtt: f64) -> bool {
+        loss > self.loss_warn || self.rtt_warn.is_some_and(|warn| avg_rtt > warn)
+    }
+}
+
+impl ThresholdsConfig {
+    fn resolve(&self, base: Thresholds) -> Thresholds {
+        let value = |v: &Option<Spanned<f64>>| v.as_ref().map(|v| *v.get_ref());
+        Thresholds {
+            loss_warn: value(&self.loss_warn).unwrap_or(base.loss_warn),
+            loss_crit: value(&self.loss_crit).unwrap_or(base.loss_crit),
+            rtt_warn: value(&self.rtt_warn).or(base.rtt_warn),
+            rtt_crit: value(&self.rtt_crit).or(base.rtt_crit),
+        }
+    }
+}
+
+/// One `[[targets]]` entry
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetConfig {
+    /// hostname, address or probe URL such as `tcp://db01:5432`
+    pub host: Spanned<String>,
+    /// shown instead of the host and used as exporter `target` label
+    pub name: Option<Spanned<String>>,
+    /// icmp, tcp, udp, http, https or dns, when `host` is not a probe URL already
+    pub probe: Option<Spanned<String>>,
+    pub port: Option<u16>,
+    /// request path of http(s) probes
+    pub path: Option<String>,
+    /// name to look up and record type of dns probes
+    pub query: Option<String>,
+    pub qtype: Option<String>,
+    /// seconds between pings of this target
+    pub interval: Option<Spanned<i32>>,
+    /// `key=value` pairs, e.g. `["dc=fra", "role=db"]`
+    #[serde(default)]
+    pub tags: Vec<Spanned<String>>,
+    #[serde(default)]
+    pub thresholds: ThresholdsConfig,
+    pub size: Option<usize>,
+    pub ttl: Option<u32>,
+    pub dscp: Option<Dscp>,
+    pub df: Option<bool>,
+}
+
+/// A target to probe, from the command line or the config file
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetSpec {
+    /// probe string as understood by [`probe::parse_target`]
+    pub target: String,
+    pub name: Option<String>,
+    /// seconds between pings, the global interval when unset
+    pub interval: Option<i32>,
+    /// `key=value` pairs
+    pub tags: Vec<String>,
+    pub thresholds: Thresholds,
+}
+
+impl TargetSpec {
+    /// A target given on the command line
+    pub fn new(target: String, thresholds: Thresholds) -> Self {
+        Self {
+            target,
+            name: None,
+            interval: None,
+            tags: Vec::new(),
+            thresholds,
+        }
+    }
+
+    /// The group the target belongs to: the value of the `by` tag, or its first tag when
+    /// no key is given
+    pub fn group(&self, by: Option<&str>) -> Option<String> {
+        match by {
+            Some(by) => self.tags.iter().map(|tag| split_tag(tag)).find(|(key, _)| *key == by).map(|(_, value)| value.to_string()),
+            None => self.tags.first().cloned(),
+        }
+    }
+}
+
+/// Label names a tag key can't take because the exporter uses them already
+const RESERVED_TAG_KEYS: [&str; 13] = ["target", "ip", "source", "af", "status", "phase", "code", "rcode", "size", "ttl", "dscp", "df", "quantile"];
+
+/// Splits a `key=value` tag
+pub fn split_tag(tag: &str) -> (&str, &str) {
+    tag.split_once('=').unwrap_or((tag, ""))
+}
+
+// tag keys become Prometheus label names
+pub(crate) fn check_tag(tag: &str) -> anyhow::Result<()> {
+    let Some((key, value)) = tag.split_once('=') else {
+        return Err(anyhow!("tag must be written as key=value: {}", tag));
+    };
+    let mut chars = key.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid || key.starts_with("__") {
+        return Err(anyhow!("tag key must be a valid label name: {}", key));
+    }
+    if RESERVED_TAG_KEYS.contains(&key) {
+        return Err(anyhow!("tag key is reserved: {}", key));
+    }
+    if value.is_empty() {
+        return Err(anyhow!("tag value can't be empty: {}", tag));
+    }
+    Ok(())
+}
+
+impl Config {
+    /// Reads and validates a config file. Errors point at the offending line and column.
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| anyhow!("unable to read config file {}: {}", path, err))?;
+        let mut config: Config = toml::from_str(&text)
+            .map_err(|err| match err.span() {
+                Some(span) => {
+                    let (line, column) = position(&text, span.start);
+                    anyhow!("{}:{}:{}: {}", path, line, column, err.message())
+                }
+                None => anyhow!("{}: {}", path, err.message()),
+            })?;
+        config.path = path.to_string();
+        config.text = text;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// The `percentiles` as positions in [`stats::PERCENTILES`], validated by [`Config::load`]
+    pub fn percentiles(&self) -> Option<Vec<usize>> {
+        self.percentiles.as_ref().map(|names| names.iter().filter_map(|name| stats::parse_percentile(name.get_ref()).ok()).collect())
+    }
+
+    /// Global thresholds with the built-in defaults filled in
+    pub fn thresholds(&self) -> Thresholds {
+        self.thresholds.resolve(Thresholds::default())
+    }
+
+    /// The `[[targets]]` entries, validated by [`Config::load`]
+    pub fn target_specs(&self) -> Vec<TargetSpec> {
+        let thresholds = self.thresholds();
+        self.targets
+            .iter()
+            .map(|target| TargetSpec {
+                target: target_string(target),
+                name: target.name.as_ref().map(|name| name.get_ref().clone()),
+                interval: target.interval.as_ref().map(|interval| *interval.get_ref()),
+                tags: target.tags.iter().map(|tag| tag.get_ref().clone()).collect(),
+                thresholds: target.thresholds.resolve(thresholds),
+            })
+            .collect()
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        for interval in [&self.interval, &self.exporter.interval].into_iter().flatten() {
+            self.validate_interval(interval)?;
+        }
+        if let Some(ttl) = &self.probe.ttl {
+            if !(1..=255).contains(ttl.get_ref()) {
+                return Err(self.error_at(ttl.span().start, "ttl must be between 1 and 255"));
+            }
+        }
+        for count in [&self.probe.outage_after, &self.probe.outage_recovery].into_iter().flatten() {
+            if *count.get_ref() == 0 {
+                return Err(self.error_at(count.span().start, "outage thresholds count probes and must be at least 1"));
+            }
+        }
+        if let Some(dscp) = &self.probe.dscp {
+            probe::parse_dscp(&dscp.get_ref().to_string()).map_err(|err| self.error_at(dscp.span().start, err))?;
+        }
+        self.validate_thresholds(&self.thresholds)?;
+        for name in self.percentiles.iter().flatten() {
+            stats::parse_percentile(name.get_ref()).map_err(|err| self.error_at(name.span().start, err))?;
+        }
+
+        let mut names = HashSet::new();
+        for target in &self.targets {
+            if let Some(name) = &target.name {
+                if !names.insert(name.get_ref()) {
+                    return Err(self.error_at(name.span().start, format!("duplicate target name: {}", name.get_ref())));
+                }
+            }
+            if let Some(interval) = &target.interval {
+                self.validate_interval(interval)?;
+            }
+            if let Some(kind) = &target.probe {
+                if !["icmp", "tcp", "udp", "http", "https", "dns"].contains(&kind.get_ref().as_str()) {
+                    return Err(self.error_at(kind.span().start, format!("unsupported probe type: {}", kind.get_ref())));
+                }
+                if target.host.get_ref().contains("://") {
+                    return Err(self.error_at(kind.span().start, "probe is set but host is a probe URL already"));
+                }
+            }
+            let mut keys = HashSet::new();
+            for tag in &target.tags {
+                check_tag(tag.get_ref()).map_err(|err| self.error_at(tag.span().start, err))?;
+                if !keys.insert(split_tag(tag.get_ref()).0) {
+                    return Err(self.error_at(tag.span().start, format!("duplicate tag key: {}", split_tag(tag.get_ref()).0)));
+                }
+            }
+            // the probe string is checked the same way as one given on the command line
+            probe::parse_target(&target_string(target)).map_err(|err| self.error_at(target.host.span().start, err))?;
+            network::expand_target(&target_string(target)).map_err(|err| self.error_at(target.host.span().start, format!("{:#}", err)))?;
+            self.validate_thresholds(&target.thresholds)?;
+        }
+        Ok(())
+    }
+
+    fn validate_interval(&self, interval: &Spanned<i32>) -> anyhow::Result<()> {
+        if *interval.get_ref() < 0 {
+            return Err(self.error_at(interval.span().start, "interval can't be negative"));
+        }
+        Ok(())
+    }
+
+    fn validate_thresholds(&self, thresholds: &ThresholdsConfig) -> anyhow::Result<()> {
+        for loss in [&thresholds.loss_warn, &thresholds.loss_crit].into_iter().flatten() {
+            if !(0.0..=100.0).contains(loss.get_ref()) {
+                return Err(self.error_at(loss.span().start, "loss thresholds are percentages between 0 and 100"));
+            }
+        }
+        for rtt in [&thresholds.rtt_warn, &thresholds.rtt_crit].into_iter().flatten() {
+            if *rtt.get_ref() < 0.0 {
+                return Err(self.error_at(rtt.span().start, "rtt thresholds can't be negative"));
+            }
+        }
+        Ok(())
+    }
+
+    fn error_at(&self, offset: usize, msg: impl fmt::Display) -> anyhow::Error {
+        let (line, column) = position(&self.text, offset);
+        anyhow!("{}:{}:{}: {}", self.path, line, column, msg)
+    }
+}
+
+// builds the probe string of a target entry, e.g. `tcp://db01:5432#dscp=46`
+fn target_string(target: &TargetConfig) -> String {
+    let host = target.host.get_ref();
+    let mut s = match target.probe.as_ref().map(|kind| kind.get_ref().as_str()) {
+        None | Some("icmp") => host.clone(),
+        Some(kind) => {
+            // bare IPv6 addresses need brackets once a port follows
+            let host = if host.contains(':') && !host.starts_with('[') { format!("[{}]", host) } else { host.clone() };
+            let mut s = format!("{}://{}", kind, host);
+            if let Some(port) = target.port {
+                s.push_str(&format!(":{}", port));
+            }
+            match kind {
+                "http" | "https" => s.push_str(target.path.as_deref().unwrap_or("/")),
+                "dns" => {
+                    s.push_str(&format!("/{}", target.query.as_deref().unwrap_or_default()));
+                    if let Some(qtype) = &target.qtype {
+                        s.push_str(&format!("?type={}", qtype));
+                    }
+                }
+                _ => {}
+            }
+            s
+        }
+    };
+
+    let mut params = Vec::new();
+    if let Some(size) = target.size {
+        params.push(format!("size={}", size));
+    }
+    if let Some(ttl) = target.ttl {
+        params.push(format!("ttl={}", ttl));
+    }
+    if let Some(dscp) = &target.dscp {
+        params.push(format!("dscp={}", dscp));
+    }
+    if let Some(df) = target.df {
+        params.push(format!("df={}", df));
+    }
+    if !params.is_empty() {
+        s.push('#');
+        s.push_str(&params.join(","));
+    }
+    s
+}
+
+// 1-based line and column of a byte offset
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir().join(format!("pingwatch-{}-{}.toml", std::process::id(), name)).to_string_lossy().into_owned()
+    }
+
+    // writes `text` to a file of its own and loads it
+    fn load(name: &str, text: &str) -> anyhow::Result<Config> {
+        let path = temp_path(name);
+        std::fs::write(&path, text).unwrap();
+        let result = Config::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        result
+    }
+
+    // the error of loading `text`, with the file written as `name.toml`
+    fn error(name: &str, text: &str) -> String {
+        load(name, text).unwrap_err().to_string().replace(&temp_path(name), &format!("{}.toml", name))
+    }
+
+    #[test]
+    fn loads_settings_and_targets() {
+        let config = load("valid", concat!(
+            "interval = 2\n",
+            "view = \"table\"\n",
+            "percentiles = [\"p50\", \"p99\"]\n",
+            "[probe]\n",
+            "ttl = 64\n",
+            "[[targets]]\n",
+            "host = \"db01\"\n",
+            "probe = \"tcp\"\n",
+            "port = 5432\n",
+            "name = \"primary\"\n",
+            "tags = [\"dc=fra\"]\n",
+        )).unwrap();
+        assert_eq!(config.interval.as_ref().map(|interval| *interval.get_ref()), Some(2));
+        assert_eq!(config.percentiles(), Some(vec![0, 3]));
+        let specs = config.target_specs();
+        assert_eq!(specs.len(), 1);
+        assert_eq!(specs[0].target, "tcp://db01:5432");
+        assert_eq!(specs[0].name.as_deref(), Some("primary"));
+        assert_eq!(specs[0].group(Some("dc")).as_deref(), Some("fra"));
+    }
+
+    #[test]
+    fn syntax_errors_point_at_line_and_column() {
+        assert!(error("syntax", "interval = 2\nview = table\n").starts_with("syntax.toml:2:8: "));
+    }
+
+    #[test]
+    fn unknown_keys_point_at_line_and_column() {
+        assert!(error("unknown", "[probe]\nttl = 64\n  retries = 3\n").starts_with("unknown.toml:3:3: "));
+    }
+
+    #[test]
+    fn invalid_values_point_at_line_and_column() {
+        assert_eq!(error("ttl", "[probe]\nttl = 300\n"), "ttl.toml:2:7: ttl must be between 1 and 255");
+        assert_eq!(
+            error("loss", "[thresholds]\nloss_warn = 5.0\nloss_crit = 150.0\n"),
+            "loss.toml:3:13: loss thresholds are percentages between 0 and 100",
+        );
+        assert_eq!(
+            error("names", "[[targets]]\nhost = \"a\"\nname = \"web\"\n\n[[targets]]\nhost = \"b\"\nname = \"web\"\n"),
+            "names.toml:7:8: duplicate target name: web",
+        );
+        assert_eq!(
+            error("tags", "[[targets]]\nhost = \"a\"\ntags = [\"dc=fra\", \"ip=1\"]\n"),
+            "tags.toml:3:19: tag key is reserved: ip",
+        );
+    }
+
+    #[test]
+    fn negative_intervals_point_at_line_and_column() {
+        assert_eq!(error("interval", "count = 3\ninterval = -1\n"), "interval.toml:2:12: interval can't be negative");
+        assert_eq!(error("exporter", "[exporter]\ninterval = -5\n"), "exporter.toml:2:12: interval can't be negative");
+        assert_eq!(
+            error("target_interval", "[[targets]]\nhost = \"a\"\ninterval = -2\n"),
+            "target_interval.toml:3:12: interval can't be negative",
+        );
+        assert_eq!(load("zero_interval", "interval = 0\n").unwrap().interval.map(|interval| *interval.get_ref()), Some(0));
+    }
+
+    #[test]
+    fn positions_count_characters() {
+        assert_eq!(position("a = 1\nb = 2\n", 0), (1, 1));
+        assert_eq!(position("a = 1\nb = 2\n", 10), (2, 5));
+        assert_eq!(position("é = \"ü\"\nx", 8), (1, 7));
+        assert_eq!(position("é = \"ü\"\nx", 10), (2, 1));
+        assert_eq!(position("a", 100), (1, 2));
+    }
+}
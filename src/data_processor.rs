@@ -1,55 +1,200 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex, mpsc};
-use crate::ping_event::PingEvent;
+use std::time::SystemTime;
+use crate::ping_event::{PingEvent, Resolution};
 use crate::ip_data::IpData;
+use crate::outage::OutageThresholds;
+use crate::probe::{DnsAnswer, HttpCheck, HttpTiming, Source};
+
+// how many lost probes per row late replies are matched against
+const LOST_SEQS: usize = 64;
+
+// why a probe got no reply, each has its own counter on the row
+#[derive(Clone, Copy)]
+enum Loss {
+    Timeout,
+    CheckFailed,
+    Unreachable,
+    Refused,
+}
 
 pub struct DataProcessor {
-    data_map: HashMap<String, IpData>, // key: addr_ip
+    data_map: HashMap<String, IpData>, // key: addr_ip_source
     point_num: usize,
+    outage: OutageThresholds,
 }
 
 impl DataProcessor {
-    pub fn new(targets: &[(String, String)], view_type: &str) -> Self {
-        let point_num = if view_type == "point" || view_type == "sparkline" {
+    pub fn new(targets: &[IpData], view_type: &str, outage: OutageThresholds) -> Self {
+        let point_num = if view_type == "point" || view_type == "sparkline" || view_type == "hops" {
             200
         } else {
             10
         };
         let mut data_map = HashMap::new();
         
-        for (addr, ip) in targets {
-            let key = format!("{}_{}", addr, ip);
-            data_map.insert(key, IpData {
-                addr: addr.clone(),
-                ip: ip.clone(),
-                rtts: VecDeque::new(),
-                last_attr: 0.0,
-                min_rtt: 0.0,
-                max_rtt: 0.0,
-                timeout: 0,
-                received: 0,
-                pop_count: 0,
-            });
+        for target in targets {
+            let key = Self::key(&target.addr, &target.ip, target.source.as_ref());
+            data_map.insert(key, target.clone());
         }
         
-        Self { data_map, point_num }
+        Self { data_map, point_num, outage }
     }
     
+    fn key(addr: &str, ip: &str, source: Option<&Source>) -> String {
+        format!("{}_{}_{}", addr, ip, source.map(Source::to_string).unwrap_or_default())
+    }
+
     pub fn process_event(&mut self, event: PingEvent) -> Option<IpData> {
         match event {
-            PingEvent::Success { addr, ip, rtt, .. } => {
-                let key = format!("{}_{}", addr, ip);
+            PingEvent::Success { addr, ip, source, seq, rtt } => {
+                let key = Self::key(&addr, &ip, source.as_ref());
+                if let Some(data) = self.data_map.get_mut(&key) {
+                    if data.last_reply_seq.is_none_or(|last| is_behind(last, seq)) {
+                        data.last_reply_seq = Some(seq);
+                    }
+                    Self::update_success_stats(data, rtt, self.point_num, self.outage);
+                    Some(data.clone())
+                } else {
+                    None
+                }
+            },
+            PingEvent::Timeout { addr, ip, source, seq } => {
+                let key = Self::key(&addr, &ip, source.as_ref());
+                if let Some(data) = self.data_map.get_mut(&key) {
+                    Self::remember_lost(data, seq);
+                    Self::update_lost_stats(data, Loss::Timeout, self.point_num, self.outage);
+                    Some(data.clone())
+                } else {
+                    None
+                }
+            },
+            PingEvent::Unreachable { addr, ip, source, seq, icmp_type, code } => {
+                let key = Self::key(&addr, &ip, source.as_ref());
                 if let Some(data) = self.data_map.get_mut(&key) {
-                    Self::update_success_stats(data, rtt, self.point_num);
+                    Self::remember_lost(data, seq);
+                    data.unreachable_reason = Some((icmp_type, code));
+                    Self::update_lost_stats(data, Loss::Unreachable, self.point_num, self.outage);
                     Some(data.clone())
                 } else {
                     None
                 }
             },
-            PingEvent::Timeout { addr, ip, .. } => {
-                let key = format!("{}_{}", addr, ip);
+            PingEvent::Duplicate { addr, ip, source, seq } => {
+                let key = Self::key(&addr, &ip, source.as_ref());
+                let data = self.data_map.get_mut(&key)?;
+                data.duplicates += 1;
+                data.duplicate_seq = Some(seq);
+                Some(data.clone())
+            },
+            PingEvent::Late { addr, ip, source, seq, rtt } => {
+                let key = Self::key(&addr, &ip, source.as_ref());
+                let data = self.data_map.get_mut(&key)?;
+                // only replies to probes this row counted as lost, they stay lost
+                let index = data.lost_seqs.iter().position(|&lost| lost == seq)?;
+                data.lost_seqs.remove(index);
+                data.late += 1;
+                data.late_rtt = Some(rtt);
+                // a later probe got its reply first
+                if data.last_reply_seq.is_some_and(|last| is_behind(seq, last)) {
+                    data.reordered += 1;
+                }
+                Some(data.clone())
+            },
+            PingEvent::Refused { addr, ip, source, .. } => {
+                let key = Self::key(&addr, &ip, source.as_ref());
                 if let Some(data) = self.data_map.get_mut(&key) {
-                    Self::update_timeout_stats(data, self.point_num);
+                    Self::update_lost_stats(data, Loss::Refused, self.point_num, self.outage);
+                    Some(data.clone())
+                } else {
+                    None
+                }
+            },
+            PingEvent::Dns { addr, ip, source, rtt, answer } => {
+                let key = Self::key(&addr, &ip, source.as_ref());
+                if let Some(data) = self.data_map.get_mut(&key) {
+                    Self::update_dns_stats(data, rtt, answer, self.point_num, self.outage);
+                    Some(data.clone())
+                } else {
+                    None
+                }
+            },
+            PingEvent::Udp { addr, ip, source, rtt, duplicates, reordered } => {
+                let key = Self::key(&addr, &ip, source.as_ref());
+                if let Some(data) = self.data_map.get_mut(&key) {
+                    data.duplicates += duplicates as usize;
+                    data.reordered += reordered as usize;
+                    match rtt {
+                        Some(rtt) => Self::update_success_stats(data, rtt, self.point_num, self.outage),
+                        None => Self::update_lost_stats(data, Loss::Timeout, self.point_num, self.outage),
+                    }
+                    Some(data.clone())
+                } else {
+                    None
+                }
+            },
+            PingEvent::PathChanged { addr, ip, source } => {
+                let key = Self::key(&addr, &ip, source.as_ref());
+                if let Some(data) = self.data_map.get_mut(&key) {
+                    data.path_changes += 1;
+                    Some(data.clone())
+                } else {
+                    None
+                }
+            },
+            PingEvent::Pmtu { addr, ip, source, mtu } => {
+                let key = Self::key(&addr, &ip, source.as_ref());
+                if let Some(data) = self.data_map.get_mut(&key) {
+                    data.pmtu = mtu;
+                    Some(data.clone())
+                } else {
+                    None
+                }
+            },
+            PingEvent::Resolve { addr, ip, source, resolution } => {
+                let key = Self::key(&addr, &ip, source.as_ref());
+                let data = self.data_map.get_mut(&key)?;
+                data.unresolved = resolution == Resolution::Failed;
+                if let Resolution::Changed(new_ip) = resolution {
+                    data.retired = true;
+                    // the new address starts from scratch but keeps the target's settings,
+                    // its row shows up with its first probe
+                    let row = IpData {
+                        name: data.name.clone(),
+                        tags: data.tags.clone(),
+                        group: data.group.clone(),
+                        thresholds: data.thresholds,
+                        packet: data.packet,
+                        source: data.source.clone(),
+                        ..IpData::new(addr.clone(), new_ip.clone())
+                    };
+                    let updated = data.clone();
+                    self.data_map.insert(Self::key(&addr, &new_ip, source.as_ref()), row);
+                    return Some(updated);
+                }
+                Some(data.clone())
+            },
+            PingEvent::Added { row } => {
+                let key = Self::key(&row.addr, &row.ip, row.source.as_ref());
+                self.data_map.insert(key, row.clone());
+                Some(row)
+            },
+            PingEvent::Removed { addr } => {
+                self.data_map.retain(|_, data| data.addr != addr);
+                // tells the ui to drop the rows of the target
+                Some(IpData { removed: true, ..IpData::new(addr, String::new()) })
+            },
+            PingEvent::Reset => {
+                // the ui resets its copies itself
+                for data in self.data_map.values_mut() {
+                    data.stats.reset();
+                }
+                None
+            },
+            PingEvent::Http { addr, ip, source, timing, check } => {
+                let key = Self::key(&addr, &ip, source.as_ref());
+                if let Some(data) = self.data_map.get_mut(&key) {
+                    Self::update_http_stats(data, timing, check, self.point_num, self.outage);
                     Some(data.clone())
                 } else {
                     None
@@ -58,17 +203,13 @@ impl DataProcessor {
         }
     }
     
-    fn update_success_stats(data: &mut IpData, rtt: f64, point_num: usize) {
+    fn update_success_stats(data: &mut IpData, rtt: f64, point_num: usize, outage: OutageThresholds) {
         data.received += 1;
         data.last_attr = rtt;
         data.rtts.push_back(rtt);
-        
-        if data.min_rtt == 0.0 || rtt < data.min_rtt {
-            data.min_rtt = rtt;
-        }
-        if rtt > data.max_rtt {
-            data.max_rtt = rtt;
-        }
+        data.stats.record(Some(rtt));
+        data.outages.record(true, SystemTime::now(), outage);
+        data.bursts.record(false);
         
         if data.rtts.len() > point_num {
             data.rtts.pop_front();
@@ -76,48 +217,180 @@ impl DataProcessor {
         }
     }
     
-    fn update_timeout_stats(data: &mut IpData, point_num: usize) {
+    fn update_lost_stats(data: &mut IpData, loss: Loss, point_num: usize, outage: OutageThresholds) {
+        match loss {
+            Loss::Timeout => data.timeout += 1,
+            Loss::CheckFailed => data.check_failed += 1,
+            Loss::Unreachable => data.unreachable += 1,
+            Loss::Refused => data.refused += 1,
+        }
         data.rtts.push_back(-1.0);
         data.last_attr = -1.0;
-        data.timeout += 1;
+        data.stats.record(None);
+        data.outages.record(false, SystemTime::now(), outage);
+        data.bursts.record(true);
         
         if data.rtts.len() > point_num {
             data.rtts.pop_front();
             data.pop_count += 1;
         }
     }
+
+    fn update_http_stats(data: &mut IpData, timing: HttpTiming, check: HttpCheck, point_num: usize, outage: OutageThresholds) {
+        data.http = Some(timing);
+        if check == HttpCheck::Passed {
+            let total = timing.total.as_secs_f64() * 1000.0;
+            let total: f64 = format!("{:.2}", total).parse().unwrap();
+            Self::update_success_stats(data, total, point_num, outage);
+            return;
+        }
+
+        Self::update_lost_stats(data, Loss::CheckFailed, point_num, outage);
+    }
+
+    fn update_dns_stats(data: &mut IpData, rtt: f64, answer: DnsAnswer, point_num: usize, outage: OutageThresholds) {
+        data.dns = Some(answer);
+        if answer.is_ok() {
+            Self::update_success_stats(data, rtt, point_num, outage);
+        } else {
+            Self::update_lost_stats(data, Loss::CheckFailed, point_num, outage);
+        }
+    }
+
+    fn remember_lost(data: &mut IpData, seq: u16) {
+        if data.lost_seqs.len() == LOST_SEQS {
+            data.lost_seqs.pop_front();
+        }
+        data.lost_seqs.push_back(seq);
+    }
     
 }
 
+// sequence numbers wrap, compare them as a signed distance
+fn is_behind(seq: u16, other: u16) -> bool {
+    (seq.wrapping_sub(other) as i16) < 0
+}
+
 pub fn start_data_processor(
     ping_event_rx: mpsc::Receiver<PingEvent>,
+    control_rx: mpsc::Receiver<PingEvent>,
     ui_data_tx: mpsc::SyncSender<IpData>,
-    targets: Vec<(String, String)>,
+    targets: Vec<IpData>,
     view_type: String,
+    outage: OutageThresholds,
     running: Arc<Mutex<bool>>,
 ) {
     std::thread::spawn(move || {
-        let mut processor = DataProcessor::new(&targets, &view_type);
+        let mut processor = DataProcessor::new(&targets, &view_type, outage);
         
-        while *running.lock().unwrap() {
-            match ping_event_rx.recv_timeout(std::time::Duration::from_millis(100)) {
-                Ok(event) => {
-                    if let Some(updated_data) = processor.process_event(event) {
-                        if ui_data_tx.send(updated_data).is_err() {
-                            // UI channel closed, exit
-                            break;
-                        }
-                    }
-                },
-                Err(mpsc::RecvTimeoutError::Timeout) => {
-                    // Continue checking running flag
-                    continue;
-                },
-                Err(mpsc::RecvTimeoutError::Disconnected) => {
-                    // Network tasks finished
-                    break;
+        'events: while *running.lock().unwrap() {
+            let event = match ping_event_rx.recv_timeout(std::time::Duration::from_millis(100)) {
+                Ok(event) => Some(event),
+                // Continue checking running flag
+                Err(mpsc::RecvTimeoutError::Timeout) => None,
+                // Network tasks finished
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+            // the ui's rows, removals and resets go first: a row added is sent before its
+            // first probe, so it is known by the time that probe's event is processed
+            let events = control_rx.try_iter().chain(event);
+            for updated_data in events.filter_map(|event| processor.process_event(event)) {
+                if ui_data_tx.send(updated_data).is_err() {
+                    // UI channel closed, exit
+                    break 'events;
                 }
             }
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn processor() -> DataProcessor {
+        let row = IpData::new("db01".to_string(), "10.0.0.1".to_string());
+        DataProcessor::new(&[row], "table", OutageThresholds::default())
+    }
+
+    fn success(seq: u16) -> PingEvent {
+        PingEvent::Success { addr: "db01".to_string(), ip: "10.0.0.1".to_string(), source: None, seq, rtt: 1.0 }
+    }
+
+    fn timeout(seq: u16) -> PingEvent {
+        PingEvent::Timeout { addr: "db01".to_string(), ip: "10.0.0.1".to_string(), source: None, seq }
+    }
+
+    fn late(seq: u16) -> PingEvent {
+        PingEvent::Late { addr: "db01".to_string(), ip: "10.0.0.1".to_string(), source: None, seq, rtt: 1500.0 }
+    }
+
+    fn duplicate(seq: u16) -> PingEvent {
+        PingEvent::Duplicate { addr: "db01".to_string(), ip: "10.0.0.1".to_string(), source: None, seq }
+    }
+
+    #[test]
+    fn sequence_numbers_compare_across_the_wrap() {
+        assert!(is_behind(1, 2));
+        assert!(!is_behind(2, 1));
+        assert!(!is_behind(7, 7));
+        assert!(is_behind(65535, 0));
+        assert!(is_behind(65530, 3));
+        assert!(!is_behind(0, 65535));
+    }
+
+    #[test]
+    fn late_replies_are_matched_against_lost_probes() {
+        let mut processor = processor();
+        processor.process_event(timeout(1));
+        processor.process_event(timeout(2));
+        let row = processor.process_event(late(1)).unwrap();
+        assert_eq!((row.late, row.late_rtt), (1, Some(1500.0)));
+        assert_eq!(row.lost_seqs, [2]);
+        // the probe stays lost
+        assert_eq!((row.timeout, row.received), (2, 0));
+        // nothing replied after it, so it is not out of order
+        assert_eq!(row.reordered, 0);
+
+        // a second reply to the same probe, or one to a probe never lost, is dropped
+        assert!(processor.process_event(late(1)).is_none());
+        assert!(processor.process_event(late(9)).is_none());
+    }
+
+    #[test]
+    fn late_replies_behind_a_later_reply_are_reordered() {
+        let mut processor = processor();
+        processor.process_event(timeout(65535));
+        processor.process_event(success(0));
+        let row = processor.process_event(late(65535)).unwrap();
+        assert_eq!((row.late, row.reordered), (1, 1));
+        assert_eq!(row.last_reply_seq, Some(0));
+
+        // an older success doesn't move the latest reply back
+        processor.process_event(timeout(1));
+        processor.process_event(success(2));
+        let row = processor.process_event(success(65534)).unwrap();
+        assert_eq!(row.last_reply_seq, Some(2));
+        let row = processor.process_event(late(1)).unwrap();
+        assert_eq!((row.late, row.reordered), (2, 2));
+    }
+
+    #[test]
+    fn only_the_last_lost_probes_are_remembered() {
+        let mut processor = processor();
+        for seq in 0..LOST_SEQS as u16 + 1 {
+            processor.process_event(timeout(seq));
+        }
+        assert!(processor.process_event(late(0)).is_none());
+        assert_eq!(processor.process_event(late(1)).unwrap().late, 1);
+    }
+
+    #[test]
+    fn duplicates_are_counted_without_touching_the_probe() {
+        let mut processor = processor();
+        processor.process_event(success(4));
+        let row = processor.process_event(duplicate(4)).unwrap();
+        assert_eq!((row.duplicates, row.duplicate_seq), (1, Some(4)));
+        assert_eq!((row.received, row.timeout), (1, 0));
+    }
+}
@@ -1,11 +1,13 @@
 use ratatui::backend::{Backend, CrosstermBackend};
 use ratatui::{Terminal};
-use crate::ip_data::IpData;
+use crate::ip_data::{group_stats, IpData};
 use std::io::{self, Stdout};
 use std::error::Error;
 use ratatui::crossterm::execute;
 use ratatui::crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
-use crate::ui::{draw_graph_view, draw_point_view, draw_table_view, draw_sparkline_view};
+use crate::ui::{draw_graph_view, draw_point_view, draw_table_view, draw_sparkline_view, draw_hops_view, draw_sweep_view, draw_prompt, draw_window, draw_outages, draw_bursts, BurstPane, ExtraColumns, GroupView, OutagePane, TargetPrompt, TargetSelection};
+use crate::network::ProbeTasks;
+use crate::stats::StatsWindow;
 use std::sync::{Arc, Mutex, mpsc};
 use std::time::Duration;
 use ratatui::crossterm::event;
@@ -40,30 +42,50 @@ pub fn draw_interface<B: Backend>(
     terminal: &mut Terminal<B>,
     view_type: &str,
     ip_data: &[IpData],
+    group_view: &GroupView,
+    selection: &TargetSelection,
+    columns: &ExtraColumns,
+    window: StatsWindow,
+    outages: &OutagePane,
+    bursts: &BurstPane,
+    prompt: &TargetPrompt,
     errs: &[String],
     lang: &str,
 ) -> Result<(), Box<dyn Error>> {
     terminal.draw(|f| {
         match view_type {
             "graph" => {
-                draw_graph_view::<B>(f, ip_data, errs);
+                draw_graph_view::<B>(f, ip_data, window, errs);
             }
             "table" => {
                 let size = f.area();
-                draw_table_view::<B>(f, ip_data, errs, size, lang);
+                let groups = group_stats(ip_data, window);
+                draw_table_view::<B>(f, ip_data, &groups, group_view, selection, columns, window, errs, size, lang);
             }
             "point" => {
                 let size = f.area();
-                draw_point_view::<B>(f, ip_data, errs, size, lang);
+                draw_point_view::<B>(f, ip_data, window, errs, size, lang);
             }
             "sparkline" => {
                 let size = f.area();
-                draw_sparkline_view::<B>(f, ip_data, errs, size, lang);
+                draw_sparkline_view::<B>(f, ip_data, window, errs, size, lang);
+            }
+            "hops" => {
+                let size = f.area();
+                draw_hops_view::<B>(f, ip_data, window, errs, size, lang);
+            }
+            "sweep" => {
+                let size = f.area();
+                draw_sweep_view::<B>(f, ip_data, window, errs, size, lang);
             }
             _ => {
-                draw_graph_view::<B>(f, ip_data, errs);
+                draw_graph_view::<B>(f, ip_data, window, errs);
             }
         }
+        draw_window(f, window);
+        draw_outages(f, outages, ip_data, lang);
+        draw_bursts(f, bursts, ip_data, lang);
+        draw_prompt(f, prompt, lang);
     })?;
     Ok(())
 }
@@ -73,10 +95,12 @@ pub fn draw_interface_with_updates<B: Backend>(
     terminal: &mut Terminal<B>,
     view_type: &Arc<String>,
     ip_data: &Arc<Mutex<Vec<IpData>>>,
+    probes: &Arc<Mutex<ProbeTasks>>,
     ping_update_rx: mpsc::Receiver<IpData>,
     running: Arc<Mutex<bool>>,
     errs: Arc<Mutex<Vec<String>>>,
     output_file: Option<String>,
+    columns: ExtraColumns,
     lang: &str,
 ) -> Result<(), Box<dyn Error>> {
     let mut output_file_handle = if let Some(ref output_path) = output_file {
@@ -96,6 +120,13 @@ pub fn draw_interface_with_updates<B: Backend>(
         None
     };
 
+    let mut group_view = GroupView::default();
+    let mut selection = TargetSelection::default();
+    let mut window = StatsWindow::default();
+    let mut outages = OutagePane::default();
+    let mut bursts = BurstPane::default();
+    let mut prompt = TargetPrompt::default();
+
     loop {
         if !*running.lock().unwrap() {
             break Ok(());
@@ -104,16 +135,81 @@ pub fn draw_interface_with_updates<B: Backend>(
         // Check for keyboard events
         if let Ok(true) = event::poll(Duration::from_millis(50)) {
             if let Ok(Event::Key(key)) = event::read() {
-                match key.code {
-                    KeyCode::Char('q') | KeyCode::Esc => {
+                let redraw = match key.code {
+                    KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
                         *running.lock().unwrap() = false;
                         break Ok(());
                     },
-                    KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
+                    // while the prompt is open every key goes to it
+                    code if prompt.is_open() => {
+                        if let Some(target) = prompt.handle_key(code) {
+                            if let Err(err) = probes.lock().unwrap().add(&target) {
+                                errs.lock().unwrap().push(format!("add target({}) err: {}", target, err));
+                            }
+                        }
+                        true
+                    },
+                    KeyCode::Char('q') | KeyCode::Esc => {
                         *running.lock().unwrap() = false;
                         break Ok(());
                     },
-                    _ => {}
+                    // the hops view is built from the paths found at startup
+                    KeyCode::Char('a') if view_type.as_str() != "hops" => {
+                        prompt.open();
+                        true
+                    },
+                    KeyCode::Char('w') => {
+                        window = window.next();
+                        true
+                    },
+                    // one detail pane at a time
+                    code @ KeyCode::Char('o') => {
+                        bursts.shown = false;
+                        outages.handle_key(code)
+                    },
+                    code @ KeyCode::Char('b') => {
+                        outages.shown = false;
+                        bursts.handle_key(code)
+                    },
+                    KeyCode::Char('r') => {
+                        // the data processor resets its rows too, later updates carry on from there
+                        for data in ip_data.lock().unwrap().iter_mut() {
+                            data.stats.reset();
+                        }
+                        probes.lock().unwrap().reset_statistics();
+                        true
+                    },
+                    KeyCode::Char('d') if view_type.as_str() == "table" && !(group_view.collapsed && !group_stats(&ip_data.lock().unwrap(), window).is_empty()) => {
+                        // the rows go away once the data processor dropped them
+                        if let Some(data) = selection.selected(&ip_data.lock().unwrap()) {
+                            probes.lock().unwrap().remove(&data.addr);
+                        }
+                        false
+                    },
+                    code if view_type.as_str() == "table" => {
+                        let ip_data = ip_data.lock().unwrap();
+                        let groups = group_stats(&ip_data, window);
+                        let collapsed = group_view.collapsed && !groups.is_empty();
+                        group_view.handle_key(code, &groups)
+                            || (!collapsed && selection.handle_key(code, &ip_data, window))
+                    },
+                    _ => false,
+                };
+                if redraw {
+                    draw_interface(
+                        terminal,
+                        view_type,
+                        &ip_data.lock().unwrap(),
+                        &group_view,
+                        &selection,
+                        &columns,
+                        window,
+                        &outages,
+                        &bursts,
+                        &prompt,
+                        &errs.lock().unwrap(),
+                        lang,
+                    ).ok();
                 }
             }
         }
@@ -123,13 +219,24 @@ pub fn draw_interface_with_updates<B: Backend>(
 
             let last_attr = updated_data.last_attr.clone();
             let addr = updated_data.addr.clone();
-            let ip = updated_data.ip.clone();
+            let ip = updated_data.display_ip();
+            let position = ip_data.iter().position(|d| d.addr == updated_data.addr && d.ip == updated_data.ip && d.source == updated_data.source);
+            // only probe outcomes are logged, not path, mtu, address or stray reply updates
+            let probed = !updated_data.removed && match position {
+                Some(pos) => ip_data[pos].probes() != updated_data.probes(),
+                None => updated_data.probes() > 0,
+            };
 
-            if let Some(pos) = ip_data.iter().position(|d| d.addr == updated_data.addr && d.ip == updated_data.ip) {
+            if updated_data.removed {
+                ip_data.retain(|d| d.addr != updated_data.addr);
+            } else if let Some(pos) = position {
                 ip_data[pos] = updated_data;
+            } else {
+                // a hostname that moved to a new address or a target added while running
+                ip_data.push(updated_data);
             }
 
-            if let Some(ref mut file) = output_file_handle {
+            if let Some(file) = output_file_handle.as_mut().filter(|_| probed) {
                 use std::io::Write;
 
                 let latency_str = if last_attr == -1.0 {
@@ -152,6 +259,13 @@ pub fn draw_interface_with_updates<B: Backend>(
                 terminal,
                 view_type,
                 &ip_data,
+                &group_view,
+                &selection,
+                &columns,
+                window,
+                &outages,
+                &bursts,
+                &prompt,
                 &mut errs.lock().unwrap(),
                 lang,
             ).ok();
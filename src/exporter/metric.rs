@@ -1,5 +1,77 @@
-use prometheus::{CounterVec, HistogramVec, HistogramOpts, Opts, Registry, TextEncoder};
+use prometheus::core::{Collector, MetricVec, MetricVecBuilder};
+use prometheus::{CounterVec, GaugeVec, HistogramVec, HistogramOpts, Opts, Registry, TextEncoder};
+use std::collections::HashMap;
+use std::net::IpAddr;
 use std::sync::Arc;
+use std::time::Duration;
+use crate::config::split_tag;
+use crate::probe::{rcode_name, DnsAnswer, HttpCheck, HttpTiming, PacketParams, Source, StrayReply};
+use crate::stats::{Aggregate, SessionPercentiles, PERCENTILES};
+
+/// Labels every series of a probed target starts with, followed by one label per tag key
+const TARGET_LABELS: [&str; 4] = ["target", "ip", "source", "af"];
+
+/// Label values identifying one probed target
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetLabels {
+    pub target: String,
+    pub ip: String,
+    /// source address or interface, empty for the default route
+    pub source: String,
+    /// `key=value` tags of the target
+    pub tags: Vec<String>,
+}
+
+impl TargetLabels {
+    pub fn new(target: &str, ip: &str, source: Option<&Source>, tags: &[String]) -> Self {
+        Self {
+            target: target.to_string(),
+            ip: ip.to_string(),
+            source: source.map(Source::to_string).unwrap_or_default(),
+            tags: tags.to_vec(),
+        }
+    }
+
+    // address family of the probed ip, tells the two series of a --dual-stack target apart
+    fn af(&self) -> &'static str {
+        match self.ip.parse::<IpAddr>() {
+            Ok(IpAddr::V6(_)) => "ipv6",
+            _ => "ipv4",
+        }
+    }
+
+    // value of a tag, empty when the target doesn't have it
+    fn tag(&self, key: &str) -> &str {
+        self.tags.iter().map(|tag| split_tag(tag)).find(|(k, _)| *k == key).map_or("", |(_, value)| value)
+    }
+}
+
+// the target and tag label names followed by `extra`
+fn label_names<'a>(tag_keys: &'a [String], extra: &[&'a str]) -> Vec<&'a str> {
+    let mut names = TARGET_LABELS.to_vec();
+    names.extend(tag_keys.iter().map(String::as_str));
+    names.extend_from_slice(extra);
+    names
+}
+
+// removes every series of `vec` belonging to the target
+fn remove_series<T: MetricVecBuilder>(vec: &MetricVec<T>, labels: &TargetLabels) {
+    for family in vec.collect() {
+        for metric in family.get_metric() {
+            let values: HashMap<&str, &str> = metric
+                .get_label()
+                .iter()
+                .map(|pair| (pair.get_name(), pair.get_value()))
+                .collect();
+            if values.get("target") == Some(&labels.target.as_str())
+                && values.get("ip") == Some(&labels.ip.as_str())
+                && values.get("source") == Some(&labels.source.as_str())
+            {
+                let _ = vec.remove(&values);
+            }
+        }
+    }
+}
 
 /// Prometheus metrics collector
 #[derive(Debug, Clone)]
@@ -8,13 +80,43 @@ pub struct PrometheusMetrics {
     ping_duration_histogram: HistogramVec,
     /// Total number of ping requests (grouped by status)
     ping_requests_total: CounterVec,
+    /// HTTP probe phase latency histogram (dns/connect/tls/ttfb/total)
+    http_phase_histogram: HistogramVec,
+    /// HTTP probe responses (grouped by status code)
+    http_responses_total: CounterVec,
+    /// DNS probe responses (grouped by rcode)
+    dns_responses_total: CounterVec,
+    /// Route changes found by periodic path re-tracing
+    path_changes_total: CounterVec,
+    /// Last discovered path MTU in bytes
+    path_mtu_bytes: GaugeVec,
+    /// Packet settings each target is probed with, always 1
+    probe_params: GaugeVec,
+    /// Failed re-resolutions of the target's hostname
+    resolve_errors_total: CounterVec,
+    /// Estimated rtt percentiles over every reply since the target was started
+    rtt_percentile_seconds: GaugeVec,
+    /// RFC 3550 jitter over every reply since the target was started
+    jitter_seconds: GaugeVec,
+    /// Standard deviation of the rtt over every reply since the target was started
+    rtt_stddev_seconds: GaugeVec,
+    /// Estimated call quality as a mean opinion score, 1 to 4.5
+    mos: GaugeVec,
+    /// E-model transmission rating behind the mos, 0 to 93.2
+    r_factor: GaugeVec,
+    /// Runs of lost probes long enough to count as outages
+    outages_total: CounterVec,
+    /// Time spent in outages, the ongoing one included
+    outage_seconds_total: CounterVec,
+    /// Tag keys that became labels, fixed once the metrics are registered
+    tag_keys: Vec<String>,
     /// Prometheus registry
     registry: Arc<Registry>,
 }
 
 impl PrometheusMetrics {
-    /// Creates a new Prometheus metrics collector
-    pub fn new() -> Result<Self, prometheus::Error> {
+    /// Creates a new Prometheus metrics collector, every series gets a label per tag key
+    pub fn new(tag_keys: Vec<String>) -> Result<Self, prometheus::Error> {
         // Create registry
         let registry = Arc::new(Registry::new());
 
@@ -30,7 +132,7 @@ impl PrometheusMetrics {
                 "Histogram of ping durations in seconds",
             )
                 .buckets(buckets),
-            &["target", "ip"], // label names
+            &label_names(&tag_keys, &[]), // label names
         )?;
 
         // Create counter for total ping requests
@@ -39,48 +141,396 @@ impl PrometheusMetrics {
                 "nbping_ping_requests_total",
                 "Total number of ping requests",
             ),
-            &["target", "ip", "status"],
+            &label_names(&tag_keys, &["status"]),
+        )?;
+
+        // Create histogram for the phases of HTTP probes
+        let http_phase_histogram = HistogramVec::new(
+            HistogramOpts::new(
+                "nbping_http_phase_duration_seconds",
+                "Histogram of HTTP probe phase durations in seconds",
+            )
+                .buckets(vec![0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0, 10.0]),
+            &label_names(&tag_keys, &["phase"]),
+        )?;
+
+        // Create counter for HTTP probe responses
+        let http_responses_total = CounterVec::new(
+            Opts::new(
+                "nbping_http_responses_total",
+                "Total number of HTTP probe responses by status code",
+            ),
+            &label_names(&tag_keys, &["code"]),
+        )?;
+
+        // Create counter for DNS probe responses
+        let dns_responses_total = CounterVec::new(
+            Opts::new(
+                "nbping_dns_responses_total",
+                "Total number of DNS probe responses by rcode",
+            ),
+            &label_names(&tag_keys, &["rcode"]),
+        )?;
+
+        // Create counter for path changes
+        let path_changes_total = CounterVec::new(
+            Opts::new(
+                "nbping_path_changes_total",
+                "Total number of detected changes of the router path to a target",
+            ),
+            &label_names(&tag_keys, &[]),
+        )?;
+
+        // Create gauge for the path MTU
+        let path_mtu_bytes = GaugeVec::new(
+            Opts::new(
+                "nbping_path_mtu_bytes",
+                "Largest packet size in bytes that reaches the target unfragmented",
+            ),
+            &label_names(&tag_keys, &[]),
+        )?;
+
+        // Create info gauge carrying the packet settings as labels
+        let probe_params = GaugeVec::new(
+            Opts::new(
+                "nbping_probe_params",
+                "Packet size, TTL, DSCP and DF setting each target is probed with",
+            ),
+            &label_names(&tag_keys, &["size", "ttl", "dscp", "df"]),
+        )?;
+
+        // Create counter for failed hostname lookups
+        let resolve_errors_total = CounterVec::new(
+            Opts::new(
+                "nbping_resolve_errors_total",
+                "Total number of failed re-resolutions of a target's hostname",
+            ),
+            &label_names(&tag_keys, &[]),
+        )?;
+
+        // Create gauge for the streaming rtt percentile estimates
+        let rtt_percentile_seconds = GaugeVec::new(
+            Opts::new(
+                "nbping_rtt_percentile_seconds",
+                "Estimated round trip time percentiles in seconds over every reply since the target was started",
+            ),
+            &label_names(&tag_keys, &["quantile"]),
+        )?;
+
+        // Create gauges for the rtt variation
+        let jitter_seconds = GaugeVec::new(
+            Opts::new(
+                "nbping_jitter_seconds",
+                "RFC 3550 interarrival jitter in seconds over every reply since the target was started",
+            ),
+            &label_names(&tag_keys, &[]),
+        )?;
+        let rtt_stddev_seconds = GaugeVec::new(
+            Opts::new(
+                "nbping_rtt_stddev_seconds",
+                "Standard deviation of the round trip time in seconds over every reply since the target was started",
+            ),
+            &label_names(&tag_keys, &[]),
+        )?;
+
+        // Create gauges for the estimated call quality
+        let mos = GaugeVec::new(
+            Opts::new(
+                "nbping_mos",
+                "Estimated mean opinion score of a voice call, from rtt, jitter and loss since the target was started",
+            ),
+            &label_names(&tag_keys, &[]),
+        )?;
+        let r_factor = GaugeVec::new(
+            Opts::new(
+                "nbping_r_factor",
+                "E-model R-factor of a voice call, from rtt, jitter and loss since the target was started",
+            ),
+            &label_names(&tag_keys, &[]),
+        )?;
+
+        // Create counters for outages
+        let outages_total = CounterVec::new(
+            Opts::new(
+                "nbping_outages_total",
+                "Total number of outages, runs of consecutive lost probes, of a target",
+            ),
+            &label_names(&tag_keys, &[]),
+        )?;
+        let outage_seconds_total = CounterVec::new(
+            Opts::new(
+                "nbping_outage_seconds_total",
+                "Total seconds a target was in an outage, including the ongoing one",
+            ),
+            &label_names(&tag_keys, &[]),
         )?;
 
         // Register metrics
         registry.register(Box::new(ping_duration_histogram.clone()))?;
         registry.register(Box::new(ping_requests_total.clone()))?;
+        registry.register(Box::new(http_phase_histogram.clone()))?;
+        registry.register(Box::new(http_responses_total.clone()))?;
+        registry.register(Box::new(dns_responses_total.clone()))?;
+        registry.register(Box::new(path_changes_total.clone()))?;
+        registry.register(Box::new(path_mtu_bytes.clone()))?;
+        registry.register(Box::new(probe_params.clone()))?;
+        registry.register(Box::new(resolve_errors_total.clone()))?;
+        registry.register(Box::new(rtt_percentile_seconds.clone()))?;
+        registry.register(Box::new(jitter_seconds.clone()))?;
+        registry.register(Box::new(rtt_stddev_seconds.clone()))?;
+        registry.register(Box::new(mos.clone()))?;
+        registry.register(Box::new(r_factor.clone()))?;
+        registry.register(Box::new(outages_total.clone()))?;
+        registry.register(Box::new(outage_seconds_total.clone()))?;
 
         Ok(Self {
             ping_duration_histogram,
             ping_requests_total,
+            http_phase_histogram,
+            http_responses_total,
+            dns_responses_total,
+            path_changes_total,
+            path_mtu_bytes,
+            probe_params,
+            resolve_errors_total,
+            rtt_percentile_seconds,
+            jitter_seconds,
+            rtt_stddev_seconds,
+            mos,
+            r_factor,
+            outages_total,
+            outage_seconds_total,
+            tag_keys,
             registry,
         })
     }
 
+    /// Tag keys the series are labelled with
+    pub fn tag_keys(&self) -> &[String] {
+        &self.tag_keys
+    }
+
+    // the label values of a target in the order of `label_names`
+    fn values<'a>(&'a self, labels: &'a TargetLabels, extra: &[&'a str]) -> Vec<&'a str> {
+        let mut values = vec![labels.target.as_str(), labels.ip.as_str(), labels.source.as_str(), labels.af()];
+        values.extend(self.tag_keys.iter().map(|key| labels.tag(key)));
+        values.extend_from_slice(extra);
+        values
+    }
+
     /// Records a successful ping (records to histogram)
-    pub fn record_ping_success(&self, target: &str, ip: &str, rtt_ms: f64) {
+    pub fn record_ping_success(&self, labels: &TargetLabels, rtt_ms: f64) {
         let rtt_seconds = rtt_ms / 1000.0;
 
         self.ping_requests_total
-            .with_label_values(&[target, ip, "success"])
+            .with_label_values(&self.values(labels, &["success"]))
             .inc();
 
         // Add labels to histogram and observe value
         self.ping_duration_histogram
-            .with_label_values(&[target, ip])
+            .with_label_values(&self.values(labels, &[]))
             .observe(rtt_seconds);
     }
 
     /// Records a timed-out ping (not recorded in histogram, but other metrics can be added here)
-    pub fn record_ping_timeout(&self, target: &str, ip: &str) {
+    pub fn record_ping_timeout(&self, labels: &TargetLabels) {
+        self.ping_requests_total
+            .with_label_values(&self.values(labels, &["timeout"]))
+            .inc();
+    }
+
+    /// Records a probe the target actively refused (e.g. TCP connection reset)
+    pub fn record_ping_refused(&self, labels: &TargetLabels) {
+        self.ping_requests_total
+            .with_label_values(&self.values(labels, &["refused"]))
+            .inc();
+    }
+
+    /// Records a completed HTTP probe, failed checks are counted under the `check_failed` status
+    pub fn record_http(&self, labels: &TargetLabels, timing: &HttpTiming, check: HttpCheck) {
+        let phases = [
+            ("dns", timing.dns),
+            ("connect", timing.connect),
+            ("tls", timing.tls),
+            ("ttfb", timing.ttfb),
+            ("total", timing.total),
+        ];
+        for (phase, duration) in phases {
+            self.http_phase_histogram
+                .with_label_values(&self.values(labels, &[phase]))
+                .observe(duration.as_secs_f64());
+        }
+
+        self.http_responses_total
+            .with_label_values(&self.values(labels, &[&timing.status.to_string()]))
+            .inc();
+
+        if check == HttpCheck::Passed {
+            self.record_ping_success(labels, timing.total.as_secs_f64() * 1000.0);
+        } else {
+            self.ping_requests_total
+                .with_label_values(&self.values(labels, &["check_failed"]))
+                .inc();
+        }
+    }
+
+    /// Records a DNS answer, SERVFAIL/REFUSED and the like are counted under `check_failed`
+    pub fn record_dns(&self, labels: &TargetLabels, rtt_ms: f64, answer: DnsAnswer) {
+        self.dns_responses_total
+            .with_label_values(&self.values(labels, &[&rcode_name(answer.rcode)]))
+            .inc();
+
+        if answer.is_ok() {
+            self.record_ping_success(labels, rtt_ms);
+        } else {
+            self.ping_requests_total
+                .with_label_values(&self.values(labels, &["check_failed"]))
+                .inc();
+        }
+    }
+
+    /// Records duplicated and reordered UDP echoes under their own statuses
+    pub fn record_udp_anomalies(&self, labels: &TargetLabels, duplicates: u16, reordered: u16) {
+        if duplicates > 0 {
+            self.ping_requests_total
+                .with_label_values(&self.values(labels, &["duplicate"]))
+                .inc_by(duplicates as f64);
+        }
+        if reordered > 0 {
+            self.ping_requests_total
+                .with_label_values(&self.values(labels, &["reordered"]))
+                .inc_by(reordered as f64);
+        }
+    }
+
+    /// Records a probe answered with an ICMP destination unreachable
+    pub fn record_ping_unreachable(&self, labels: &TargetLabels) {
         self.ping_requests_total
-            .with_label_values(&[target, ip, "timeout"])
+            .with_label_values(&self.values(labels, &["unreachable"]))
+            .inc();
+    }
+
+    /// Records a reply to an earlier probe, under the `duplicate` or `late` status.
+    /// It comes on top of the success or timeout its probe was already counted as.
+    pub fn record_stray(&self, labels: &TargetLabels, stray: StrayReply) {
+        let status = match stray {
+            StrayReply::Duplicate { .. } => "duplicate",
+            StrayReply::Late { .. } => "late",
+        };
+        self.ping_requests_total
+            .with_label_values(&self.values(labels, &[status]))
+            .inc();
+    }
+
+    /// Records a change of the router path to a target
+    pub fn record_path_change(&self, labels: &TargetLabels) {
+        self.path_changes_total
+            .with_label_values(&self.values(labels, &[]))
+            .inc();
+    }
+
+    /// Records the discovered path MTU of a target
+    pub fn record_pmtu(&self, labels: &TargetLabels, mtu: u16) {
+        self.path_mtu_bytes
+            .with_label_values(&self.values(labels, &[]))
+            .set(mtu as f64);
+    }
+
+    /// Publishes the packet settings of a target, unset ones are labelled `default`
+    pub fn record_probe_params(&self, labels: &TargetLabels, params: &PacketParams) {
+        let label = |value: Option<String>| value.unwrap_or_else(|| String::from("default"));
+        let size = label(params.size.map(|v| v.to_string()));
+        let ttl = label(params.ttl.map(|v| v.to_string()));
+        let dscp = label(params.dscp.map(|v| v.to_string()));
+        let df = label(params.df.map(|v| v.to_string()));
+        self.probe_params
+            .with_label_values(&self.values(labels, &[&size, &ttl, &dscp, &df]))
+            .set(1.0);
+    }
+
+    /// Publishes the current percentile estimates, labelled by quantile such as `0.95`
+    pub fn record_percentiles(&self, labels: &TargetLabels, percentiles: &SessionPercentiles) {
+        for (index, (_, quantile)) in PERCENTILES.iter().enumerate() {
+            if let Some(rtt_ms) = percentiles.get(index) {
+                self.rtt_percentile_seconds
+                    .with_label_values(&self.values(labels, &[&quantile.to_string()]))
+                    .set(rtt_ms / 1000.0);
+            }
+        }
+    }
+
+    /// Publishes the jitter and standard deviation of the replies so far
+    pub fn record_variation(&self, labels: &TargetLabels, session: &Aggregate) {
+        self.jitter_seconds
+            .with_label_values(&self.values(labels, &[]))
+            .set(session.jitter() / 1000.0);
+        self.rtt_stddev_seconds
+            .with_label_values(&self.values(labels, &[]))
+            .set(session.stddev() / 1000.0);
+    }
+
+    /// Publishes the estimated call quality, nothing until the first reply
+    pub fn record_quality(&self, labels: &TargetLabels, session: &Aggregate) {
+        if let Some(quality) = session.quality() {
+            self.mos
+                .with_label_values(&self.values(labels, &[]))
+                .set(quality.mos);
+            self.r_factor
+                .with_label_values(&self.values(labels, &[]))
+                .set(quality.r_factor);
+        }
+    }
+
+    /// Counts an outage when it starts
+    pub fn record_outage(&self, labels: &TargetLabels) {
+        self.outages_total
+            .with_label_values(&self.values(labels, &[]))
+            .inc();
+    }
+
+    /// Adds time spent in an outage, counted while it lasts
+    pub fn record_outage_time(&self, labels: &TargetLabels, time: Duration) {
+        if !time.is_zero() {
+            self.outage_seconds_total
+                .with_label_values(&self.values(labels, &[]))
+                .inc_by(time.as_secs_f64());
+        }
+    }
+
+    /// Records a failed lookup of the target's hostname
+    pub fn record_resolve_error(&self, labels: &TargetLabels) {
+        self.resolve_errors_total
+            .with_label_values(&self.values(labels, &[]))
             .inc();
     }
 
     /// Records a failed ping
-    pub fn record_ping_error(&self, target: &str, ip: &str) {
+    pub fn record_ping_error(&self, labels: &TargetLabels) {
         self.ping_requests_total
-            .with_label_values(&[target, ip, "error"])
+            .with_label_values(&self.values(labels, &["error"]))
             .inc();
     }
 
+    /// Drops all series of a target that is no longer probed
+    pub fn remove_target(&self, labels: &TargetLabels) {
+        remove_series(&self.ping_duration_histogram, labels);
+        remove_series(&self.ping_requests_total, labels);
+        remove_series(&self.http_phase_histogram, labels);
+        remove_series(&self.http_responses_total, labels);
+        remove_series(&self.dns_responses_total, labels);
+        remove_series(&self.path_changes_total, labels);
+        remove_series(&self.path_mtu_bytes, labels);
+        remove_series(&self.probe_params, labels);
+        remove_series(&self.resolve_errors_total, labels);
+        remove_series(&self.rtt_percentile_seconds, labels);
+        remove_series(&self.jitter_seconds, labels);
+        remove_series(&self.rtt_stddev_seconds, labels);
+        remove_series(&self.mos, labels);
+        remove_series(&self.r_factor, labels);
+        remove_series(&self.outages_total, labels);
+        remove_series(&self.outage_seconds_total, labels);
+    }
+
     /// Gets metrics data in Prometheus format
     pub fn gather(&self) -> String {
         let encoder = TextEncoder::new();
@@ -96,7 +546,7 @@ impl PrometheusMetrics {
 
 impl Default for PrometheusMetrics {
     fn default() -> Self {
-        Self::new().expect("Failed to create PrometheusMetrics")
+        Self::new(Vec::new()).expect("Failed to create PrometheusMetrics")
     }
 }
 
@@ -1,6 +1,6 @@
 mod metric;
 mod runner;
 
-pub use metric::PrometheusMetrics;
+pub use metric::{PrometheusMetrics, TargetLabels};
 pub use metric::http_server;
-pub use runner::spawn_ping_workers;
+pub use runner::{watch_dns, ProbeTarget, Workers};
@@ -1,70 +1,486 @@
-use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
 use std::thread;
-use std::time::Duration;
+use std::net::IpAddr;
+use std::time::{Duration, Instant, SystemTime};
 
-use pinger::{ping, PingOptions, PingResult};
+use crate::exporter::{PrometheusMetrics, TargetLabels};
+use crate::network::{self, PathTracker};
+use crate::probe::{packet_params, parse_target, HttpCheck, Prober, ProbeOptions, ProbeResult, Source};
+use crate::outage::{OutageChange, OutageLog};
+use crate::stats::{Aggregate, SessionPercentiles};
 
-use crate::exporter::PrometheusMetrics;
+/// One probed combination of target, resolved address and source
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeTarget {
+    /// the target as given, e.g. `tcp://db01:5432`
+    pub addr: String,
+    pub ip: String,
+    pub source: Option<Source>,
+    /// configured display name, used as `target` label instead of `addr`
+    pub name: Option<String>,
+    /// overrides the global interval
+    pub interval: Option<Duration>,
+    /// `key=value` tags, exported as labels
+    pub tags: Vec<String>,
+}
+
+impl ProbeTarget {
+    fn labels(&self) -> TargetLabels {
+        TargetLabels::new(self.name.as_deref().unwrap_or(&self.addr), &self.ip, self.source.as_ref(), &self.tags)
+    }
+}
 
-pub fn spawn_ping_workers(
-    targets: Vec<(String, String)>,
+/// The probe threads of every exporter target. Targets are started and stopped one by
+/// one, so a reload leaves the threads and series of unchanged targets alone.
+pub struct Workers {
     interval: Duration,
+    options: ProbeOptions,
+    metrics: Arc<PrometheusMetrics>,
+    workers: Vec<Worker>,
+}
+
+// the ping thread of one target plus its path and pmtu watchers
+struct Worker {
+    target: ProbeTarget,
     running: Arc<AtomicBool>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+/// Workers told to stop by [`Workers::reload`] or [`Workers::readdress`]. A path trace
+/// or pmtu search can take a while to notice, so they are joined once the `Workers`
+/// lock is released.
+#[must_use = "stopped workers have to be joined"]
+pub struct Retired {
+    workers: Vec<Worker>,
+    // series of targets that are gone, dropped once no thread can write them anymore
+    gone: Vec<TargetLabels>,
     metrics: Arc<PrometheusMetrics>,
-) -> Vec<thread::JoinHandle<()>> {
-    targets
-        .into_iter()
-        .map(|(addr, ip)| {
-            let running = running.clone();
-            let metrics = metrics.clone();
-            let interval = interval;
-            thread::spawn(move || run_ping_loop(addr, ip, interval, running, metrics))
-        })
-        .collect()
+}
+
+impl Retired {
+    /// Number of stopped targets
+    pub fn count(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Waits for the threads to finish, then drops the series of targets that are gone
+    pub fn join(self) {
+        for worker in self.workers {
+            worker.join();
+        }
+        for labels in &self.gone {
+            self.metrics.remove_target(labels);
+        }
+    }
+}
+
+impl Workers {
+    pub fn new(interval: Duration, options: ProbeOptions, metrics: Arc<PrometheusMetrics>) -> Self {
+        Self {
+            interval,
+            options,
+            metrics,
+            workers: Vec::new(),
+        }
+    }
+
+    /// Starts probing `target`
+    pub fn start(&mut self, target: ProbeTarget) {
+        let running = Arc::new(AtomicBool::new(true));
+        let mut threads = Vec::new();
+
+        let interval = target.interval.unwrap_or(self.interval);
+        let (ping_target, ping_running, metrics, options) =
+            (target.clone(), running.clone(), self.metrics.clone(), self.options.clone());
+        threads.push(thread::spawn(move || run_ping_loop(ping_target, interval, ping_running, metrics, options)));
+
+        if !self.options.trace_interval.is_zero() {
+            let (every, max_hops) = (self.options.trace_interval, self.options.max_hops);
+            let (path_target, path_running, metrics) = (target.clone(), running.clone(), self.metrics.clone());
+            threads.push(thread::spawn(move || run_path_watch_loop(path_target, every, max_hops, path_running, metrics)));
+        }
+
+        if !self.options.pmtu_interval.is_zero() {
+            let every = self.options.pmtu_interval;
+            let (pmtu_target, pmtu_running, metrics) = (target.clone(), running.clone(), self.metrics.clone());
+            threads.push(thread::spawn(move || run_pmtu_watch_loop(pmtu_target, every, pmtu_running, metrics)));
+        }
+
+        self.workers.push(Worker { target, running, threads });
+    }
+
+    /// Switches to a new target set: stops the targets missing from `targets` and starts
+    /// the new ones. Returns the number started and the stopped workers, whose series
+    /// nobody reports anymore are dropped when they are joined.
+    pub fn reload(&mut self, targets: Vec<ProbeTarget>) -> (usize, Retired) {
+        let (kept, removed): (Vec<Worker>, Vec<Worker>) =
+            self.workers.drain(..).partition(|worker| targets.contains(&worker.target));
+        self.workers = kept;
+
+        let mut gone = Vec::new();
+        for worker in &removed {
+            worker.running.store(false, Ordering::Relaxed);
+            let labels = worker.target.labels();
+            // a target whose interval changed keeps its labels and counters
+            if !targets.iter().any(|target| target.labels() == labels) {
+                gone.push(labels);
+            }
+        }
+
+        let mut started = 0;
+        for target in targets {
+            if !self.workers.iter().any(|worker| worker.target == target) {
+                self.start(target);
+                started += 1;
+            }
+        }
+        (started, Retired { workers: removed, gone, metrics: self.metrics.clone() })
+    }
+
+    /// The targets currently probed
+    pub fn targets(&self) -> Vec<ProbeTarget> {
+        self.workers.iter().map(|worker| worker.target.clone()).collect()
+    }
+
+    /// Moves a target to a new address. The series of the old address are dropped
+    /// once its worker is joined, the new address starts its own.
+    pub fn readdress(&mut self, target: &ProbeTarget, ip: String) -> Retired {
+        let mut workers = Vec::new();
+        let mut gone = Vec::new();
+        if let Some(pos) = self.workers.iter().position(|worker| worker.target == *target) {
+            let worker = self.workers.remove(pos);
+            worker.running.store(false, Ordering::Relaxed);
+            gone.push(worker.target.labels());
+            workers.push(worker);
+            self.start(ProbeTarget { ip, ..target.clone() });
+        }
+        Retired { workers, gone, metrics: self.metrics.clone() }
+    }
+
+    /// Stops every target and waits for the threads to finish
+    pub fn shutdown(&mut self) {
+        for worker in &self.workers {
+            worker.running.store(false, Ordering::Relaxed);
+        }
+        for worker in self.workers.drain(..) {
+            worker.join();
+        }
+    }
+}
+
+impl Worker {
+    fn join(self) {
+        for handle in self.threads {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Resolves the hostnames of all targets every `every` and moves targets whose name points
+/// somewhere else now. Failed lookups are logged and counted, the old address keeps being probed.
+pub fn watch_dns(
+    workers: Arc<Mutex<Workers>>,
+    metrics: Arc<PrometheusMetrics>,
+    every: Duration,
+    running: Arc<AtomicBool>,
+) {
+    loop {
+        network::sleep_while_running(every, || running.load(Ordering::Relaxed));
+        if !running.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let targets = workers.lock().unwrap().targets();
+        for target in targets.iter().filter(|target| network::is_hostname(&target.addr)) {
+            let Ok((host, _)) = parse_target(&target.addr) else {
+                continue;
+            };
+            match network::reresolve(&host, &target.ip) {
+                Ok(None) => {}
+                Ok(Some(ip)) => {
+                    println!("[{}] host({}) address changed: {} -> {}", network::timestamp(), host, target.ip, ip);
+                    // a separate statement, the lock guard would live until the end of a chained one
+                    let retired = workers.lock().unwrap().readdress(target, ip);
+                    retired.join();
+                }
+                Err(err) => {
+                    eprintln!("[{}] host({}) resolve err: {}", network::timestamp(), host, err);
+                    metrics.record_resolve_error(&target.labels());
+                }
+            }
+        }
+    }
 }
 
 fn run_ping_loop(
-    addr: String,
-    ip: String,
+    target: ProbeTarget,
     interval: Duration,
     running: Arc<AtomicBool>,
     metrics: Arc<PrometheusMetrics>,
+    options: ProbeOptions,
 ) {
-    let options = PingOptions::new(ip.clone(), interval, None);
-    let stream = match ping(options) {
-        Ok(stream) => stream,
+    let labels = target.labels();
+    let ProbeTarget { addr, ip, source, .. } = target;
+    let target_ip: IpAddr = match ip.parse() {
+        Ok(target_ip) => target_ip,
+        Err(err) => {
+            eprintln!("host({}) ping err, reason: invalid address, err: {}", ip, err);
+            return;
+        }
+    };
+
+    let mut prober = match Prober::new(&addr, target_ip, &options, source.as_ref()) {
+        Ok(prober) => prober,
         Err(err) => {
             eprintln!("host({}) ping err, reason: ping init failed, err: {}", ip, err);
             return;
         }
     };
+    if let Ok(params) = packet_params(&addr, &options) {
+        metrics.record_probe_params(&labels, &params);
+    }
 
+    let mut seq: u16 = 0;
+    // rtt percentiles and variation over every probe since the target was started
+    let mut percentiles = SessionPercentiles::default();
+    let mut session = Aggregate::default();
+    let mut outages = OutageLog::default();
+    // outage time already on the counter, for the outage the target is in
+    let mut outage_counted = Duration::ZERO;
     while running.load(Ordering::Relaxed) {
-        match stream.recv() {
-            Ok(PingResult::Pong(duration, _size)) => {
-                let rtt_ms = duration.as_secs_f64() * 1000.0;
-                metrics.record_ping_success(&addr, &ip, rtt_ms);
+        let started = Instant::now();
+        // rtt in ms of a probe that counts as a success
+        let result = prober.probe(seq, interval);
+        for stray in prober.take_strays() {
+            metrics.record_stray(&labels, stray);
+        }
+        let reply = match result {
+            Ok(ProbeResult::Reply { rtt, .. }) => {
+                let rtt_ms = rtt.as_secs_f64() * 1000.0;
+                metrics.record_ping_success(&labels, rtt_ms);
+                Some(rtt_ms)
+            }
+            Ok(ProbeResult::Timeout { .. }) => {
+                metrics.record_ping_timeout(&labels);
+                None
+            }
+            Ok(ProbeResult::Refused { .. }) => {
+                metrics.record_ping_refused(&labels);
+                None
             }
-            Ok(PingResult::Timeout(_)) => {
-                metrics.record_ping_timeout(&addr, &ip);
+            Ok(ProbeResult::Unreachable { .. }) => {
+                metrics.record_ping_unreachable(&labels);
+                None
             }
-            Ok(PingResult::PingExited(status, err)) => {
-                if status.code() != Some(0) {
-                    eprintln!(
-                        "host({}) ping err, reason: ping exited, status: {} err: {}",
-                        ip, err, status
-                    );
-                    metrics.record_ping_error(&addr, &ip);
+            Ok(ProbeResult::Dns { rtt, answer, .. }) => {
+                let rtt_ms = rtt.as_secs_f64() * 1000.0;
+                metrics.record_dns(&labels, rtt_ms, answer);
+                answer.is_ok().then_some(rtt_ms)
+            }
+            Ok(ProbeResult::Udp { rtt, duplicates, reordered, .. }) => {
+                let rtt_ms = rtt.map(|rtt| rtt.as_secs_f64() * 1000.0);
+                match rtt_ms {
+                    Some(rtt_ms) => metrics.record_ping_success(&labels, rtt_ms),
+                    None => metrics.record_ping_timeout(&labels),
                 }
+                metrics.record_udp_anomalies(&labels, duplicates, reordered);
+                rtt_ms
+            }
+            Ok(ProbeResult::Http { timing, check, .. }) => {
+                metrics.record_http(&labels, &timing, check);
+                (check == HttpCheck::Passed).then(|| timing.total.as_secs_f64() * 1000.0)
+            }
+            Err(err) => {
+                eprintln!("host({}) ping err, reason: probe failed, err: {}", ip, err);
+                metrics.record_ping_error(&labels);
+                None
             }
-            Ok(PingResult::Unknown(msg)) => {
-                eprintln!("host({}) ping err, reason: unknown, err: {}", ip, msg);
-                metrics.record_ping_error(&addr, &ip);
+        };
+        session.record(reply);
+        if let Some(rtt_ms) = reply {
+            percentiles.observe(rtt_ms);
+            metrics.record_percentiles(&labels, &percentiles);
+            metrics.record_variation(&labels, &session);
+        }
+        // losses lower the quality too
+        metrics.record_quality(&labels, &session);
+        let now = SystemTime::now();
+        let change = outages.record(reply.is_some(), now, options.outage);
+        if let Some(OutageChange::Started(_)) = change {
+            metrics.record_outage(&labels);
+        }
+        // counted while it lasts, a long outage doesn't show up only once it is over
+        let lasted = match change {
+            Some(OutageChange::Ended(outage)) => outage.duration(),
+            _ => outages.ongoing_for(now).unwrap_or_default(),
+        };
+        metrics.record_outage_time(&labels, lasted.saturating_sub(outage_counted));
+        outage_counted = if outages.ongoing().is_some() { lasted } else { Duration::ZERO };
+        seq = seq.wrapping_add(1);
+
+        if let Some(rest) = interval.checked_sub(started.elapsed()) {
+            network::sleep_while_running(rest, || running.load(Ordering::Relaxed));
+        }
+    }
+}
+
+fn run_path_watch_loop(
+    target: ProbeTarget,
+    every: Duration,
+    max_hops: u8,
+    running: Arc<AtomicBool>,
+    metrics: Arc<PrometheusMetrics>,
+) {
+    let labels = target.labels();
+    let ProbeTarget { ip, source, .. } = target;
+    let mut tracker = PathTracker::default();
+    while running.load(Ordering::Relaxed) {
+        match network::discover_path(&ip, max_hops, source.as_ref()) {
+            Ok(path) => {
+                if let Some(change) = tracker.update(path) {
+                    println!("[{}] host({}) path changed: {}", network::timestamp(), ip, change);
+                    metrics.record_path_change(&labels);
+                }
+            }
+            Err(err) => {
+                eprintln!("host({}) path trace err: {}", ip, err);
+                return;
+            }
+        }
+
+        network::sleep_while_running(every, || running.load(Ordering::Relaxed));
+    }
+}
+
+fn run_pmtu_watch_loop(
+    target: ProbeTarget,
+    every: Duration,
+    running: Arc<AtomicBool>,
+    metrics: Arc<PrometheusMetrics>,
+) {
+    let labels = target.labels();
+    let ProbeTarget { ip, source, .. } = target;
+    let mut last = None;
+    while running.load(Ordering::Relaxed) {
+        match network::discover_pmtu(&ip, source.as_ref()) {
+            Ok(Some(mtu)) => {
+                if last.is_some_and(|last| last != mtu) {
+                    println!("[{}] host({}) path mtu changed: {} -> {}", network::timestamp(), ip, last.unwrap(), mtu);
+                }
+                last = Some(mtu);
+                metrics.record_pmtu(&labels, mtu);
             }
+            // target not answering, the ping loop already reports that
+            Ok(None) => {}
             Err(err) => {
-                eprintln!("host({}) ping err, reason: recv failed, err: {}", ip, err);
-                metrics.record_ping_error(&addr, &ip);
+                eprintln!("host({}) path mtu err: {}", ip, err);
+                return;
             }
         }
+
+        network::sleep_while_running(every, || running.load(Ordering::Relaxed));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // nothing listens on these ports, every probe is refused right away
+    fn target(port: u16) -> ProbeTarget {
+        ProbeTarget {
+            addr: format!("tcp://127.0.0.1:{}", port),
+            ip: String::from("127.0.0.1"),
+            source: None,
+            name: None,
+            interval: None,
+            tags: Vec::new(),
+        }
+    }
+
+    fn workers() -> Workers {
+        let metrics = Arc::new(PrometheusMetrics::new(Vec::new()).unwrap());
+        Workers::new(Duration::from_millis(100), ProbeOptions::default(), metrics)
+    }
+
+    fn exported(workers: &Workers, ip: &str) -> bool {
+        workers.metrics.gather().contains(&format!("ip=\"{}\"", ip))
+    }
+
+    // the stop flag of the worker probing `target`, every started worker gets its own
+    fn flag(workers: &Workers, target: &ProbeTarget) -> Arc<AtomicBool> {
+        workers.workers.iter().find(|worker| worker.target == *target).unwrap().running.clone()
+    }
+
+    #[test]
+    fn unchanged_targets_keep_their_worker() {
+        let mut workers = workers();
+        let (started, retired) = workers.reload(vec![target(1), target(2)]);
+        assert_eq!((started, retired.count()), (2, 0));
+        retired.join();
+        let first = flag(&workers, &target(1));
+
+        let (started, retired) = workers.reload(vec![target(2), target(1)]);
+        assert_eq!((started, retired.count()), (0, 0));
+        retired.join();
+        assert!(Arc::ptr_eq(&first, &flag(&workers, &target(1))));
+        assert!(first.load(Ordering::Relaxed));
+        workers.shutdown();
+    }
+
+    #[test]
+    fn removed_targets_are_retired_with_their_series() {
+        let mut workers = workers();
+        let (_, retired) = workers.reload(vec![target(1), target(2)]);
+        retired.join();
+        let removed = flag(&workers, &target(2));
+
+        let (started, retired) = workers.reload(vec![target(1), target(3)]);
+        assert_eq!((started, retired.count()), (1, 1));
+        assert_eq!(retired.gone, vec![target(2).labels()]);
+        assert!(!removed.load(Ordering::Relaxed));
+        retired.join();
+        assert_eq!(workers.targets(), vec![target(1), target(3)]);
+        workers.shutdown();
+    }
+
+    #[test]
+    fn changed_options_restart_the_worker_and_keep_the_series() {
+        let mut workers = workers();
+        let (_, retired) = workers.reload(vec![target(1)]);
+        retired.join();
+        let old = flag(&workers, &target(1));
+
+        let slower = ProbeTarget { interval: Some(Duration::from_secs(5)), ..target(1) };
+        let (started, retired) = workers.reload(vec![slower.clone()]);
+        assert_eq!((started, retired.count()), (1, 1));
+        // same labels, its counters carry on
+        assert!(retired.gone.is_empty());
+        assert!(!old.load(Ordering::Relaxed));
+        retired.join();
+        assert!(!Arc::ptr_eq(&old, &flag(&workers, &slower)));
+        assert_eq!(workers.targets(), vec![slower]);
+        workers.shutdown();
+    }
+
+    #[test]
+    fn readdressed_targets_drop_the_series_of_the_old_address() {
+        let mut workers = workers();
+        let (_, retired) = workers.reload(vec![target(1)]);
+        retired.join();
+        std::thread::sleep(Duration::from_millis(200));
+        assert!(exported(&workers, "127.0.0.1"));
+
+        let retired = workers.readdress(&target(1), String::from("127.0.0.2"));
+        assert_eq!((retired.count(), retired.gone.clone()), (1, vec![target(1).labels()]));
+        retired.join();
+        assert!(!exported(&workers, "127.0.0.1"));
+        assert_eq!(workers.targets(), vec![ProbeTarget { ip: String::from("127.0.0.2"), ..target(1) }]);
+
+        // a target that is gone already has nothing to move
+        let retired = workers.readdress(&target(9), String::from("127.0.0.3"));
+        assert_eq!(retired.count(), 0);
+        retired.join();
+        workers.shutdown();
     }
 }
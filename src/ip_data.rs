@@ -1,14 +1,259 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use crate::config::Thresholds;
+use crate::outage::OutageLog;
+use crate::probe::{DnsAnswer, HttpTiming, PacketParams, Source};
+use crate::stats::{Aggregate, LossBursts, Statistics, StatsWindow};
 
-#[derive(Debug, Clone)]
+/// `ip` of a hop that never answered during path discovery
+pub(crate) const SILENT_HOP: &str = "*";
+
+#[derive(Debug, Clone, Default)]
 pub struct IpData {
     pub(crate) addr: String,
     pub(crate) ip: String,
+    /// display name from the config file, shown instead of `addr`
+    pub(crate) name: Option<String>,
+    /// `key=value` pairs from the config file
+    pub(crate) tags: Vec<String>,
+    /// the group the table view folds the row into
+    pub(crate) group: Option<String>,
+    pub(crate) thresholds: Thresholds,
+    /// source address or interface the row is probed from, `None` for the default route
+    pub(crate) source: Option<Source>,
+    /// the last replies the views plot, -1 for lost probes
     pub(crate) rtts: VecDeque<f64>,
     pub(crate) last_attr: f64,
-    pub(crate) min_rtt: f64,
-    pub(crate) max_rtt: f64,
     pub(crate) timeout: usize,
+    pub(crate) refused: usize,
     pub(crate) received: usize,
     pub(crate) pop_count: usize,
+    /// position on the path to `addr`, only set in the hops view
+    pub(crate) hop: Option<u8>,
+    /// route changes seen by periodic re-tracing
+    pub(crate) path_changes: usize,
+    /// largest packet that reached the target unfragmented, when discovery is on
+    pub(crate) pmtu: Option<u16>,
+    /// size/ttl/dscp/df the target is probed with
+    pub(crate) packet: PacketParams,
+    /// timing of the last request, only set for http(s) targets
+    pub(crate) http: Option<HttpTiming>,
+    /// replies received more than once (icmp and udp targets)
+    pub(crate) duplicates: usize,
+    /// replies that arrived after those of later probes (icmp and udp targets)
+    pub(crate) reordered: usize,
+    /// sequence number of the last duplicated reply, to find it in a capture
+    pub(crate) duplicate_seq: Option<u16>,
+    /// replies that arrived after their probe timed out (icmp targets)
+    pub(crate) late: usize,
+    /// rtt of the last late reply
+    pub(crate) late_rtt: Option<f64>,
+    /// probes answered with destination unreachable (icmp targets)
+    pub(crate) unreachable: usize,
+    /// ICMP type and code of the last destination unreachable
+    pub(crate) unreachable_reason: Option<(u8, u8)>,
+    /// sequence numbers of the last probes counted as lost, late replies are matched against them
+    pub(crate) lost_seqs: VecDeque<u16>,
+    /// sequence number of the latest probe that got a reply
+    pub(crate) last_reply_seq: Option<u16>,
+    /// last answer, only set for dns targets
+    pub(crate) dns: Option<DnsAnswer>,
+    /// probes that got an answer which failed the check (http status/body, dns rcode)
+    pub(crate) check_failed: usize,
+    /// the hostname resolves to another address now, this one is no longer probed
+    pub(crate) retired: bool,
+    /// the last re-resolution of the hostname failed
+    pub(crate) unresolved: bool,
+    /// the target was removed while running, the ui drops all of its rows
+    pub(crate) removed: bool,
+    /// loss, rtt and percentiles over the lifetime, since the last reset and the rolling windows
+    pub(crate) stats: Statistics,
+    /// runs of lost probes long enough to count as outages
+    pub(crate) outages: OutageLog,
+    /// runs of lost probes of any length
+    pub(crate) bursts: LossBursts,
+}
+
+impl IpData {
+    pub(crate) fn new(addr: String, ip: String) -> Self {
+        Self {
+            addr,
+            ip,
+            ..Default::default()
+        }
+    }
+
+    /// name of the row, the configured one or the target as given
+    pub(crate) fn label(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.addr)
+    }
+
+    /// `ip`, followed by the source when the row is probed from a specific one
+    pub(crate) fn display_ip(&self) -> String {
+        match &self.source {
+            Some(source) => format!("{} via {}", self.noted_ip(), source),
+            None => self.noted_ip(),
+        }
+    }
+
+    /// `ip`, marked when the hostname moved away from it or could not be resolved again
+    pub(crate) fn noted_ip(&self) -> String {
+        if self.retired {
+            format!("{} (old)", self.ip)
+        } else if self.unresolved {
+            format!("{} (unresolved)", self.ip)
+        } else {
+            self.ip.clone()
+        }
+    }
+
+    /// address family of `ip`
+    pub(crate) fn family(&self) -> &'static str {
+        if self.ip.contains(':') { "IPv6" } else { "IPv4" }
+    }
+
+    /// whether `other` probes the same target and source over the other address family (--dual-stack)
+    pub(crate) fn is_pair_of(&self, other: &IpData) -> bool {
+        self.hop.is_none()
+            && other.hop.is_none()
+            && self.addr == other.addr
+            && self.source == other.source
+            && self.family() != other.family()
+    }
+
+    /// probes that did not produce a usable reply
+    pub(crate) fn failed(&self) -> usize {
+        self.timeout + self.refused + self.unreachable + self.check_failed
+    }
+
+    /// probes with an outcome, answered or not
+    pub(crate) fn probes(&self) -> usize {
+        self.received + self.failed()
+    }
+
+    /// loss and rtt summary over `window`
+    pub(crate) fn summary(&self, window: StatsWindow) -> Aggregate {
+        self.stats.aggregate(window)
+    }
+}
+
+/// Aggregate over the rows sharing a group
+#[derive(Debug, Clone, Default)]
+pub struct GroupStats {
+    pub(crate) name: String,
+    pub(crate) members: usize,
+    /// members within their warning thresholds
+    pub(crate) healthy: usize,
+    /// highest loss of any member in percent
+    pub(crate) worst_loss: f64,
+    /// median of the members' average rtts, members without replies are left out
+    pub(crate) median_rtt: f64,
+}
+
+/// The group aggregates over `window`, sorted like the table: worst loss, then median rtt
+pub(crate) fn group_stats(rows: &[IpData], window: StatsWindow) -> Vec<GroupStats> {
+    let mut members: HashMap<&str, Vec<&IpData>> = HashMap::new();
+    for data in rows {
+        if let Some(group) = &data.group {
+            members.entry(group).or_default().push(data);
+        }
+    }
+
+    let mut groups: Vec<GroupStats> = members
+        .into_iter()
+        .map(|(name, members)| {
+            let mut healthy = 0;
+            let mut worst_loss: f64 = 0.0;
+            let mut rtts = Vec::new();
+            for data in &members {
+                let summary = data.summary(window);
+                if !data.thresholds.is_warning(summary.loss(), summary.avg_rtt()) {
+                    healthy += 1;
+                }
+                worst_loss = worst_loss.max(summary.loss());
+                if summary.received > 0 {
+                    rtts.push(summary.avg_rtt());
+                }
+            }
+            GroupStats {
+                name: name.to_string(),
+                members: members.len(),
+                healthy,
+                worst_loss,
+                median_rtt: median(&mut rtts),
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| {
+        a.worst_loss.total_cmp(&b.worst_loss)
+            .then(a.median_rtt.total_cmp(&b.median_rtt))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    groups
+}
+
+// 0 when there are no values
+fn median(values: &mut [f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a member of `group` with these probe outcomes, `None` for a lost probe
+    fn member(group: &str, outcomes: &[Option<f64>]) -> IpData {
+        let mut data = IpData { group: Some(group.to_string()), ..IpData::new(String::from("host"), String::from("192.0.2.1")) };
+        for &rtt in outcomes {
+            data.stats.record(rtt);
+        }
+        data
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(&mut [30.0, 10.0, 20.0]), 20.0);
+        assert_eq!(median(&mut [40.0, 10.0, 30.0, 20.0]), 25.0);
+        assert_eq!(median(&mut [7.0]), 7.0);
+        assert_eq!(median(&mut []), 0.0);
+    }
+
+    #[test]
+    fn groups_take_the_median_rtt_and_the_worst_loss() {
+        let rows = [
+            member("db", &[Some(10.0)]),
+            member("db", &[Some(30.0), None]),
+            member("db", &[Some(20.0)]),
+            member("web", &[Some(5.0)]),
+            member("web", &[Some(15.0)]),
+        ];
+        let groups = group_stats(&rows, StatsWindow::Lifetime);
+        // sorted by worst loss, the lossless group first
+        let summary: Vec<_> = groups.iter().map(|g| (g.name.as_str(), g.members, g.healthy, g.worst_loss, g.median_rtt)).collect();
+        assert_eq!(summary, vec![("web", 2, 2, 0.0, 10.0), ("db", 3, 2, 50.0, 20.0)]);
+    }
+
+    #[test]
+    fn members_without_replies_count_but_have_no_rtt() {
+        let rows = [member("dc", &[None, None]), member("dc", &[Some(12.0)]), member("dark", &[None])];
+        let groups = group_stats(&rows, StatsWindow::Lifetime);
+        let summary: Vec<_> = groups.iter().map(|g| (g.name.as_str(), g.members, g.healthy, g.worst_loss, g.median_rtt)).collect();
+        // a group where nobody answered has no median, 0 like a row without replies
+        assert_eq!(summary, vec![("dark", 1, 0, 100.0, 0.0), ("dc", 2, 1, 100.0, 12.0)]);
+    }
+
+    #[test]
+    fn rows_without_a_group_make_no_group() {
+        assert!(group_stats(&[], StatsWindow::Lifetime).is_empty());
+        let ungrouped = IpData::new(String::from("host"), String::from("192.0.2.1"));
+        assert!(group_stats(&[ungrouped], StatsWindow::Lifetime).is_empty());
+    }
 }
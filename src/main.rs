@@ -7,21 +7,32 @@ mod ping_event;
 mod data_processor;
 mod exporter;
 mod i18n;
-
-use clap::{Parser, Subcommand};
-use std::collections::{HashSet, VecDeque};
+mod probe;
+mod config;
+mod targets_file;
+mod stats;
+mod outage;
+
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Subcommand};
+use clap::parser::ValueSource;
+use std::collections::HashSet;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use ratatui::crossterm::event::{self, Event, KeyCode, KeyModifiers};
 use ratatui::crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use tokio::{task, runtime::Builder, signal};
-use crate::ip_data::IpData;
+use crate::ip_data::{IpData, SILENT_HOP};
+use crate::ui::{BurstPane, ExtraColumns, GroupView, OutagePane, Sweep, TargetPrompt, TargetSelection};
 use crate::ping_event::PingEvent;
 use crate::data_processor::start_data_processor;
 use std::sync::mpsc;
-use crate::network::send_ping;
-use crate::exporter::{PrometheusMetrics, http_server, spawn_ping_workers};
+use crate::network::ProbeTasks;
+use crate::stats::StatsWindow;
+use crate::outage::OutageThresholds;
+use crate::exporter::{watch_dns, PrometheusMetrics, ProbeTarget, Workers, http_server};
+use crate::probe::{HttpExpect, PacketParams, ProbeOptions, Source, DEFAULT_REFLECT_PORT};
+use crate::config::{split_tag, Config, ProbeConfig, TargetSpec};
 
 struct RawModeGuard;
 
@@ -38,15 +49,15 @@ impl Drop for RawModeGuard {
     }
 }
 
-#[derive(Parser, Debug)]
+#[derive(clap::Parser, Debug)]
 #[command(
     version = "v0.6.0",
     author = "hanshuaikang<https://github.com/hanshuaikang>",
     about = "🏎  PingWatch - A Ping Tool in Rust with Real-Time Data and Visualizations"
 )]
 struct Args {
-    /// Target IP address or hostname to ping
-    #[arg(help = "target IP address or hostname to ping", required = false)]
+    /// Target IP address or hostname to ping, or a probe URL (tcp://host:port, udp://host:port, http(s)://host/path, dns://resolver/name?type=A)
+    #[arg(help = "target IP address or hostname to ping, or a tcp://, udp://, http(s):// or dns:// probe URL", required = false)]
     target: Vec<String>,
 
     /// Number of pings to send, when count is 0, the maximum number of pings per address is calculated
@@ -54,7 +65,7 @@ struct Args {
     count: usize,
 
     /// Interval in seconds between pings
-    #[arg(short, long, default_value_t = 0, help = "Interval in seconds between pings")]
+    #[arg(short, long, default_value_t = 0, value_parser = clap::value_parser!(i32).range(0..), help = "Interval in seconds between pings")]
     interval: i32,
 
     #[clap(long = "force_ipv6", default_value_t = false, short = '6', help = "Force using IPv6")]
@@ -68,15 +79,38 @@ struct Args {
     )]
     multiple: i32,
 
-    #[arg(short, long, default_value = "graph", help = "View mode graph/table/point/sparkline")]
+    #[arg(short, long, default_value = "graph", help = "View mode graph/table/point/sparkline/hops/sweep")]
     view_type: String,
 
     #[arg(short = 'o', long = "output", help = "Output file to save ping results")]
     output: Option<String>,
 
+    /// Tag key the table view groups targets by, their first tag when unset
+    #[arg(long = "group-by", help = "Tag key to group targets by in the table view, e.g. dc (default: first tag)")]
+    group_by: Option<String>,
+
+    /// Percentile columns of the table view
+    #[arg(long = "percentiles", value_delimiter = ',', value_parser = stats::parse_percentile, help = "Percentile columns of the table view, e.g. p50,p95,p99 (p50/p90/p95/p99)")]
+    percentiles: Vec<usize>,
+
+    /// Adds the loss burst ratio to the table view
+    #[arg(long = "burst-column", help = "Show the loss burst ratio in the table view, 1 = random loss, above = bursty")]
+    burst_column: bool,
+
     #[arg(long = "lang", help = "Language: en, pt-BR, es (default: system language)")]
     lang: Option<String>,
 
+    /// Settings file, flags given on the command line take precedence over it
+    #[arg(long = "config", global = true, help = "Read targets and settings from a TOML file, e.g. pingwatch.toml")]
+    config: Option<String>,
+
+    /// Hosts file, CSV or YAML inventory to read targets from, next to those given as arguments
+    #[arg(long = "targets-file", global = true, help = "Read targets from a hosts file, a CSV (address,name,tags) or a YAML inventory")]
+    targets_file: Option<String>,
+
+    #[command(flatten)]
+    probe: ProbeArgs,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -85,41 +119,276 @@ struct Args {
 enum Commands {
     /// Exporter mode for monitoring
     Exporter {
-        /// Target IP addresses or hostnames to ping
-        #[arg(help = "target IP addresses or hostnames to ping", required = true)]
+        /// Target IP addresses or hostnames to ping, or probe URLs (tcp://host:port, udp://host:port, http(s)://host/path, dns://resolver/name?type=A)
+        #[arg(help = "target IP addresses or hostnames to ping, or tcp://, udp://, http(s):// or dns:// probe URLs (or from --config)", required = false)]
         target: Vec<String>,
 
         /// Interval in seconds between pings
-        #[arg(short, long, default_value_t = 1, help = "Interval in seconds between pings")]
+        #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(i32).range(0..), help = "Interval in seconds between pings")]
         interval: i32,
 
         /// Prometheus metrics HTTP port
         #[arg(short, long, default_value_t = 9090, help = "Prometheus metrics HTTP port")]
         port: u16,
+
+        #[command(flatten)]
+        probe: ProbeArgs,
+    },
+    /// UDP echo reflector answering udp:// targets of other PingWatch instances
+    Reflect {
+        /// Address to listen on
+        #[arg(short, long, default_value = "0.0.0.0", help = "Address to listen on")]
+        bind: String,
+
+        /// UDP port to listen on
+        #[arg(short, long, default_value_t = DEFAULT_REFLECT_PORT, help = "UDP port to listen on")]
+        port: u16,
     },
 }
 
+/// Probe settings shared by the TUI and the exporter
+#[derive(clap::Args, Debug, Clone)]
+struct ProbeArgs {
+    /// Status codes an http(s) target has to answer with, any 2xx/3xx when unset
+    #[arg(long = "expect-status", value_delimiter = ',', help = "Accepted HTTP status codes, e.g. 200,204 (default: any 2xx/3xx)")]
+    expect_status: Vec<u16>,
+
+    /// Text the body of an http(s) response has to contain
+    #[arg(long = "expect-body", help = "Text the HTTP response body has to contain")]
+    expect_body: Option<String>,
+
+    /// Hop limit of path discovery
+    #[arg(long = "max-hops", default_value_t = 30, help = "Maximum number of hops to discover")]
+    max_hops: u8,
+
+    /// Seconds between re-tracing the path of each target, 0 disables path change detection
+    #[arg(long = "trace-interval", default_value_t = 0, help = "Seconds between path re-traces to detect route changes (0 = off)")]
+    trace_interval: u64,
+
+    /// Discover the path MTU of every target with Don't-Fragment echoes
+    #[arg(long = "pmtu", help = "Discover the path MTU of each target")]
+    pmtu: bool,
+
+    /// Seconds between path MTU checks
+    #[arg(long = "pmtu-interval", default_value_t = 300, help = "Seconds between path MTU re-checks")]
+    pmtu_interval: u64,
+
+    /// Seconds between re-resolving hostnames, 0 keeps the address found at startup
+    #[arg(long = "resolve-interval", default_value_t = 0, help = "Seconds between DNS re-resolutions of hostname targets (0 = off)")]
+    resolve_interval: u64,
+
+    /// Probe the IPv4 and the IPv6 address of every hostname side by side
+    #[arg(long = "dual-stack", help = "Ping both the IPv4 and the IPv6 address of each hostname")]
+    dual_stack: bool,
+
+    /// Payload bytes of icmp and udp probes
+    #[arg(long = "size", value_parser = probe::parse_size, help = "Payload size in bytes of ICMP and UDP probes")]
+    size: Option<usize>,
+
+    /// TTL (IPv4) / hop limit (IPv6) of the probes
+    #[arg(long = "ttl", value_parser = clap::value_parser!(u32).range(1..=255), help = "TTL / hop limit of the probes")]
+    ttl: Option<u32>,
+
+    /// DSCP marking of the probes
+    #[arg(long = "dscp", value_parser = probe::parse_dscp, help = "DSCP marking, 0-63 or a class name such as ef, af41, cs1")]
+    dscp: Option<u8>,
+
+    /// Set the Don't-Fragment flag on the probes
+    #[arg(long = "df", help = "Set the Don't-Fragment flag")]
+    df: bool,
+
+    /// Local addresses (or interfaces) to probe from, every target is probed from each
+    #[arg(long = "source", value_delimiter = ',', help = "Source address or interface to probe from, repeat to compare uplinks")]
+    source: Vec<Source>,
+
+    /// Interfaces to probe from, every target is probed through each
+    #[arg(long = "interface", value_delimiter = ',', help = "Interface to probe through, e.g. wg0, repeat to compare uplinks")]
+    interface: Vec<String>,
+
+    /// Consecutive lost probes that count as an outage
+    #[arg(long = "outage-after", default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..), help = "Consecutive lost probes that start an outage")]
+    outage_after: u32,
+
+    /// Consecutive replies that end an outage
+    #[arg(long = "outage-recovery", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), help = "Consecutive replies that end an outage")]
+    outage_recovery: u32,
+}
+
+impl ProbeArgs {
+    fn to_options(&self) -> ProbeOptions {
+        ProbeOptions {
+            http_expect: HttpExpect {
+                status: self.expect_status.clone(),
+                body: self.expect_body.clone(),
+            },
+            max_hops: self.max_hops,
+            trace_interval: Duration::from_secs(self.trace_interval),
+            pmtu_interval: if self.pmtu {
+                Duration::from_secs(self.pmtu_interval.max(1))
+            } else {
+                Duration::ZERO
+            },
+            resolve_interval: Duration::from_secs(self.resolve_interval),
+            dual_stack: self.dual_stack,
+            packet: PacketParams {
+                size: self.size,
+                ttl: self.ttl,
+                dscp: self.dscp,
+                df: self.df.then_some(true),
+            },
+            sources: self.source.iter().cloned()
+                .chain(self.interface.iter().cloned().map(Source::Interface))
+                .collect(),
+            outage: OutageThresholds {
+                after: self.outage_after as usize,
+                recovery: self.outage_recovery as usize,
+            },
+        }
+    }
+
+    /// Takes every setting of the `[probe]` section that wasn't given on the command line
+    fn merge_config(&mut self, config: &ProbeConfig, matches: &ArgMatches) {
+        merge(matches, "expect_status", &mut self.expect_status, config.expect_status.clone());
+        merge(matches, "expect_body", &mut self.expect_body, config.expect_body.clone().map(Some));
+        merge(matches, "max_hops", &mut self.max_hops, config.max_hops);
+        merge(matches, "trace_interval", &mut self.trace_interval, config.trace_interval);
+        merge(matches, "pmtu", &mut self.pmtu, config.pmtu);
+        merge(matches, "pmtu_interval", &mut self.pmtu_interval, config.pmtu_interval);
+        merge(matches, "resolve_interval", &mut self.resolve_interval, config.resolve_interval);
+        merge(matches, "dual_stack", &mut self.dual_stack, config.dual_stack);
+        merge(matches, "size", &mut self.size, config.size.map(Some));
+        merge(matches, "ttl", &mut self.ttl, config.ttl.as_ref().map(|ttl| Some(*ttl.get_ref())));
+        // already validated when the file was loaded
+        let dscp = config.dscp.as_ref().and_then(|dscp| probe::parse_dscp(&dscp.get_ref().to_string()).ok());
+        merge(matches, "dscp", &mut self.dscp, dscp.map(Some));
+        merge(matches, "df", &mut self.df, config.df);
+        let sources = config.sources.as_ref().map(|sources| sources.iter().filter_map(|s| s.parse().ok()).collect());
+        merge(matches, "source", &mut self.source, sources);
+        merge(matches, "interface", &mut self.interface, config.interfaces.clone());
+        merge(matches, "outage_after", &mut self.outage_after, config.outage_after.as_ref().map(|after| *after.get_ref()));
+        merge(matches, "outage_recovery", &mut self.outage_recovery, config.outage_recovery.as_ref().map(|recovery| *recovery.get_ref()));
+    }
+}
+
+impl Args {
+    /// Takes every setting of the config file that wasn't given on the command line
+    fn merge_config(&mut self, config: &Config, matches: &ArgMatches) {
+        merge(matches, "count", &mut self.count, config.count);
+        merge(matches, "interval", &mut self.interval, config.interval.as_ref().map(|interval| *interval.get_ref()));
+        merge(matches, "force_ipv6", &mut self.force_ipv6, config.ipv6);
+        merge(matches, "view_type", &mut self.view_type, config.view.clone());
+        merge(matches, "output", &mut self.output, config.output.file.clone().map(Some));
+        merge(matches, "group_by", &mut self.group_by, config.group_by.clone().map(Some));
+        merge(matches, "percentiles", &mut self.percentiles, config.percentiles());
+        merge(matches, "burst_column", &mut self.burst_column, config.burst_column);
+        self.probe.merge_config(&config.probe, matches);
+    }
+}
+
+// a flag given on the command line wins over the config file
+fn merge<T>(matches: &ArgMatches, id: &str, value: &mut T, file: Option<T>) {
+    if matches.value_source(id) == Some(ValueSource::CommandLine) {
+        return;
+    }
+    if let Some(file) = file {
+        *value = file;
+    }
+}
+
+// targets given on the command line replace those of the config file,
+// subnets and address ranges become one target per address
+fn target_specs(targets: Vec<String>, targets_file: Option<&str>, config: &Config) -> anyhow::Result<Vec<TargetSpec>> {
+    let mut specs: Vec<TargetSpec> = targets.into_iter().map(|target| TargetSpec::new(target, config.thresholds())).collect();
+    if let Some(path) = targets_file {
+        specs.extend(targets_file::load(path, config.thresholds())?);
+    }
+    // the config file's targets are used when none are given otherwise
+    if specs.is_empty() {
+        specs = config.target_specs();
+    }
+
+    let mut expanded = Vec::new();
+    for spec in specs {
+        let targets = network::expand_target(&spec.target)?;
+        if targets.len() == 1 && targets[0] == spec.target {
+            expanded.push(spec);
+            continue;
+        }
+        // the addresses of a range are told apart by themselves, not by the range's name
+        expanded.extend(targets.into_iter().map(|target| TargetSpec {
+            target,
+            name: None,
+            ..spec.clone()
+        }));
+    }
+
+    // after de-duplication, the original order is still preserved
+    let mut seen = HashSet::new();
+    Ok(expanded.into_iter().filter(|spec| seen.insert(spec.target.clone())).collect())
+}
+
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // parse command line arguments
-    let args = Args::parse();
-
-    // Determine language: command line arg > environment variable > system language > default to 'en'
+    let matches = Args::command().get_matches();
+    let mut args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+
+    // settings file, command line flags take precedence
+    let config = match &args.config {
+        Some(path) => Config::load(path).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }),
+        None => Config::default(),
+    };
+
+    // Determine language: command line arg > config file > environment variable > system language > default to 'en'
     let lang = args.lang
         .clone()
+        .or_else(|| config.lang.clone())
         .or_else(|| std::env::var("PINGWATCH_LANG").ok())
         .unwrap_or_else(|| i18n::detect_system_language());
 
     match args.command {
-        Some(Commands::Exporter { target, interval, port }) => {
-            let worker_threads = (target.len() + 1).max(1);
+        Some(Commands::Exporter { target, mut interval, mut port, mut probe }) => {
+            let sub_matches = matches.subcommand_matches("exporter").unwrap_or(&matches);
+            let config_interval = config.exporter.interval.as_ref().or(config.interval.as_ref());
+            merge(sub_matches, "interval", &mut interval, config_interval.map(|interval| *interval.get_ref()));
+            merge(sub_matches, "port", &mut port, config.exporter.port);
+            probe.merge_config(&config.probe, sub_matches);
+
+            let reload = args.config.clone().map(|path| ConfigReload { path, targets: target.clone(), targets_file: args.targets_file.clone() });
+            let targets = target_specs(target, args.targets_file.as_deref(), &config).unwrap_or_else(|err| {
+                eprintln!("{:#}", err);
+                std::process::exit(1);
+            });
+            if targets.is_empty() {
+                eprintln!("{}", i18n::t(&lang, "error-target-required"));
+                std::process::exit(1);
+            }
+
+            let worker_threads = (targets.len() + 1).max(1);
             // Create tokio runtime for Exporter mode
             let rt = Builder::new_multi_thread()
                 .worker_threads(worker_threads)
                 .enable_all()
                 .build()?;
 
-            let res = rt.block_on(run_exporter_mode(target, interval, port, lang));
+            let res = rt.block_on(run_exporter_mode(targets, interval, port, probe.to_options(), reload, lang));
+
+            // if error print error message and exit
+            if let Err(err) = res {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+        },
+        Some(Commands::Reflect { bind, port }) => {
+            let rt = Builder::new_multi_thread()
+                .worker_threads(2)
+                .enable_all()
+                .build()?;
+
+            let res = rt.block_on(run_reflect_mode(bind, port, lang));
 
             // if error print error message and exit
             if let Err(err) = res {
@@ -128,8 +397,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         },
         None => {
+            args.merge_config(&config, &matches);
+
             // Default ping mode
-            if args.target.is_empty() {
+            let targets = target_specs(std::mem::take(&mut args.target), args.targets_file.as_deref(), &config).unwrap_or_else(|err| {
+                eprintln!("{:#}", err);
+                std::process::exit(1);
+            });
+            if targets.is_empty() {
                 eprintln!("{}", i18n::t(&lang, "error-target-required"));
                 std::process::exit(1);
             }
@@ -147,27 +422,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
 
-            // after de-duplication, the original order is still preserved
-            let mut seen = HashSet::new();
-            let targets: Vec<String> = args.target.into_iter()
-                .filter(|item| seen.insert(item.clone()))
-                .collect();
-
-            // Calculate worker threads based on IP count
-            let ip_count = if targets.len() == 1 && args.multiple > 0 {
-                args.multiple as usize
-            } else {
-                targets.len()
-            };
-            let worker_threads = (ip_count +  1).max(1);
-
-            // Create tokio runtime with specific worker thread count
+            // probes and path watchers run on the blocking pool, which grows with the
+            // targets, also those added at runtime
             let rt = Builder::new_multi_thread()
-                .worker_threads(worker_threads)
+                .worker_threads(2)
                 .enable_all()
                 .build()?;
 
-            let res = rt.block_on(run_app(targets, args.count, args.interval, running.clone(), args.force_ipv6, args.multiple, args.view_type, args.output, lang));
+            let probe_options = args.probe.to_options();
+            let res = rt.block_on(run_app(targets, args.count, args.interval, running.clone(), args.force_ipv6, args.multiple, args.view_type, args.output, args.group_by, ExtraColumns { percentiles: args.percentiles, burst_ratio: args.burst_column }, probe_options, lang));
 
             // if error print error message and exit
             if let Err(err) = res {
@@ -179,8 +442,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+fn print_sweep_summary(ip_data: &[IpData], lang: &str) {
+    let sweep = Sweep::new(ip_data);
+    let ips = |data: &[&IpData]| data.iter().map(|d| d.display_ip()).collect::<Vec<_>>().join(", ");
+    println!("{}: {}/{}", i18n::t(lang, "sweep-hosts-up"), sweep.answered.len(), sweep.total());
+    println!("{}: {}", i18n::t(lang, "sweep-answered"), ips(&sweep.answered));
+    println!("{}: {}", i18n::t(lang, "sweep-silent"), ips(&sweep.silent));
+}
+
 async fn run_app(
-    targets: Vec<String>,
+    targets: Vec<TargetSpec>,
     count: usize,
     interval: i32,
     running: Arc<Mutex<bool>>,
@@ -188,6 +459,9 @@ async fn run_app(
     multiple: i32,
     view_type: String,
     output_file: Option<String>,
+    group_by: Option<String>,
+    columns: ExtraColumns,
+    probe_options: ProbeOptions,
     lang: String,
 ) -> Result<(), Box<dyn std::error::Error>> {
 
@@ -207,44 +481,84 @@ async fn run_app(
 
     let ping_event_tx = Arc::new(ping_event_tx);
 
+    // control channel (ui -> data processor), buffered as the data processor may be
+    // waiting on the ui with an update
+    let (control_tx, control_rx) = mpsc::channel::<PingEvent>();
+
 
+    // (index of the target, address) pairs
     let mut ips = Vec::new();
     // if multiple is set, get multiple IP addresses for each target
     if targets.len() == 1 && multiple > 0 {
         // get multiple IP addresses for the target
-        ips = network::get_multiple_host_ipaddr(&targets[0], force_ipv6, multiple as usize)?;
+        let (host, _) = probe::parse_target(&targets[0].target)?;
+        ips = network::get_multiple_host_ipaddr(&host, force_ipv6, multiple as usize)?
+            .into_iter()
+            .map(|ip| (0, ip))
+            .collect();
     } else {
         // get IP address for each target
-        for target in &targets {
-            let ip = network::get_host_ipaddr(target, force_ipv6)?;
-            ips.push(ip);
+        for (i, target) in targets.iter().enumerate() {
+            let (host, _) = probe::parse_target(&target.target)?;
+            if probe_options.dual_stack {
+                ips.extend(network::get_dual_stack_ipaddrs(&host)?.into_iter().map(|ip| (i, ip)));
+            } else {
+                ips.push((i, network::get_host_ipaddr(&host, force_ipv6)?));
+            }
+        }
+    }
+
+    // one row per resolved address and source, the v4 and v6 row of a target
+    // and source stay next to each other
+    let sources = probe_options.probe_sources();
+    let mut rows = Vec::new();
+    for (i, spec) in targets.iter().enumerate() {
+        let packet = probe::packet_params(&spec.target, &probe_options)?;
+        for source in &sources {
+            for (_, ip) in ips.iter().filter(|(j, _)| *j == i) {
+                rows.push(IpData {
+                    name: spec.name.clone(),
+                    tags: spec.tags.clone(),
+                    group: spec.group(group_by.as_deref()),
+                    thresholds: spec.thresholds,
+                    packet,
+                    source: source.clone(),
+                    ..IpData::new(spec.target.clone(), ip.clone())
+                });
+            }
         }
     }
 
+    // hops view: every target is replaced by the routers on its path
+    if view_type == "hops" {
+        let mut hop_rows = Vec::new();
+        for row in &rows {
+            let (host, _) = probe::parse_target(&row.addr)?;
+            let path = network::discover_path(&row.ip, probe_options.max_hops, row.source.as_ref())?;
+            for (i, hop) in path.into_iter().enumerate() {
+                hop_rows.push(IpData {
+                    hop: Some(i as u8 + 1),
+                    name: row.name.clone(),
+                    thresholds: row.thresholds,
+                    source: row.source.clone(),
+                    ..IpData::new(host.clone(), hop.unwrap_or_else(|| SILENT_HOP.to_string()))
+                });
+            }
+        }
+        rows = hop_rows;
+    }
+
     // Define initial data for UI
-    let ip_data = Arc::new(Mutex::new(ips.iter().enumerate().map(|(i, _)| IpData {
-        ip: String::new(),
-        addr: if targets.len() == 1 { targets[0].clone() } else { targets[i].clone() },
-        rtts: VecDeque::new(),
-        last_attr: 0.0,
-        min_rtt: 0.0,
-        max_rtt: 0.0,
-        timeout: 0,
-        received: 0,
-        pop_count: 0,
-    }).collect::<Vec<_>>()));
+    let ip_data = Arc::new(Mutex::new(rows.clone()));
 
     // Start data processor
-    let targets_for_processor: Vec<(String, String)> = ips.iter().enumerate().map(|(i, ip)| {
-        let addr = if targets.len() == 1 { targets[0].clone() } else { targets[i].clone() };
-        (addr, ip.clone())
-    }).collect();
-    
     start_data_processor(
         ping_event_rx,
+        control_rx,
         ui_data_tx,
-        targets_for_processor,
+        rows.clone(),
         view_type.clone(),
+        probe_options.outage,
         running.clone(),
     );
 
@@ -253,7 +567,6 @@ async fn run_app(
     let errs = Arc::new(Mutex::new(Vec::new()));
 
     let interval = if interval == 0 { 500 } else { interval * 1000 };
-    let mut tasks = Vec::new();
 
 
     // first draw ui
@@ -265,27 +578,29 @@ async fn run_app(
             &mut guard.terminal.as_mut().unwrap(),
             &view_type,
             &ip_data,
+            &GroupView::default(),
+            &TargetSelection::default(),
+            &ExtraColumns::default(),
+            StatsWindow::default(),
+            &OutagePane::default(),
+            &BurstPane::default(),
+            &TargetPrompt::default(),
             &mut errs.lock().unwrap(),
             &lang,
         ).ok();
     }
-    for (i, ip) in ips.iter().enumerate() {
-        let ip = ip.clone();
-        let running = running.clone();
-        let errs = errs.clone();
-        let task = task::spawn({
-            let errs = errs.clone();
-            let ping_event_tx = ping_event_tx.clone();
-            let ip_data = ip_data.clone();
-            let mut data = ip_data.lock().unwrap();
-            // update the ip
-            data[i].ip = ip.clone();
-            let addr = data[i].addr.clone();
-            async move {
-                send_ping(addr, ip, errs.clone(), count, interval, running.clone(), ping_event_tx).await.unwrap();
-            }
-        });
-        tasks.push(task)
+    // hostnames are followed to new addresses, not possible for hops and -m fan-outs
+    let follow_dns = multiple == 0 && view_type.as_str() != "hops";
+    let probes = ProbeTasks::new(count, interval as u64, force_ipv6, follow_dns, view_type.as_str() != "hops", errs.clone(), probe_options, ping_event_tx, control_tx);
+    let probes = Arc::new(Mutex::new(probes));
+    for row in rows.iter().filter(|row| row.ip != SILENT_HOP) {
+        // targets from the config file may come with their own interval
+        let interval = match targets.iter().find(|spec| spec.target == row.addr).and_then(|spec| spec.interval) {
+            Some(0) => 500,
+            Some(secs) => secs * 1000,
+            None => interval,
+        };
+        probes.lock().unwrap().start(row, interval as u64);
     }
 
     // Spawn UI task in background
@@ -296,24 +611,30 @@ async fn run_app(
     let errs_for_ui = errs.clone();
     let lang_for_ui = lang.clone();
     
-    let ui_task = task::spawn(async move {
+    let probes_for_ui = probes.clone();
+
+    // the ui loop blocks, on a thread of its own the probes it starts don't wait behind it
+    let ui_task = task::spawn_blocking(move || {
         let mut guard = terminal_guard_for_ui.lock().unwrap();
         draw::draw_interface_with_updates(
             &mut guard.terminal.as_mut().unwrap(),
             &view_type_for_ui,
             &ip_data_for_ui,
+            &probes_for_ui,
             ui_data_rx,
             running_for_ui,
             errs_for_ui,
             output_file,
+            columns,
             &lang_for_ui,
         ).ok();
     });
 
-    // Wait for all ping tasks to complete
-    for task in tasks {
-        task.await?;
+    // Wait until quit or until every ping task completed, targets come and go in between
+    while *running.lock().unwrap() && !probes.lock().unwrap().finished() {
+        tokio::time::sleep(Duration::from_millis(100)).await;
     }
+    let probed = ProbeTasks::shutdown(&probes).await;
     
     // All ping tasks completed, signal UI to exit
     *running.lock().unwrap() = false;
@@ -323,18 +644,139 @@ async fn run_app(
     
     // restore terminal
     draw::restore_terminal(&mut terminal_guard.lock().unwrap().terminal.as_mut().unwrap())?;
+    probed?;
+
+    // sweeps end with the list of who answered, the screen is gone by now
+    if view_type.as_str() == "sweep" {
+        print_sweep_summary(&ip_data.lock().unwrap(), &lang);
+    }
 
     Ok(())
 }
 
-async fn run_exporter_mode(
+/// Where the exporter re-reads its targets from on SIGHUP or when the file changes
+struct ConfigReload {
+    path: String,
+    /// targets given on the command line, they keep replacing those of the file
     targets: Vec<String>,
+    /// read again on every reload as well
+    targets_file: Option<String>,
+}
+
+// resolves every target and pairs it with each probe source
+fn probe_targets(specs: &[TargetSpec], options: &ProbeOptions) -> Result<Vec<ProbeTarget>, Box<dyn std::error::Error>> {
+    let mut targets = Vec::new();
+    for spec in specs {
+        let (host, _) = probe::parse_target(&spec.target)?;
+        let ips = if options.dual_stack {
+            network::get_dual_stack_ipaddrs(&host)?
+        } else {
+            vec![network::get_host_ipaddr(&host, false)?]
+        };
+        targets.extend(spec_targets(spec, &ips, options));
+    }
+    Ok(targets)
+}
+
+// one target per source and resolved address of `spec`, both families of a source side by side
+fn spec_targets(spec: &TargetSpec, ips: &[String], options: &ProbeOptions) -> Vec<ProbeTarget> {
+    let mut targets = Vec::new();
+    for source in options.probe_sources() {
+        for ip in ips {
+            targets.push(ProbeTarget {
+                addr: spec.target.clone(),
+                ip: ip.clone(),
+                source: source.clone(),
+                name: spec.name.clone(),
+                interval: spec.interval.map(|secs| Duration::from_secs(secs.max(1) as u64)),
+                tags: spec.tags.clone(),
+            });
+        }
+    }
+    targets
+}
+
+// applies the target set of the config file whenever it changes or a reload is requested.
+// Only targets are reloaded, the other settings keep their startup values.
+fn watch_config(
+    reload: ConfigReload,
+    options: ProbeOptions,
+    workers: Arc<Mutex<Workers>>,
+    tag_keys: Vec<String>,
+    running: Arc<AtomicBool>,
+    reload_rx: mpsc::Receiver<()>,
+) {
+    let modified = || std::fs::metadata(&reload.path).and_then(|meta| meta.modified()).ok();
+    let mut last_modified = modified();
+
+    while running.load(Ordering::Relaxed) {
+        match reload_rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(()) => {}
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                let current = modified();
+                if current.is_none() || current == last_modified {
+                    continue;
+                }
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        }
+        last_modified = modified();
+
+        let config = match Config::load(&reload.path) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("[{}] reload failed, keeping the current targets: {}", network::timestamp(), err);
+                continue;
+            }
+        };
+        let specs = match target_specs(reload.targets.clone(), reload.targets_file.as_deref(), &config) {
+            Ok(specs) => specs,
+            Err(err) => {
+                eprintln!("[{}] reload failed, keeping the current targets: {:#}", network::timestamp(), err);
+                continue;
+            }
+        };
+        let targets = match probe_targets(&specs, &options) {
+            Ok(targets) => targets,
+            Err(err) => {
+                eprintln!("[{}] reload failed, keeping the current targets: {}", network::timestamp(), err);
+                continue;
+            }
+        };
+        if !running.load(Ordering::Relaxed) {
+            return;
+        }
+        let unknown: HashSet<&str> = specs.iter()
+            .flat_map(|spec| spec.tags.iter().map(|tag| split_tag(tag).0))
+            .filter(|key| !tag_keys.iter().any(|known| known == key))
+            .collect();
+        if !unknown.is_empty() {
+            let unknown: Vec<&str> = unknown.into_iter().collect();
+            eprintln!("[{}] new tag keys need a restart to become labels: {}", network::timestamp(), unknown.join(", "));
+        }
+
+        let (started, retired) = workers.lock().unwrap().reload(targets);
+        let stopped = retired.count();
+        retired.join();
+        println!("[{}] reloaded {}: {} target(s) started, {} stopped", network::timestamp(), reload.path, started, stopped);
+    }
+}
+
+async fn run_exporter_mode(
+    targets: Vec<TargetSpec>,
     interval: i32,
     port: u16,
+    probe_options: ProbeOptions,
+    reload: Option<ConfigReload>,
     lang: String,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Create Prometheus metrics collector
-    let prometheus_metrics = Arc::new(PrometheusMetrics::new()?);
+    // Create Prometheus metrics collector, the tag keys known at startup become labels
+    let mut tag_keys: Vec<String> = targets.iter()
+        .flat_map(|spec| spec.tags.iter().map(|tag| split_tag(tag).0.to_string()))
+        .collect();
+    tag_keys.sort();
+    tag_keys.dedup();
+    let prometheus_metrics = Arc::new(PrometheusMetrics::new(tag_keys)?);
 
     // Create signal handling channel
     let running = Arc::new(AtomicBool::new(true));
@@ -364,37 +806,37 @@ async fn run_exporter_mode(
         }
     });
 
-    // Deduplicate target addresses while preserving original order
-    let mut seen = std::collections::HashSet::new();
-    let targets: Vec<String> = targets.into_iter()
-        .filter(|item| seen.insert(item.clone()))
-        .collect();
-
     if targets.is_empty() {
         return Err("No valid targets provided".into());
     }
 
     // Parse target addresses to IP addresses
-    let mut target_pairs = Vec::new();
-    for target in &targets {
-        let ip = network::get_host_ipaddr(target, false)?;
-        target_pairs.push((target.clone(), ip));
-    }
+    let target_pairs = probe_targets(&targets, &probe_options)?;
 
     println!("🚀 PingWatch Prometheus Exporter Mode Started");
     println!("┌─────────────────────────────────────────────────────────");
     println!("│ Targets     : {} host(s)", targets.len());
-    for (i, target) in targets.iter().enumerate() {
+    for (i, spec) in targets.iter().enumerate() {
         if i < 5 {
-            println!("│             : {}", target);
+            match &spec.name {
+                Some(name) => println!("│             : {} ({})", name, spec.target),
+                None => println!("│             : {}", spec.target),
+            }
         } else if i == 5 {
             println!("│             : ... ({} more)", targets.len() - 5);
             break;
         }
     }
+    if !probe_options.sources.is_empty() {
+        let sources: Vec<String> = probe_options.sources.iter().map(|s| s.to_string()).collect();
+        println!("│ Sources     : {}", sources.join(", "));
+    }
     println!("│ Interval    : {} seconds", interval);
     println!("│ Metrics port: {}", port);
     println!("│ Metrics     : http://0.0.0.0:{}/metrics", port);
+    if let Some(reload) = &reload {
+        println!("│ Reload      : on change of {} or SIGHUP", reload.path);
+    }
     println!("│ Actions     : Press Ctrl+C or q to stop");
     println!("└─────────────────────────────────────────────────────────");
 
@@ -410,12 +852,45 @@ async fn run_exporter_mode(
     });
 
     let interval_ms = interval * 1000;
-    let ping_threads = spawn_ping_workers(
-        target_pairs,
+    let mut workers = Workers::new(
         Duration::from_millis(interval_ms as u64),
-        running.clone(),
+        probe_options.clone(),
         prometheus_metrics.clone(),
     );
+    for target in target_pairs {
+        workers.start(target);
+    }
+    let workers = Arc::new(Mutex::new(workers));
+
+    // hostnames are looked up again to follow load balancers and failovers
+    let dns_watcher = if probe_options.resolve_interval.is_zero() {
+        None
+    } else {
+        let (workers, metrics, running) = (workers.clone(), prometheus_metrics.clone(), running.clone());
+        let every = probe_options.resolve_interval;
+        Some(std::thread::spawn(move || watch_dns(workers, metrics, every, running)))
+    };
+
+    // SIGHUP and changes of the config file swap the target set without a restart
+    let (reload_tx, reload_rx) = mpsc::channel();
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        if let Ok(mut hangup) = signal::unix::signal(signal::unix::SignalKind::hangup()) {
+            while hangup.recv().await.is_some() {
+                if reload_tx.send(()).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+    let reload_watcher = match reload {
+        Some(reload) => {
+            let (workers, running) = (workers.clone(), running.clone());
+            let tag_keys = prometheus_metrics.tag_keys().to_vec();
+            Some(std::thread::spawn(move || watch_config(reload, probe_options, workers, tag_keys, running, reload_rx)))
+        }
+        None => None,
+    };
 
     // Listen for q/esc to exit (exporter mode only)
     let running_for_key = running.clone();
@@ -458,9 +933,10 @@ async fn run_exporter_mode(
     running.store(false, Ordering::Relaxed);
 
     // Wait for ping threads to complete
-    for handle in ping_threads {
+    for handle in reload_watcher.into_iter().chain(dns_watcher) {
         let _ = handle.join();
     }
+    workers.lock().unwrap().shutdown();
 
     let _ = key_listener.join();
 
@@ -470,3 +946,125 @@ async fn run_exporter_mode(
 
     Ok(())
 }
+
+async fn run_reflect_mode(
+    bind: String,
+    port: u16,
+    lang: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let bind_ip: std::net::IpAddr = bind.parse()?;
+    let bind_addr = std::net::SocketAddr::new(bind_ip, port);
+
+    let running = Arc::new(AtomicBool::new(true));
+
+    // Setup signal handling
+    let running_for_signal = running.clone();
+    tokio::spawn(async move {
+        match signal::ctrl_c().await {
+            Ok(()) => {
+                println!("\nReceived Ctrl+C, shutting down gracefully...");
+                running_for_signal.store(false, Ordering::Relaxed);
+            }
+            Err(err) => {
+                let mut args_map = std::collections::HashMap::new();
+                args_map.insert("error".to_string(), err.to_string());
+                eprintln!("{}", i18n::t_with_args(&lang, "error-unable-shutdown", &args_map));
+            }
+        }
+    });
+
+    println!("🚀 PingWatch UDP Reflector Started");
+    println!("┌─────────────────────────────────────────────────────────");
+    println!("│ Listening   : udp://{}", bind_addr);
+    println!("│ Probe with  : pingwatch udp://<this-host>:{}", port);
+    println!("│ Actions     : Press Ctrl+C to stop");
+    println!("└─────────────────────────────────────────────────────────");
+
+    let running_for_reflector = running.clone();
+    let reflected = task::spawn_blocking(move || {
+        probe::run_reflector(bind_addr, running_for_reflector)
+    }).await??;
+
+    println!("Reflected {} datagram(s)", reflected);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = concat!(
+        "interval = 5\n",
+        "count = 20\n",
+        "view = \"table\"\n",
+        "percentiles = [\"p99\"]\n",
+        "[probe]\n",
+        "max_hops = 12\n",
+        "ttl = 64\n",
+        "dual_stack = true\n",
+        "[output]\n",
+        "file = \"pings.log\"\n",
+    );
+
+    fn args(argv: &[&str]) -> Args {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let matches = Args::command().try_get_matches_from(argv).unwrap();
+        let mut args = Args::from_arg_matches(&matches).unwrap();
+        args.merge_config(&config, &matches);
+        args
+    }
+
+    #[test]
+    fn config_file_fills_in_unset_flags() {
+        let args = args(&["pingwatch", "example.com"]);
+        assert_eq!(args.interval, 5);
+        assert_eq!(args.count, 20);
+        assert_eq!(args.view_type, "table");
+        assert_eq!(args.percentiles, vec![3]);
+        assert_eq!(args.output.as_deref(), Some("pings.log"));
+        assert_eq!(args.probe.max_hops, 12);
+        assert_eq!(args.probe.ttl, Some(64));
+        assert!(args.probe.dual_stack);
+    }
+
+    #[test]
+    fn command_line_flags_win_over_the_config_file() {
+        let args = args(&[
+            "pingwatch", "example.com",
+            "--interval", "1", "-v", "graph", "--percentiles", "p50,p90",
+            "-o", "other.log", "--max-hops", "30", "--ttl", "8",
+        ]);
+        assert_eq!(args.interval, 1);
+        assert_eq!(args.view_type, "graph");
+        assert_eq!(args.percentiles, vec![0, 1]);
+        assert_eq!(args.output.as_deref(), Some("other.log"));
+        // a flag given with its default value still wins
+        assert_eq!(args.probe.max_hops, 30);
+        assert_eq!(args.probe.ttl, Some(8));
+        // settings without a flag on the command line still come from the file
+        assert_eq!(args.count, 20);
+        assert!(args.probe.dual_stack);
+    }
+
+    #[test]
+    fn dual_stack_targets_get_a_row_per_family_and_source() {
+        let spec = TargetSpec::new(String::from("db.example.test"), Default::default());
+        let options = ProbeOptions {
+            dual_stack: true,
+            sources: vec!["192.0.2.10".parse().unwrap(), "wg0".parse().unwrap()],
+            ..Default::default()
+        };
+        let ips = [String::from("192.0.2.1"), String::from("2001:db8::1")];
+        let rows: Vec<_> = spec_targets(&spec, &ips, &options)
+            .into_iter()
+            .map(|target| (target.addr, target.ip, target.source.map(|source| source.to_string())))
+            .collect();
+        let row = |ip: &str, source: &str| (String::from("db.example.test"), ip.to_string(), Some(source.to_string()));
+        assert_eq!(rows, vec![
+            row("192.0.2.1", "192.0.2.10"),
+            row("2001:db8::1", "192.0.2.10"),
+            row("192.0.2.1", "wg0"),
+            row("2001:db8::1", "wg0"),
+        ]);
+    }
+}
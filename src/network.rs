@@ -1,17 +1,22 @@
 use std::error::Error;
 use std::net::{IpAddr, ToSocketAddrs};
 use std::sync::{Arc, Mutex};
-use std::sync::mpsc::SyncSender;
-use std::time::Duration;
+use std::sync::mpsc::{Sender, SyncSender};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use anyhow::{anyhow, Context};
 
-use pinger::{ping, PingOptions, PingResult};
-use crate::ping_event::PingEvent;
+use crate::ip_data::IpData;
+use crate::ping_event::{PingEvent, Resolution};
+use crate::probe::{IcmpPinger, Prober, ProbeOptions, ProbeResult, Source, StrayReply};
 
-// get host ip address default to ipv4
-pub(crate) fn resolve_host_ips(host: &str, force_ipv6: bool) -> Result<Vec<IpAddr>, Box<dyn Error>> {
+// largest packet tried by path MTU discovery, covers jumbo frames
+const MAX_PMTU: u16 = 9000;
 
-    // get ip address
+// most addresses a subnet or range target may expand into
+const MAX_RANGE_HOSTS: u128 = 1024;
+
+// every address of a host, both families
+fn lookup_host(host: &str) -> Result<Vec<IpAddr>, Box<dyn Error>> {
     let ipaddr: Vec<_> = (host, 80)
         .to_socket_addrs()
         .with_context(|| format!("failed to resolve host: {}", host))?
@@ -21,6 +26,14 @@ pub(crate) fn resolve_host_ips(host: &str, force_ipv6: bool) -> Result<Vec<IpAdd
     if ipaddr.is_empty() {
         return Err(anyhow!("Could not resolve host: {}", host).into());
     }
+    Ok(ipaddr)
+}
+
+// get host ip address default to ipv4
+pub(crate) fn resolve_host_ips(host: &str, force_ipv6: bool) -> Result<Vec<IpAddr>, Box<dyn Error>> {
+
+    // get ip address
+    let ipaddr = lookup_host(host)?;
 
     // filter ipv4 or ipv6
     let filtered_ips: Vec<IpAddr> = if force_ipv6 {
@@ -45,6 +58,38 @@ pub(crate) fn get_host_ipaddr(host: &str, force_ipv6: bool) -> Result<String, Bo
     Ok(ips[0].to_string())
 }
 
+/// Resolves `host` again: `None` while it still resolves to `current`, otherwise the address
+/// to probe from now on. Round-robin answers that only reorder the set are no change.
+/// The address family of `current` is kept.
+pub(crate) fn reresolve(host: &str, current: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let v6 = current.parse::<IpAddr>().is_ok_and(|ip| ip.is_ipv6());
+    let ips = resolve_host_ips(host, v6)?;
+    if ips.iter().any(|ip| ip.to_string() == current) {
+        return Ok(None);
+    }
+    Ok(Some(ips[0].to_string()))
+}
+
+/// Whether `addr` names a host that can be resolved again, literal addresses can't move
+pub(crate) fn is_hostname(addr: &str) -> bool {
+    crate::probe::parse_target(addr).is_ok_and(|(host, _)| host.parse::<IpAddr>().is_err())
+}
+
+/// The first IPv4 and the first IPv6 address of a host, for probing both families side by
+/// side. A host with a single family yields a single address.
+pub(crate) fn get_dual_stack_ipaddrs(host: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    Ok(dual_stack_pair(&lookup_host(host)?))
+}
+
+// the first address of each family, IPv4 first whatever order the resolver answered in
+fn dual_stack_pair(ips: &[IpAddr]) -> Vec<String> {
+    ips.iter().find(|ip| ip.is_ipv4())
+        .into_iter()
+        .chain(ips.iter().find(|ip| ip.is_ipv6()))
+        .map(|ip| ip.to_string())
+        .collect()
+}
+
 pub(crate) fn get_multiple_host_ipaddr(host: &str, force_ipv6: bool, multiple: usize) -> Result<Vec<String>, Box<dyn Error>> {
     let ips = resolve_host_ips(host, force_ipv6)?;
     Ok(ips.into_iter()
@@ -53,7 +98,269 @@ pub(crate) fn get_multiple_host_ipaddr(host: &str, force_ipv6: bool, multiple: u
         .collect())
 }
 
+/// Expands a subnet (`10.1.2.0/28`) or range (`10.1.2.10-20`, `10.1.2.10-10.1.3.5`) into
+/// one target per address, packet settings after `#` are kept on each. Anything else is
+/// returned as is. Network and broadcast addresses of IPv4 subnets are left out.
+pub(crate) fn expand_target(target: &str) -> anyhow::Result<Vec<String>> {
+    let (host, params) = match target.split_once('#') {
+        Some((host, params)) => (host, Some(params)),
+        None => (target, None),
+    };
+
+    let ips = if let Some((net, prefix)) = host.split_once('/') {
+        let Ok(net) = net.parse::<IpAddr>() else {
+            return Ok(vec![target.to_string()]);
+        };
+        let prefix: u32 = prefix.parse().map_err(|_| anyhow!("invalid prefix length: {}", host))?;
+        subnet_hosts(net, prefix).with_context(|| format!("invalid subnet: {}", host))?
+    } else if let Some((first, last)) = host.split_once('-') {
+        let Ok(first) = first.parse::<IpAddr>() else {
+            return Ok(vec![target.to_string()]);
+        };
+        // a bare number replaces the last octet
+        let last = match (first, last.parse::<u8>()) {
+            (IpAddr::V4(first), Ok(octet)) => {
+                let mut octets = first.octets();
+                octets[3] = octet;
+                IpAddr::from(octets)
+            }
+            _ => last.parse().map_err(|_| anyhow!("invalid range end: {}", host))?,
+        };
+        range_hosts(first, last).with_context(|| format!("invalid range: {}", host))?
+    } else {
+        return Ok(vec![target.to_string()]);
+    };
+
+    Ok(ips
+        .into_iter()
+        .map(|ip| match params {
+            Some(params) => format!("{}#{}", ip, params),
+            None => ip.to_string(),
+        })
+        .collect())
+}
+
+fn subnet_hosts(net: IpAddr, prefix: u32) -> anyhow::Result<Vec<IpAddr>> {
+    let width = if net.is_ipv4() { 32 } else { 128 };
+    if prefix > width {
+        return Err(anyhow!("prefix length must be at most {}", width));
+    }
+    let size = 1u128.checked_shl(width - prefix).filter(|size| *size <= MAX_RANGE_HOSTS)
+        .ok_or_else(|| anyhow!("more than {} addresses", MAX_RANGE_HOSTS))?;
+    let first = ip_to_u128(net) & !(size - 1);
+    let last = first + size - 1;
+    if net.is_ipv4() && prefix < 31 {
+        range_hosts(u128_to_ip(first + 1, true), u128_to_ip(last - 1, true))
+    } else {
+        range_hosts(u128_to_ip(first, net.is_ipv4()), u128_to_ip(last, net.is_ipv4()))
+    }
+}
+
+fn range_hosts(first: IpAddr, last: IpAddr) -> anyhow::Result<Vec<IpAddr>> {
+    if first.is_ipv4() != last.is_ipv4() {
+        return Err(anyhow!("range mixes IPv4 and IPv6"));
+    }
+    let (start, end) = (ip_to_u128(first), ip_to_u128(last));
+    if start > end {
+        return Err(anyhow!("range end comes before its start"));
+    }
+    if end - start >= MAX_RANGE_HOSTS {
+        return Err(anyhow!("more than {} addresses", MAX_RANGE_HOSTS));
+    }
+    Ok((start..=end).map(|ip| u128_to_ip(ip, first.is_ipv4())).collect())
+}
+
+fn ip_to_u128(ip: IpAddr) -> u128 {
+    match ip {
+        IpAddr::V4(ip) => u32::from(ip) as u128,
+        IpAddr::V6(ip) => u128::from(ip),
+    }
+}
+
+fn u128_to_ip(ip: u128, v4: bool) -> IpAddr {
+    if v4 {
+        IpAddr::from((ip as u32).to_be_bytes())
+    } else {
+        IpAddr::from(ip.to_be_bytes())
+    }
+}
+
+/// Discovers the routers between us and `ip`, see [`IcmpPinger::discover_path`].
+/// Silent hops are `None`.
+pub(crate) fn discover_path(ip: &str, max_hops: u8, source: Option<&Source>) -> Result<Vec<Option<String>>, Box<dyn Error>> {
+    let target: IpAddr = ip.parse()?;
+    let mut pinger = IcmpPinger::new_raw(target, source)
+        .context("hop discovery needs raw ICMP sockets, run as root or grant CAP_NET_RAW")?;
+    let path = pinger
+        .discover_path(max_hops, Duration::from_secs(3))
+        .with_context(|| format!("failed to discover path to {}", ip))?;
+    Ok(path.into_iter().map(|hop| hop.map(|ip| ip.to_string())).collect())
+}
+
+/// Finds the path MTU to `ip`, see [`IcmpPinger::discover_pmtu`].
+pub(crate) fn discover_pmtu(ip: &str, source: Option<&Source>) -> Result<Option<u16>, Box<dyn Error>> {
+    let target: IpAddr = ip.parse()?;
+    let mut pinger = IcmpPinger::new(target, source)?;
+    let mtu = pinger
+        .discover_pmtu(MAX_PMTU, Duration::from_secs(1))
+        .with_context(|| format!("failed to discover path mtu to {}", ip))?;
+    Ok(mtu)
+}
 
+/// Re-checks the path MTU to `ip` every `every` until stopped. Every result goes to the
+/// data processor, changes are logged to the error pane. Blocks, run it on its own thread.
+pub(crate) fn watch_pmtu(
+    addr: String,
+    ip: String,
+    source: Option<Source>,
+    every: Duration,
+    running: Arc<Mutex<bool>>,
+    errs: Arc<Mutex<Vec<String>>>,
+    ping_event_tx: Arc<SyncSender<PingEvent>>,
+) {
+    let mut last: Option<Option<u16>> = None;
+    loop {
+        if !*running.lock().unwrap() {
+            break;
+        }
+
+        match discover_pmtu(&ip, source.as_ref()) {
+            Ok(mtu) => {
+                if let Some(Some(old)) = last {
+                    if mtu.is_some_and(|mtu| mtu != old) {
+                        let msg = format!("[{}] host({}) path mtu changed: {} -> {}", timestamp(), ip, old, mtu.unwrap());
+                        set_error(errs.clone(), msg);
+                    }
+                }
+                // keep the last known value while the target doesn't answer at all
+                if mtu.is_some() || last.is_none() {
+                    last = Some(mtu);
+                    let event = PingEvent::Pmtu { addr: addr.clone(), ip: ip.clone(), source: source.clone(), mtu };
+                    if ping_event_tx.send(event).is_err() {
+                        break;
+                    }
+                }
+            }
+            Err(err) => {
+                let err = format!("[{}] host({}) path mtu err: {}", timestamp(), ip, err);
+                set_error(errs.clone(), err);
+                break;
+            }
+        }
+
+        sleep_while_running(every, || *running.lock().unwrap());
+    }
+}
+
+/// Remembers the last path to a target and tells when the router sequence changed.
+#[derive(Debug, Default)]
+pub(crate) struct PathTracker {
+    last: Option<Vec<Option<String>>>,
+}
+
+impl PathTracker {
+    /// Feeds a fresh trace, returns a description of the change if the route moved.
+    /// The first trace only sets the baseline.
+    pub(crate) fn update(&mut self, path: Vec<Option<String>>) -> Option<String> {
+        let changed = match &self.last {
+            Some(last) if path_changed(last, &path) => Some(format!(
+                "{} -> {}",
+                format_path(last),
+                format_path(&path)
+            )),
+            _ => None,
+        };
+        // a trace full of silent hops carries no information, keep the old baseline
+        if self.last.is_none() || path.iter().any(Option::is_some) {
+            self.last = Some(path);
+        }
+        changed
+    }
+}
+
+// silent hops match anything, they usually are rate limited routers and not a new route
+fn path_changed(old: &[Option<String>], new: &[Option<String>]) -> bool {
+    let differs = old
+        .iter()
+        .zip(new)
+        .any(|(a, b)| matches!((a, b), (Some(a), Some(b)) if a != b));
+    if differs {
+        return true;
+    }
+    // a different length only counts when both traces reached the target
+    old.len() != new.len() && old.last().is_some_and(Option::is_some) && new.last().is_some_and(Option::is_some)
+}
+
+fn format_path(path: &[Option<String>]) -> String {
+    path.iter()
+        .map(|hop| hop.as_deref().unwrap_or(crate::ip_data::SILENT_HOP))
+        .collect::<Vec<_>>()
+        .join(" > ")
+}
+
+/// Wall clock time of day in UTC, used to stamp events.
+pub(crate) fn timestamp() -> String {
+    clock(SystemTime::now())
+}
+
+/// Time of day of `at` as `HH:MM:SS UTC`, the date is left out. Every time pingwatch shows
+/// goes through here so the error pane, the outage log and the console agree.
+pub(crate) fn clock(at: SystemTime) -> String {
+    let secs = at.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    format!("{:02}:{:02}:{:02} UTC", secs / 3600 % 24, secs / 60 % 60, secs % 60)
+}
+
+/// Re-traces the path to `ip` every `every` until stopped and reports route changes
+/// to the error pane and the data processor. Blocks, run it on its own thread.
+pub(crate) fn watch_path(
+    addr: String,
+    ip: String,
+    source: Option<Source>,
+    every: Duration,
+    max_hops: u8,
+    running: Arc<Mutex<bool>>,
+    errs: Arc<Mutex<Vec<String>>>,
+    ping_event_tx: Arc<SyncSender<PingEvent>>,
+) {
+    let mut tracker = PathTracker::default();
+    loop {
+        if !*running.lock().unwrap() {
+            break;
+        }
+
+        match discover_path(&ip, max_hops, source.as_ref()) {
+            Ok(path) => {
+                if let Some(change) = tracker.update(path) {
+                    let msg = format!("[{}] host({}) path changed: {}", timestamp(), ip, change);
+                    set_error(errs.clone(), msg);
+                    let event = PingEvent::PathChanged { addr: addr.clone(), ip: ip.clone(), source: source.clone() };
+                    if ping_event_tx.send(event).is_err() {
+                        break;
+                    }
+                }
+            }
+            Err(err) => {
+                // without raw sockets every later trace fails the same way
+                let err = format!("[{}] host({}) path trace err: {}", timestamp(), ip, err);
+                set_error(errs.clone(), err);
+                break;
+            }
+        }
+
+        sleep_while_running(every, || *running.lock().unwrap());
+    }
+}
+
+/// Sleeps for `duration` in small steps so ctrl+c is not held up by long intervals.
+pub(crate) fn sleep_while_running(duration: Duration, running: impl Fn() -> bool) {
+    let started = Instant::now();
+    while started.elapsed() < duration && running() {
+        std::thread::sleep(Duration::from_millis(200).min(duration));
+    }
+}
+
+
+#[derive(Clone)]
 pub struct PingTask {
     addr: String,
     ip: String,
@@ -61,6 +368,8 @@ pub struct PingTask {
     interval: u64,
     running: Arc<Mutex<bool>>,
     errs: Arc<Mutex<Vec<String>>>,
+    options: ProbeOptions,
+    source: Option<Source>,
 }
 
 impl PingTask {
@@ -71,6 +380,8 @@ impl PingTask {
         interval: u64,
         running: Arc<Mutex<bool>>,
         errs: Arc<Mutex<Vec<String>>>,
+        options: ProbeOptions,
+        source: Option<Source>,
     ) -> Self {
         Self {
             addr,
@@ -79,23 +390,23 @@ impl PingTask {
             interval,
             running,
             errs,
+            options,
+            source,
         }
     }
 
-    pub async fn run(&self, ping_event_tx: Arc<SyncSender<PingEvent>>) -> Result<(), Box<dyn Error>>
+    /// Probes until stopped or the count is reached. Blocks, run it on a thread of its own.
+    pub fn run(&self, ping_event_tx: Arc<SyncSender<PingEvent>>) -> Result<(), Box<dyn Error>>
     {
         // interval defined 0.5s/every ping
         let interval = Duration::from_millis(self.interval);
-        let options = PingOptions::new(
-            self.ip.clone(),
-            interval,
-            None,
-        );
+        let ip: IpAddr = self.ip.parse()?;
 
-        // star ping
-        let stream = ping(options)?;
+        // open the probe matching the target scheme
+        let mut prober = Prober::new(&self.addr, ip, &self.options, self.source.as_ref())?;
 
         let mut ping_count = 0;
+        let mut seq: u16 = 0;
         loop {
             // if ctrl+c is pressed, break the loop
             if !*self.running.lock().unwrap() {
@@ -110,84 +421,538 @@ impl PingTask {
                 ping_count += 1;
             }
 
-            match stream.recv() {
-                Ok(result) => {
-                    match result {
-                        PingResult::Pong(duration, _size) => {
-                            // calculate rtt
-                            let rtt = duration.as_secs_f64() * 1000.0;
-                            let rtt_display: f64 = format!("{:.2}", rtt).parse().unwrap();
-                            
-                            let event = PingEvent::Success {
-                                addr: self.addr.clone(),
-                                ip: self.ip.clone(),
-                                rtt: rtt_display,
-                            };
-                            
-                            if ping_event_tx.send(event).is_err() {
-                                break;
-                            }
-                        }
-                        PingResult::Timeout(_) => {
-                            let event = PingEvent::Timeout {
-                                addr: self.addr.clone(),
-                                ip: self.ip.clone(),
-                            };
-                            
-                            if ping_event_tx.send(event).is_err() {
-                                break;
-                            }
-                        }
-                        PingResult::PingExited(status, err) => {
-                            if status.code() != Option::from(0) {
-                                let err = format!("host({}) ping err, reason: ping excited, status: {} err: {}", self.ip, err, status);
-                                set_error(self.errs.clone(), err);
-                            }
-                        }
-                        PingResult::Unknown(msg) => {
-                            let err = format!("host({}) ping err, reason:unknown, err: {}", self.ip, msg);
-                            set_error(self.errs.clone(), err);
-                        }
+            let started = Instant::now();
+            let result = prober.probe(seq, interval);
+            // stray replies arrived before the outcome of this probe, they go first
+            let strays = prober.take_strays();
+            if strays.into_iter().any(|stray| ping_event_tx.send(self.stray_event(stray)).is_err()) {
+                break;
+            }
+            match result {
+                Ok(ProbeResult::Reply { seq, rtt }) => {
+                    // calculate rtt
+                    let rtt = rtt.as_secs_f64() * 1000.0;
+                    let rtt_display: f64 = format!("{:.2}", rtt).parse().unwrap();
+
+                    let event = PingEvent::Success {
+                        addr: self.addr.clone(),
+                        ip: self.ip.clone(),
+                        source: self.source.clone(),
+                        seq,
+                        rtt: rtt_display,
+                    };
+
+                    if ping_event_tx.send(event).is_err() {
+                        break;
+                    }
+                }
+                Ok(ProbeResult::Timeout { seq }) => {
+                    let event = PingEvent::Timeout {
+                        addr: self.addr.clone(),
+                        ip: self.ip.clone(),
+                        source: self.source.clone(),
+                        seq,
+                    };
+
+                    if ping_event_tx.send(event).is_err() {
+                        break;
+                    }
+                }
+                Ok(ProbeResult::Refused { .. }) => {
+                    let event = PingEvent::Refused {
+                        addr: self.addr.clone(),
+                        ip: self.ip.clone(),
+                        source: self.source.clone(),
+                    };
+
+                    if ping_event_tx.send(event).is_err() {
+                        break;
+                    }
+                }
+                Ok(ProbeResult::Dns { rtt, answer, .. }) => {
+                    let rtt = rtt.as_secs_f64() * 1000.0;
+                    let rtt_display: f64 = format!("{:.2}", rtt).parse().unwrap();
+
+                    let event = PingEvent::Dns {
+                        addr: self.addr.clone(),
+                        ip: self.ip.clone(),
+                        source: self.source.clone(),
+                        rtt: rtt_display,
+                        answer,
+                    };
+
+                    if ping_event_tx.send(event).is_err() {
+                        break;
+                    }
+                }
+                Ok(ProbeResult::Udp { rtt, duplicates, reordered, .. }) => {
+                    let rtt_display = rtt.map(|rtt| {
+                        let rtt = rtt.as_secs_f64() * 1000.0;
+                        format!("{:.2}", rtt).parse::<f64>().unwrap()
+                    });
+
+                    let event = PingEvent::Udp {
+                        addr: self.addr.clone(),
+                        ip: self.ip.clone(),
+                        source: self.source.clone(),
+                        rtt: rtt_display,
+                        duplicates,
+                        reordered,
+                    };
+
+                    if ping_event_tx.send(event).is_err() {
+                        break;
+                    }
+                }
+                Ok(ProbeResult::Http { timing, check, .. }) => {
+                    let event = PingEvent::Http {
+                        addr: self.addr.clone(),
+                        ip: self.ip.clone(),
+                        source: self.source.clone(),
+                        timing,
+                        check,
+                    };
+
+                    if ping_event_tx.send(event).is_err() {
+                        break;
+                    }
+                }
+                Ok(ProbeResult::Unreachable { seq, icmp_type, code }) => {
+                    let event = PingEvent::Unreachable {
+                        addr: self.addr.clone(),
+                        ip: self.ip.clone(),
+                        source: self.source.clone(),
+                        seq,
+                        icmp_type,
+                        code,
+                    };
+
+                    if ping_event_tx.send(event).is_err() {
+                        break;
                     }
                 }
                 Err(err) => {
-                    let err = format!("host({}) ping err, reason: unknown, err: {}", self.ip, err);
+                    let err = format!("host({}) ping err, reason: probe failed, err: {}", self.ip, err);
                     set_error(self.errs.clone(), err);
                 }
             }
+            seq = seq.wrapping_add(1);
 
-
+            // keep a steady cadence, a reply may arrive well before the interval is over
+            if let Some(rest) = interval.checked_sub(started.elapsed()) {
+                sleep_while_running(rest, || *self.running.lock().unwrap());
+            }
         }
 
         Ok(())
     }
+
+    fn stray_event(&self, stray: StrayReply) -> PingEvent {
+        match stray {
+            StrayReply::Duplicate { seq } => PingEvent::Duplicate {
+                addr: self.addr.clone(),
+                ip: self.ip.clone(),
+                source: self.source.clone(),
+                seq,
+            },
+            StrayReply::Late { seq, rtt } => PingEvent::Late {
+                addr: self.addr.clone(),
+                ip: self.ip.clone(),
+                source: self.source.clone(),
+                seq,
+                rtt: format!("{:.2}", rtt.as_secs_f64() * 1000.0).parse().unwrap(),
+            },
+        }
+    }
+
+    /// Probes a hostname target and resolves it again every `every`. When the name moves to
+    /// another address, the old one stops being probed and the new one is probed as a row
+    /// of its own. Changes and failed lookups go to the error pane and the data processor.
+    /// Blocks like [`PingTask::run`].
+    pub fn run_resolving(self, every: Duration, ping_event_tx: Arc<SyncSender<PingEvent>>) -> Result<(), Box<dyn Error>> {
+        let (host, _) = crate::probe::parse_target(&self.addr)?;
+        let mut ip = self.ip.clone();
+        loop {
+            // the probe of the current address has a stop flag of its own
+            let probing = Arc::new(Mutex::new(true));
+            let task = PingTask { ip: ip.clone(), running: probing.clone(), ..self.clone() };
+            let tx = ping_event_tx.clone();
+            let probe = std::thread::spawn(move || task.run(tx).map_err(|err| err.to_string()));
+
+            let mut resolved_at = Instant::now();
+            let new_ip = loop {
+                // count reached or the probe could not be opened
+                if probe.is_finished() {
+                    return probe.join().map_err(|_| "probe thread panicked")?.map_err(Into::into);
+                }
+                std::thread::sleep(Duration::from_millis(200));
+                if !*self.running.lock().unwrap() {
+                    *probing.lock().unwrap() = false;
+                    let _ = probe.join();
+                    return Ok(());
+                }
+                if resolved_at.elapsed() < every {
+                    continue;
+                }
+                resolved_at = Instant::now();
+
+                let resolution = match reresolve(&host, &ip) {
+                    Ok(None) => Resolution::Unchanged,
+                    Ok(Some(new_ip)) => {
+                        let msg = format!("[{}] host({}) address changed: {} -> {}", timestamp(), host, ip, new_ip);
+                        set_error(self.errs.clone(), msg);
+                        Resolution::Changed(new_ip)
+                    }
+                    Err(err) => {
+                        let msg = format!("[{}] host({}) resolve err: {}", timestamp(), host, err);
+                        set_error(self.errs.clone(), msg);
+                        Resolution::Failed
+                    }
+                };
+                let event = PingEvent::Resolve {
+                    addr: self.addr.clone(),
+                    ip: ip.clone(),
+                    source: self.source.clone(),
+                    resolution: resolution.clone(),
+                };
+                if ping_event_tx.send(event).is_err() {
+                    *probing.lock().unwrap() = false;
+                    return Ok(());
+                }
+                if let Resolution::Changed(new_ip) = resolution {
+                    break new_ip;
+                }
+            };
+
+            *probing.lock().unwrap() = false;
+            let _ = probe.join();
+            ip = new_ip;
+        }
+    }
 }
 
-// send ping to the target address
-pub async fn send_ping(
+/// The probes behind the rows of the TUI. Every target has a stop flag of its own, so
+/// targets can be added and removed while the others keep running.
+pub struct ProbeTasks {
+    /// ms between probes of targets that don't bring their own interval
+    interval: u64,
+    force_ipv6: bool,
+    /// events of the ui, buffered so the ui never waits on the data processor
+    control_tx: Sender<PingEvent>,
+    rows: RowContext,
+    probes: Vec<Probe>,
+}
+
+// a target being probed, with all its rows and their path and pmtu watchers
+struct Probe {
     addr: String,
-    ip: String,
-    errs: Arc<Mutex<Vec<String>>>,
-    count: usize,
-    interval: i32,
     running: Arc<Mutex<bool>>,
+    handle: tokio::task::JoinHandle<Result<(), String>>,
+}
+
+// what every row is probed with, cloned into the tasks that start rows later
+#[derive(Clone)]
+struct RowContext {
+    count: usize,
+    /// hostnames are followed to new addresses, not possible for hops and -m fan-outs
+    follow_dns: bool,
+    /// the path of every target is re-traced, not needed for the hops rows themselves
+    trace: bool,
+    errs: Arc<Mutex<Vec<String>>>,
+    options: ProbeOptions,
     ping_event_tx: Arc<SyncSender<PingEvent>>,
-) -> Result<(), Box<dyn Error>>
-{
-    // draw ui first
-    let task = PingTask::new(
-        addr.to_string(),
-        ip,
-        count,
-        interval as u64,
-        running,
-        errs,
-    );
-    Ok(task.run(ping_event_tx).await?)
 }
 
+impl ProbeTasks {
+    pub fn new(
+        count: usize,
+        interval: u64,
+        force_ipv6: bool,
+        follow_dns: bool,
+        trace: bool,
+        errs: Arc<Mutex<Vec<String>>>,
+        options: ProbeOptions,
+        ping_event_tx: Arc<SyncSender<PingEvent>>,
+        control_tx: Sender<PingEvent>,
+    ) -> Self {
+        Self {
+            interval,
+            force_ipv6,
+            control_tx,
+            rows: RowContext { count, follow_dns, trace, errs, options, ping_event_tx },
+            probes: Vec::new(),
+        }
+    }
+
+    /// Starts probing a row the data processor already knows, every `interval` ms
+    pub fn start(&mut self, row: &IpData, interval: u64) {
+        let running = Arc::new(Mutex::new(true));
+        let (rows, row, flag) = (self.rows.clone(), row.clone(), running.clone());
+        let addr = row.addr.clone();
+        // probes block, on the runtime workers they would hold up every other target
+        let handle = tokio::task::spawn_blocking(move || rows.run(&row, interval, flag));
+        self.probes.push(Probe { addr, running, handle });
+    }
+
+    /// Probes `target` from every source. It is resolved on the blocking pool, the ui
+    /// thread calling this holds the probes lock. Failed lookups end up in the errors.
+    pub fn add(&mut self, target: &str) -> Result<(), Box<dyn Error>> {
+        if self.probes.iter().any(|probe| probe.addr == target) {
+            return Err(format!("target {} is already monitored", target).into());
+        }
+        let (host, _) = crate::probe::parse_target(target)?;
+        let packet = crate::probe::packet_params(target, &self.rows.options)?;
+
+        let running = Arc::new(Mutex::new(true));
+        let (rows, flag, control_tx) = (self.rows.clone(), running.clone(), self.control_tx.clone());
+        let (addr, interval, force_ipv6) = (target.to_string(), self.interval, self.force_ipv6);
+        let handle = tokio::task::spawn_blocking(move || {
+            let ips = if rows.options.dual_stack {
+                get_dual_stack_ipaddrs(&host)
+            } else {
+                get_host_ipaddr(&host, force_ipv6).map(|ip| vec![ip])
+            };
+            let ips = match ips {
+                Ok(ips) => ips,
+                Err(err) => {
+                    set_error(rows.errs.clone(), format!("add target({}) err: {}", addr, err));
+                    return Ok(());
+                }
+            };
+            let mut added = Vec::new();
+            for source in rows.options.probe_sources() {
+                for ip in &ips {
+                    added.push(IpData {
+                        packet,
+                        source: source.clone(),
+                        ..IpData::new(addr.clone(), ip.clone())
+                    });
+                }
+            }
+            {
+                // under the stop flag, a target removed meanwhile must not come back. The
+                // removal goes over the same channel, so it can't overtake the rows either.
+                let running = flag.lock().unwrap();
+                if !*running {
+                    return Ok(());
+                }
+                for row in &added {
+                    if control_tx.send(PingEvent::Added { row: row.clone() }).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+            let handles: Vec<_> = added
+                .into_iter()
+                .map(|row| {
+                    let (rows, flag) = (rows.clone(), flag.clone());
+                    tokio::task::spawn_blocking(move || rows.run(&row, interval, flag))
+                })
+                .collect();
+            let runtime = tokio::runtime::Handle::current();
+            for handle in handles {
+                runtime.block_on(handle).map_err(|err| err.to_string())??;
+            }
+            Ok(())
+        });
+        self.probes.push(Probe { addr: target.to_string(), running, handle });
+        Ok(())
+    }
+
+    /// Stops every probe of `addr` and has the data processor drop its rows
+    pub fn remove(&mut self, addr: &str) {
+        self.probes.retain(|probe| {
+            if probe.addr == addr {
+                *probe.running.lock().unwrap() = false;
+            }
+            probe.addr != addr
+        });
+        let _ = self.control_tx.send(PingEvent::Removed { addr: addr.to_string() });
+    }
+
+    /// Starts the since-reset statistics of every row over
+    pub fn reset_statistics(&self) {
+        let _ = self.control_tx.send(PingEvent::Reset);
+    }
+
+    /// Whether every probe stopped on its own, e.g. because the count was reached.
+    /// Without any target the TUI keeps waiting for one to be added.
+    pub fn finished(&self) -> bool {
+        !self.probes.is_empty() && self.probes.iter().all(|probe| probe.handle.is_finished())
+    }
+
+    /// Stops all probes and waits for them, the first probe error is returned
+    pub async fn shutdown(probes: &Mutex<ProbeTasks>) -> Result<(), Box<dyn Error>> {
+        let probes: Vec<Probe> = probes.lock().unwrap().probes.drain(..).collect();
+        for probe in &probes {
+            *probe.running.lock().unwrap() = false;
+        }
+        for probe in probes {
+            probe.handle.await??;
+        }
+        Ok(())
+    }
+}
+
+impl RowContext {
+    // probes `row` until `running` is cleared or the count is reached. Blocks, so it
+    // runs on the blocking pool, its watchers are started there next to it.
+    fn run(&self, row: &IpData, interval: u64, running: Arc<Mutex<bool>>) -> Result<(), String> {
+        let addr = row.addr.clone();
+        let ip = row.ip.clone();
+        let source = row.source.clone();
+
+        if self.trace && !self.options.trace_interval.is_zero() {
+            let (addr, ip, source, running, errs, tx) = (addr.clone(), ip.clone(), source.clone(), running.clone(), self.errs.clone(), self.ping_event_tx.clone());
+            let (every, max_hops) = (self.options.trace_interval, self.options.max_hops);
+            tokio::task::spawn_blocking(move || {
+                watch_path(addr, ip, source, every, max_hops, running, errs, tx);
+            });
+        }
+        if !self.options.pmtu_interval.is_zero() {
+            let (addr, ip, source, running, errs, tx) = (addr.clone(), ip.clone(), source.clone(), running.clone(), self.errs.clone(), self.ping_event_tx.clone());
+            let every = self.options.pmtu_interval;
+            tokio::task::spawn_blocking(move || {
+                watch_pmtu(addr, ip, source, every, running, errs, tx);
+            });
+        }
+
+        let resolving = self.follow_dns && !self.options.resolve_interval.is_zero() && is_hostname(&addr);
+        let every = self.options.resolve_interval;
+        let task = PingTask::new(addr, ip, self.count, interval, running, self.errs.clone(), self.options.clone(), source);
+        let result = if resolving {
+            task.run_resolving(every, self.ping_event_tx.clone())
+        } else {
+            task.run(self.ping_event_tx.clone())
+        };
+        result.map_err(|err| err.to_string())
+    }
+}
 
 fn set_error(errs: Arc<Mutex<Vec<String>>>, err: String) {
     let mut err_list = errs.lock().unwrap();
     err_list.push(err)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ips(ips: &[&str]) -> Vec<IpAddr> {
+        ips.iter().map(|ip| ip.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn dual_stack_takes_the_first_address_of_each_family() {
+        let answer = ips(&["2001:db8::1", "192.0.2.1", "2001:db8::2", "192.0.2.2"]);
+        assert_eq!(dual_stack_pair(&answer), ["192.0.2.1", "2001:db8::1"]);
+        // a host with a single family keeps its single row
+        assert_eq!(dual_stack_pair(&ips(&["192.0.2.1", "192.0.2.2"])), ["192.0.2.1"]);
+        assert_eq!(dual_stack_pair(&ips(&["2001:db8::1"])), ["2001:db8::1"]);
+    }
+
+    fn expand(target: &str) -> Vec<String> {
+        expand_target(target).unwrap()
+    }
+
+    #[test]
+    fn subnets_leave_out_network_and_broadcast() {
+        assert_eq!(expand("10.0.0.6/30"), ["10.0.0.5", "10.0.0.6"]);
+        // point to point links use both addresses
+        assert_eq!(expand("10.0.0.6/31"), ["10.0.0.6", "10.0.0.7"]);
+        assert_eq!(expand("10.0.0.6/32"), ["10.0.0.6"]);
+        assert_eq!(expand("10.0.0.0/29#ttl=8").len(), 6);
+        assert_eq!(expand("10.0.0.0/29#ttl=8")[0], "10.0.0.1#ttl=8");
+        assert!(expand_target("10.0.0.0/33").is_err());
+        assert!(expand_target("10.0.0.0/x").is_err());
+    }
+
+    #[test]
+    fn ipv6_prefixes_keep_every_address() {
+        assert_eq!(expand("2001:db8::5/126"), ["2001:db8::4", "2001:db8::5", "2001:db8::6", "2001:db8::7"]);
+        assert_eq!(expand("2001:db8::1/128"), ["2001:db8::1"]);
+        assert!(expand_target("2001:db8::/64").is_err());
+    }
+
+    #[test]
+    fn ranges_expand_from_start_to_end() {
+        assert_eq!(expand("10.0.0.1-5"), ["10.0.0.1", "10.0.0.2", "10.0.0.3", "10.0.0.4", "10.0.0.5"]);
+        assert_eq!(expand("10.0.0.254-10.0.1.1"), ["10.0.0.254", "10.0.0.255", "10.0.1.0", "10.0.1.1"]);
+        assert_eq!(expand("10.0.0.7-7"), ["10.0.0.7"]);
+        assert_eq!(expand("2001:db8::1-2001:db8::3"), ["2001:db8::1", "2001:db8::2", "2001:db8::3"]);
+        assert!(expand_target("10.0.0.5-1").is_err());
+        assert!(expand_target("10.0.1.0-10.0.0.255").is_err());
+        assert!(expand_target("10.0.0.1-2001:db8::1").is_err());
+        assert!(expand_target("10.0.0.1-300").is_err());
+    }
+
+    #[test]
+    fn expansions_are_capped() {
+        // a /22 is exactly the cap, less its network and broadcast address
+        assert_eq!(expand("10.0.0.0/22").len(), 1022);
+        assert!(expand_target("10.0.0.0/21").is_err());
+        assert_eq!(expand("10.0.0.0-10.0.3.255").len(), MAX_RANGE_HOSTS as usize);
+        assert!(expand_target("10.0.0.0-10.0.4.0").is_err());
+    }
+
+    #[test]
+    fn hostnames_and_urls_are_not_ranges() {
+        assert_eq!(expand("db-01.example.test"), ["db-01.example.test"]);
+        assert_eq!(expand("tcp://db-01:5432"), ["tcp://db-01:5432"]);
+        assert_eq!(expand("http://10.0.0.1/health-check"), ["http://10.0.0.1/health-check"]);
+        assert_eq!(expand("https://example.test/a/b#dscp=ef"), ["https://example.test/a/b#dscp=ef"]);
+        assert_eq!(expand("10.0.0.1"), ["10.0.0.1"]);
+    }
+
+    // `*` is a silent hop
+    fn path(hops: &str) -> Vec<Option<String>> {
+        hops.split(' ').map(|hop| (hop != "*").then(|| hop.to_string())).collect()
+    }
+
+    #[test]
+    fn identical_traces_are_no_change() {
+        assert!(!path_changed(&path("10.0.0.1 10.1.0.1 192.0.2.1"), &path("10.0.0.1 10.1.0.1 192.0.2.1")));
+    }
+
+    #[test]
+    fn silent_hops_match_any_router() {
+        assert!(!path_changed(&path("10.0.0.1 10.1.0.1 192.0.2.1"), &path("10.0.0.1 * 192.0.2.1")));
+        assert!(!path_changed(&path("10.0.0.1 * 192.0.2.1"), &path("10.0.0.1 10.1.0.1 192.0.2.1")));
+    }
+
+    #[test]
+    fn another_router_is_a_change() {
+        assert!(path_changed(&path("10.0.0.1 10.1.0.1 192.0.2.1"), &path("10.0.0.1 10.2.0.1 192.0.2.1")));
+        // even when the traces differ in length as well
+        assert!(path_changed(&path("10.0.0.1 10.1.0.1 *"), &path("10.0.0.1 10.2.0.1")));
+    }
+
+    #[test]
+    fn length_only_counts_when_both_traces_reached_the_target() {
+        assert!(path_changed(&path("10.0.0.1 10.1.0.1 192.0.2.1"), &path("10.0.0.1 10.1.0.1 10.3.0.1 192.0.2.1")));
+        assert!(path_changed(&path("10.0.0.1 10.1.0.1 10.3.0.1 192.0.2.1"), &path("10.0.0.1 10.1.0.1 192.0.2.1")));
+        // the shorter or longer trace ran out of hops in silence
+        assert!(!path_changed(&path("10.0.0.1 10.1.0.1 192.0.2.1"), &path("10.0.0.1 10.1.0.1 * *")));
+        assert!(!path_changed(&path("10.0.0.1 10.1.0.1 * *"), &path("10.0.0.1 10.1.0.1")));
+    }
+
+    #[test]
+    fn tracker_reports_changes_against_the_last_informative_trace() {
+        let mut tracker = PathTracker::default();
+        assert_eq!(tracker.update(path("10.0.0.1 192.0.2.1")), None);
+        assert_eq!(tracker.update(path("10.0.0.1 192.0.2.1")), None);
+        // a trace with no answers at all keeps the baseline
+        assert_eq!(tracker.update(path("* *")), None);
+        assert_eq!(
+            tracker.update(path("10.0.0.2 192.0.2.1")).as_deref(),
+            Some("10.0.0.1 > 192.0.2.1 -> 10.0.0.2 > 192.0.2.1")
+        );
+        assert_eq!(tracker.update(path("10.0.0.2 192.0.2.1")), None);
+    }
+
+    #[test]
+    fn clock_shows_the_utc_time_of_day() {
+        assert_eq!(clock(UNIX_EPOCH), "00:00:00 UTC");
+        assert_eq!(clock(UNIX_EPOCH + Duration::from_secs(20_000 * 86_400 + 13 * 3600 + 5 * 60 + 9)), "13:05:09 UTC");
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+/// Outages kept per row, older ones only count in the totals
+const LOG_SIZE: usize = 100;
+
+/// When a run of lost probes counts as an outage and when it is over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutageThresholds {
+    /// consecutive lost probes that start an outage
+    pub after: usize,
+    /// consecutive replies that end it
+    pub recovery: usize,
+}
+
+impl Default for OutageThresholds {
+    fn default() -> Self {
+        Self { after: 3, recovery: 1 }
+    }
+}
+
+/// A run of lost probes. It starts with the first of them and ends with the first of the
+/// replies that ended it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Outage {
+    pub start: SystemTime,
+    /// `None` while it lasts
+    pub end: Option<SystemTime>,
+    /// probes lost during the outage
+    pub lost: usize,
+}
+
+impl Outage {
+    /// How long it lasted, or lasts so far
+    pub fn duration(&self) -> Duration {
+        self.end
+            .unwrap_or_else(SystemTime::now)
+            .duration_since(self.start)
+            .unwrap_or_default()
+    }
+}
+
+/// What a probe changed about the outages of a row
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutageChange {
+    Started(Outage),
+    Ended(Outage),
+}
+
+/// Outages of a row, fed with the outcome of every probe
+#[derive(Debug, Clone, Default)]
+pub struct OutageLog {
+    /// oldest first, the last one may still be ongoing
+    outages: VecDeque<Outage>,
+    total: usize,
+    /// first loss and length of the current run of losses, while it is too short for an outage
+    streak: Option<(SystemTime, usize)>,
+    /// first reply and length of the current run of replies during an outage
+    recovering: Option<(SystemTime, usize)>,
+}
+
+impl OutageLog {
+    pub fn record(&mut self, replied: bool, at: SystemTime, thresholds: OutageThresholds) -> Option<OutageChange> {
+        let ongoing = self.outages.back_mut().filter(|outage| outage.end.is_none());
+        match (replied, ongoing) {
+            (false, Some(outage)) => {
+                // replies in between were not enough to end it
+                outage.lost += 1;
+                self.recovering = None;
+                None
+            }
+            (false, None) => {
+                let (start, lost) = self.streak.map_or((at, 1), |(start, lost)| (start, lost + 1));
+                if lost < thresholds.after {
+                    self.streak = Some((start, lost));
+                    return None;
+                }
+                self.streak = None;
+                let outage = Outage { start, end: None, lost };
+                if self.outages.len() == LOG_SIZE {
+                    self.outages.pop_front();
+                }
+                self.outages.push_back(outage);
+                self.total += 1;
+                Some(OutageChange::Started(outage))
+            }
+            (true, Some(outage)) => {
+                let (first, replies) = self.recovering.map_or((at, 1), |(first, replies)| (first, replies + 1));
+                if replies < thresholds.recovery {
+                    self.recovering = Some((first, replies));
+                    return None;
+                }
+                self.recovering = None;
+                outage.end = Some(first);
+                Some(OutageChange::Ended(*outage))
+            }
+            (true, None) => {
+                self.streak = None;
+                None
+            }
+        }
+    }
+
+    /// The outage the row is in, if any
+    pub fn ongoing(&self) -> Option<&Outage> {
+        self.outages.back().filter(|outage| outage.end.is_none())
+    }
+
+    /// How long the ongoing outage lasted up to `now`. Replies that may yet end it are left
+    /// out, the outage ends with the first of them.
+    pub fn ongoing_for(&self, now: SystemTime) -> Option<Duration> {
+        let until = self.recovering.map_or(now, |(first, _)| first);
+        self.ongoing().map(|outage| until.duration_since(outage.start).unwrap_or_default())
+    }
+
+    /// The logged outages, most recent first
+    pub fn recent(&self) -> impl Iterator<Item = &Outage> {
+        self.outages.iter().rev()
+    }
+
+    /// Outages since the row was started, including those no longer logged
+    pub fn total(&self) -> usize {
+        self.total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::UNIX_EPOCH;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn thresholds(after: usize, recovery: usize) -> OutageThresholds {
+        OutageThresholds { after, recovery }
+    }
+
+    #[test]
+    fn outages_start_after_the_configured_losses() {
+        let mut log = OutageLog::default();
+        let three = thresholds(3, 1);
+        assert_eq!(log.record(false, at(0), three), None);
+        assert_eq!(log.record(false, at(1), three), None);
+        // a reply ends the run before it is long enough
+        assert_eq!(log.record(true, at(2), three), None);
+        assert_eq!(log.record(false, at(3), three), None);
+        assert_eq!(log.record(false, at(4), three), None);
+        assert!(log.ongoing().is_none());
+        let started = Outage { start: at(3), end: None, lost: 3 };
+        assert_eq!(log.record(false, at(5), three), Some(OutageChange::Started(started)));
+        assert_eq!(log.ongoing(), Some(&started));
+        assert_eq!(log.total(), 1);
+    }
+
+    #[test]
+    fn recovery_needs_consecutive_replies() {
+        let mut log = OutageLog::default();
+        let three = thresholds(1, 3);
+        assert!(matches!(log.record(false, at(0), three), Some(OutageChange::Started(_))));
+        assert_eq!(log.record(true, at(1), three), None);
+        assert_eq!(log.record(true, at(2), three), None);
+        // the loss starts the recovery over and counts towards the outage
+        assert_eq!(log.record(false, at(3), three), None);
+        assert_eq!(log.record(true, at(4), three), None);
+        assert_eq!(log.record(true, at(5), three), None);
+        // it ends with the first reply of the run that ended it
+        let ended = Outage { start: at(0), end: Some(at(4)), lost: 2 };
+        assert_eq!(log.record(true, at(6), three), Some(OutageChange::Ended(ended)));
+        assert_eq!(ended.duration(), Duration::from_secs(4));
+        assert!(log.ongoing().is_none());
+        assert_eq!(log.recent().collect::<Vec<_>>(), [&ended]);
+    }
+
+    #[test]
+    fn ongoing_time_leaves_out_the_recovering_replies() {
+        let mut log = OutageLog::default();
+        let two = thresholds(2, 2);
+        log.record(false, at(0), two);
+        assert_eq!(log.ongoing_for(at(1)), None);
+        log.record(false, at(1), two);
+        assert_eq!(log.ongoing_for(at(5)), Some(Duration::from_secs(5)));
+        log.record(true, at(6), two);
+        assert_eq!(log.ongoing_for(at(7)), Some(Duration::from_secs(6)));
+        log.record(true, at(7), two);
+        assert_eq!(log.ongoing_for(at(8)), None);
+    }
+
+    #[test]
+    fn the_oldest_outages_are_evicted() {
+        let mut log = OutageLog::default();
+        let one = thresholds(1, 1);
+        for i in 0..LOG_SIZE as u64 + 5 {
+            log.record(false, at(2 * i), one);
+            log.record(true, at(2 * i + 1), one);
+        }
+        assert_eq!(log.recent().count(), LOG_SIZE);
+        assert_eq!(log.total(), LOG_SIZE + 5);
+        assert_eq!(log.recent().last().unwrap().start, at(10));
+        assert_eq!(log.recent().next().unwrap().start, at(2 * (LOG_SIZE as u64 + 4)));
+    }
+}
@@ -1,12 +1,112 @@
+use crate::ip_data::IpData;
+use crate::probe::{DnsAnswer, HttpCheck, HttpTiming, Source};
+
+/// Updates of one row, identified by target, resolved ip and the source it is probed from
+/// (`None` for the default route)
 #[derive(Debug, Clone)]
 pub enum PingEvent {
     Success {
         addr: String,
         ip: String,
+        source: Option<Source>,
+        seq: u16,
         rtt: f64,
     },
     Timeout {
         addr: String,
         ip: String,
+        source: Option<Source>,
+        seq: u16,
     },
+    /// another reply to a probe that was answered already
+    Duplicate {
+        addr: String,
+        ip: String,
+        source: Option<Source>,
+        seq: u16,
+    },
+    /// a reply to a probe that timed out already, it stays counted as lost
+    Late {
+        addr: String,
+        ip: String,
+        source: Option<Source>,
+        seq: u16,
+        rtt: f64,
+    },
+    /// an ICMP destination unreachable about the probe, counted as lost
+    Unreachable {
+        addr: String,
+        ip: String,
+        source: Option<Source>,
+        seq: u16,
+        icmp_type: u8,
+        code: u8,
+    },
+    Refused {
+        addr: String,
+        ip: String,
+        source: Option<Source>,
+    },
+    Dns {
+        addr: String,
+        ip: String,
+        source: Option<Source>,
+        rtt: f64,
+        answer: DnsAnswer,
+    },
+    Udp {
+        addr: String,
+        ip: String,
+        source: Option<Source>,
+        rtt: Option<f64>,
+        duplicates: u16,
+        reordered: u16,
+    },
+    /// the router sequence to the target changed since the last trace
+    PathChanged {
+        addr: String,
+        ip: String,
+        source: Option<Source>,
+    },
+    /// result of a path MTU check, `None` when no probe size got through
+    Pmtu {
+        addr: String,
+        ip: String,
+        source: Option<Source>,
+        mtu: Option<u16>,
+    },
+    Http {
+        addr: String,
+        ip: String,
+        source: Option<Source>,
+        timing: HttpTiming,
+        check: HttpCheck,
+    },
+    /// outcome of re-resolving the hostname of the target
+    Resolve {
+        addr: String,
+        ip: String,
+        source: Option<Source>,
+        resolution: Resolution,
+    },
+    /// a target added while running, sent before its first probe
+    Added {
+        row: IpData,
+    },
+    /// every row of the target goes away, its probes are stopped
+    Removed {
+        addr: String,
+    },
+    /// the since-reset statistics of every row start over
+    Reset,
+}
+
+/// What a periodic lookup of a target's hostname found
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    /// the name still resolves to the probed address
+    Unchanged,
+    /// the name moved, the new address is probed as a row of its own
+    Changed(String),
+    Failed,
 }
\ No newline at end of file
@@ -0,0 +1,347 @@
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+use anyhow::anyhow;
+
+use crate::probe::{connect_tcp, connect_udp, PacketParams, ProbeResult, Source};
+
+const DNS_HEADER_LEN: usize = 12;
+const FLAG_TRUNCATED: u16 = 0x0200;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const CLASS_IN: u16 = 1;
+
+/// Parsed `dns://resolver/name?type=A` target
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsQuery {
+    pub port: u16,
+    pub name: String,
+    pub qtype: u16,
+}
+
+/// What the resolver answered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DnsAnswer {
+    pub rcode: u8,
+    pub answers: u16,
+    /// the UDP answer was truncated and the query was repeated over TCP
+    pub tcp: bool,
+}
+
+impl DnsAnswer {
+    /// NXDOMAIN is a valid answer as far as resolver health goes
+    pub fn is_ok(&self) -> bool {
+        self.rcode == 0 || self.rcode == 3
+    }
+}
+
+/// Converts a record type name such as `AAAA` (or a plain number) into its code
+pub fn parse_qtype(qtype: &str) -> anyhow::Result<u16> {
+    let code = match qtype.to_ascii_uppercase().as_str() {
+        "A" => 1,
+        "NS" => 2,
+        "CNAME" => 5,
+        "SOA" => 6,
+        "PTR" => 12,
+        "MX" => 15,
+        "TXT" => 16,
+        "AAAA" => 28,
+        "SRV" => 33,
+        "ANY" => 255,
+        other => other
+            .trim_start_matches("TYPE")
+            .parse()
+            .map_err(|_| anyhow!("unknown dns record type: {}", qtype))?,
+    };
+    Ok(code)
+}
+
+/// Display name of a response code
+pub fn rcode_name(rcode: u8) -> String {
+    match rcode {
+        0 => "NOERROR".to_string(),
+        1 => "FORMERR".to_string(),
+        2 => "SERVFAIL".to_string(),
+        3 => "NXDOMAIN".to_string(),
+        4 => "NOTIMP".to_string(),
+        5 => "REFUSED".to_string(),
+        n => format!("RCODE{}", n),
+    }
+}
+
+/// Sends DNS queries to one resolver and times the answers
+pub struct DnsPinger {
+    resolver: SocketAddr,
+    query: DnsQuery,
+    socket: UdpSocket,
+    params: PacketParams,
+    source: Option<Source>,
+    id_base: u16,
+}
+
+impl DnsPinger {
+    pub fn new(ip: IpAddr, query: DnsQuery, params: PacketParams, source: Option<Source>) -> io::Result<Self> {
+        let resolver = SocketAddr::new(ip, query.port);
+        let socket = connect_udp(resolver, &params, source.as_ref())?;
+
+        Ok(Self {
+            resolver,
+            query,
+            socket,
+            params,
+            source,
+            id_base: std::process::id() as u16,
+        })
+    }
+
+    /// Sends one query over UDP, repeating it over TCP when the answer is truncated.
+    /// The reported time covers both legs.
+    pub fn ping(&mut self, seq: u16, timeout: Duration) -> io::Result<ProbeResult> {
+        let id = self.id_base.wrapping_add(seq);
+        let packet = build_query(id, &self.query.name, self.query.qtype)?;
+
+        let started = Instant::now();
+        let deadline = started + timeout;
+        self.socket.send(&packet)?;
+
+        let mut buf = [0u8; 4096];
+        let (flags, answers) = loop {
+            let Some(left) = deadline.checked_duration_since(Instant::now()).filter(|d| !d.is_zero()) else {
+                return Ok(ProbeResult::Timeout { seq });
+            };
+            self.socket.set_read_timeout(Some(left))?;
+            let len = match self.socket.recv(&mut buf) {
+                Ok(len) => len,
+                Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    return Ok(ProbeResult::Timeout { seq });
+                }
+                // ICMP port unreachable from the resolver host
+                Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => return Ok(ProbeResult::Refused { seq }),
+                Err(err) => return Err(err),
+            };
+            // answers to earlier, timed out queries are skipped
+            if let Some((reply_id, flags, answers)) = parse_header(&buf[..len]) {
+                if reply_id == id {
+                    break (flags, answers);
+                }
+            }
+        };
+
+        let answer = if flags & FLAG_TRUNCATED != 0 {
+            match self.query_tcp(id, &packet, deadline) {
+                Ok((flags, answers)) => DnsAnswer { rcode: (flags & 0x000f) as u8, answers, tcp: true },
+                Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    return Ok(ProbeResult::Timeout { seq });
+                }
+                Err(err) => return Err(err),
+            }
+        } else {
+            DnsAnswer { rcode: (flags & 0x000f) as u8, answers, tcp: false }
+        };
+
+        Ok(ProbeResult::Dns { seq, rtt: started.elapsed(), answer })
+    }
+
+    fn query_tcp(&self, id: u16, packet: &[u8], deadline: Instant) -> io::Result<(u16, u16)> {
+        let left = deadline
+            .checked_duration_since(Instant::now())
+            .filter(|d| !d.is_zero())
+            .ok_or_else(|| io::Error::from(io::ErrorKind::TimedOut))?;
+        let mut stream = connect_tcp(self.resolver, left, &self.params, self.source.as_ref())?;
+        stream.set_read_timeout(Some(left))?;
+        stream.set_write_timeout(Some(left))?;
+
+        // TCP messages are prefixed with their length
+        let mut message = (packet.len() as u16).to_be_bytes().to_vec();
+        message.extend_from_slice(packet);
+        stream.write_all(&message)?;
+
+        let mut len = [0u8; 2];
+        stream.read_exact(&mut len)?;
+        let mut response = vec![0u8; u16::from_be_bytes(len) as usize];
+        stream.read_exact(&mut response)?;
+
+        match parse_header(&response) {
+            Some((reply_id, flags, answers)) if reply_id == id => Ok((flags, answers)),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected dns response over tcp")),
+        }
+    }
+}
+
+fn build_query(id: u16, name: &str, qtype: u16) -> io::Result<Vec<u8>> {
+    let mut packet = Vec::with_capacity(DNS_HEADER_LEN + name.len() + 6);
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&FLAG_RECURSION_DESIRED.to_be_bytes());
+    packet.extend_from_slice(&1u16.to_be_bytes()); // qdcount
+    packet.extend_from_slice(&[0, 0, 0, 0, 0, 0]); // ancount, nscount, arcount
+
+    for label in name.trim_end_matches('.').split('.').filter(|l| !l.is_empty()) {
+        if label.len() > 63 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("dns label too long: {}", label)));
+        }
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(packet)
+}
+
+// returns (id, flags, answer count)
+fn parse_header(data: &[u8]) -> Option<(u16, u16, u16)> {
+    if data.len() < DNS_HEADER_LEN {
+        return None;
+    }
+    let id = u16::from_be_bytes([data[0], data[1]]);
+    let flags = u16::from_be_bytes([data[2], data[3]]);
+    // only responses (QR bit set)
+    if flags & 0x8000 == 0 {
+        return None;
+    }
+    let answers = u16::from_be_bytes([data[6], data[7]]);
+    Some((id, flags, answers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, TcpListener};
+
+    const WAIT: Duration = Duration::from_millis(500);
+
+    // answers by the first label of the query name: the rcode, an answer count, `truncated`
+    // (to be asked again over TCP), or `wrongid` (an answer for another query first)
+    // and `strayonly` (never the right one)
+    fn respond(query: &[u8]) -> Vec<Vec<u8>> {
+        let label = &query[DNS_HEADER_LEN + 1..DNS_HEADER_LEN + 1 + query[DNS_HEADER_LEN] as usize];
+        let (rcode, answers): (u16, u16) = match label {
+            b"servfail" => (2, 0),
+            b"nxdomain" => (3, 0),
+            b"refused" => (5, 0),
+            b"three" => (0, 3),
+            b"truncated" => (0, 0),
+            _ => (0, 1),
+        };
+        let truncated = if label == b"truncated" { FLAG_TRUNCATED } else { 0 };
+        let mut answer = query.to_vec();
+        answer[2..4].copy_from_slice(&(0x8180u16 | truncated | rcode).to_be_bytes());
+        answer[6..8].copy_from_slice(&answers.to_be_bytes());
+        let mut stray = answer.clone();
+        stray[1] = stray[1].wrapping_add(1);
+        match label {
+            b"wrongid" => vec![stray, answer],
+            b"strayonly" => vec![stray],
+            _ => vec![answer],
+        }
+    }
+
+    // the full answer over TCP, always five records
+    fn respond_tcp(mut stream: std::net::TcpStream) {
+        let mut len = [0u8; 2];
+        stream.read_exact(&mut len).unwrap();
+        let mut answer = vec![0u8; u16::from_be_bytes(len) as usize];
+        stream.read_exact(&mut answer).unwrap();
+        answer[2..4].copy_from_slice(&0x8180u16.to_be_bytes());
+        answer[6..8].copy_from_slice(&5u16.to_be_bytes());
+        let mut message = len.to_vec();
+        message.extend_from_slice(&answer);
+        stream.write_all(&message).unwrap();
+    }
+
+    // a resolver on an ephemeral loopback port for UDP and TCP, running until the test process exits
+    fn serve() -> u16 {
+        let (socket, listener) = loop {
+            let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let port = socket.local_addr().unwrap().port();
+            if let Ok(listener) = TcpListener::bind((Ipv4Addr::LOCALHOST, port)) {
+                break (socket, listener);
+            }
+        };
+        let port = socket.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            while let Ok((len, from)) = socket.recv_from(&mut buf) {
+                for packet in respond(&buf[..len]) {
+                    socket.send_to(&packet, from).unwrap();
+                }
+            }
+        });
+        std::thread::spawn(move || {
+            for stream in listener.incoming().map_while(Result::ok) {
+                respond_tcp(stream);
+            }
+        });
+        port
+    }
+
+    fn query(port: u16, name: &str) -> ProbeResult {
+        let query = DnsQuery { port, name: name.to_string(), qtype: 1 };
+        let mut pinger = DnsPinger::new(IpAddr::V4(Ipv4Addr::LOCALHOST), query, PacketParams::default(), None).unwrap();
+        pinger.ping(1, WAIT).unwrap()
+    }
+
+    fn answer(port: u16, name: &str) -> DnsAnswer {
+        match query(port, name) {
+            ProbeResult::Dns { answer, rtt, .. } => {
+                assert!(rtt < WAIT);
+                answer
+            }
+            other => panic!("expected an answer, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn maps_the_rcode() {
+        let port = serve();
+        let ok = answer(port, "example.test");
+        assert_eq!((ok.rcode, ok.is_ok(), ok.tcp), (0, true, false));
+        let nxdomain = answer(port, "nxdomain.example.test");
+        assert_eq!((nxdomain.rcode, nxdomain.is_ok()), (3, true));
+        let servfail = answer(port, "servfail.example.test");
+        assert_eq!((servfail.rcode, servfail.is_ok()), (2, false));
+        assert_eq!(rcode_name(servfail.rcode), "SERVFAIL");
+        assert_eq!(rcode_name(answer(port, "refused.example.test").rcode), "REFUSED");
+        assert_eq!(rcode_name(9), "RCODE9");
+    }
+
+    #[test]
+    fn counts_the_answers() {
+        let port = serve();
+        assert_eq!(answer(port, "example.test").answers, 1);
+        assert_eq!(answer(port, "three.example.test").answers, 3);
+        assert_eq!(answer(port, "nxdomain.example.test").answers, 0);
+    }
+
+    #[test]
+    fn skips_answers_with_another_transaction_id() {
+        let port = serve();
+        assert_eq!(answer(port, "wrongid.example.test").answers, 1);
+        assert_eq!(query(port, "strayonly.example.test"), ProbeResult::Timeout { seq: 1 });
+    }
+
+    #[test]
+    fn truncated_answers_are_asked_again_over_tcp() {
+        let port = serve();
+        let truncated = answer(port, "truncated.example.test");
+        assert_eq!((truncated.rcode, truncated.answers, truncated.tcp), (0, 5, true));
+        // untruncated answers never touch TCP
+        assert!(!answer(port, "three.example.test").tcp);
+    }
+
+    #[test]
+    fn parses_record_types() {
+        assert_eq!(parse_qtype("aaaa").unwrap(), 28);
+        assert_eq!(parse_qtype("MX").unwrap(), 15);
+        assert_eq!(parse_qtype("TYPE65").unwrap(), 65);
+        assert_eq!(parse_qtype("99").unwrap(), 99);
+        assert!(parse_qtype("BOGUS").is_err());
+    }
+
+    #[test]
+    fn encodes_the_query_name() {
+        let packet = build_query(0x1234, "www.example.test.", 28).unwrap();
+        assert_eq!(&packet[..2], &[0x12, 0x34]);
+        assert_eq!(&packet[DNS_HEADER_LEN..packet.len() - 4], b"\x03www\x07example\x04test\x00");
+        assert!(build_query(1, &"a".repeat(64), 1).is_err());
+    }
+}
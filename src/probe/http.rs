@@ -0,0 +1,397 @@
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+
+use crate::probe::{connect_tcp, PacketParams, ProbeResult, Source};
+
+// bodies are only needed for matching, don't buffer whole downloads
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// Parsed `http://` or `https://` target
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpUrl {
+    pub tls: bool,
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+/// Per-phase timing of one request, each phase measured on its own
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct HttpTiming {
+    pub dns: Duration,
+    pub connect: Duration,
+    pub tls: Duration,
+    /// from request written to first response byte
+    pub ttfb: Duration,
+    /// whole request including reading the body
+    pub total: Duration,
+    pub status: u16,
+}
+
+/// Whether a completed request met the configured expectations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HttpCheck {
+    Passed,
+    UnexpectedStatus,
+    BodyMismatch,
+}
+
+/// What a response has to look like to count as healthy
+#[derive(Debug, Clone, Default)]
+pub struct HttpExpect {
+    /// accepted status codes, any 2xx/3xx when empty
+    pub status: Vec<u16>,
+    /// substring the body has to contain
+    pub body: Option<String>,
+}
+
+/// Times HTTP(S) GET requests against one resolved address
+pub struct HttpPinger {
+    url: HttpUrl,
+    ip: IpAddr,
+    expect: HttpExpect,
+    params: PacketParams,
+    source: Option<Source>,
+    tls_config: Option<Arc<ClientConfig>>,
+}
+
+impl HttpPinger {
+    pub fn new(url: HttpUrl, ip: IpAddr, expect: HttpExpect, params: PacketParams, source: Option<Source>) -> io::Result<Self> {
+        let tls_config = if url.tls {
+            let roots = RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+            let config = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .map_err(io::Error::other)?
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            Some(Arc::new(config))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            url,
+            ip,
+            expect,
+            params,
+            source,
+            tls_config,
+        })
+    }
+
+    /// Performs one GET request. The host name is looked up again on every request so the
+    /// DNS phase reflects the resolver, but the connection always goes to the pinned address.
+    pub fn ping(&mut self, seq: u16, timeout: Duration) -> io::Result<ProbeResult> {
+        let started = Instant::now();
+        let deadline = started + timeout;
+        let mut timing = HttpTiming::default();
+
+        if self.url.host.parse::<IpAddr>().is_err() {
+            (self.url.host.as_str(), self.url.port).to_socket_addrs()?;
+        }
+        timing.dns = started.elapsed();
+
+        let phase = Instant::now();
+        let stream = match connect_tcp(SocketAddr::new(self.ip, self.url.port), remaining(deadline)?, &self.params, self.source.as_ref()) {
+            Ok(stream) => stream,
+            Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => return Ok(ProbeResult::Refused { seq }),
+            Err(err) if is_timeout(&err) => return Ok(ProbeResult::Timeout { seq }),
+            Err(err) => return Err(err),
+        };
+        timing.connect = phase.elapsed();
+
+        // shares the socket, its timeouts are cut down to the time left before every read and write
+        let socket = stream.try_clone()?;
+        let result = match &self.tls_config {
+            Some(config) => {
+                let phase = Instant::now();
+                self.handshake(config.clone(), stream, &socket, deadline).and_then(|tls_stream| {
+                    timing.tls = phase.elapsed();
+                    self.exchange(tls_stream, &socket, deadline, &mut timing)
+                })
+            }
+            None => self.exchange(stream, &socket, deadline, &mut timing),
+        };
+
+        let body = match result {
+            Ok(body) => body,
+            Err(err) if is_timeout(&err) => return Ok(ProbeResult::Timeout { seq }),
+            Err(err) => return Err(err),
+        };
+        timing.total = started.elapsed();
+
+        let status_ok = if self.expect.status.is_empty() {
+            (200..400).contains(&timing.status)
+        } else {
+            self.expect.status.contains(&timing.status)
+        };
+        let check = if !status_ok {
+            HttpCheck::UnexpectedStatus
+        } else if self.expect.body.as_ref().is_some_and(|want| !String::from_utf8_lossy(&body).contains(want.as_str())) {
+            HttpCheck::BodyMismatch
+        } else {
+            HttpCheck::Passed
+        };
+
+        Ok(ProbeResult::Http { seq, timing, check })
+    }
+
+    fn handshake(&self, config: Arc<ClientConfig>, mut stream: TcpStream, socket: &TcpStream, deadline: Instant) -> io::Result<StreamOwned<ClientConnection, TcpStream>> {
+        let server_name = ServerName::try_from(self.url.host.clone())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let mut conn = ClientConnection::new(config, server_name).map_err(io::Error::other)?;
+        while conn.is_handshaking() {
+            shorten_timeouts(socket, deadline)?;
+            conn.complete_io(&mut stream)?;
+        }
+        Ok(StreamOwned::new(conn, stream))
+    }
+
+    // writes the request and reads the response, returns the decoded body
+    fn exchange<S: Read + Write>(&self, mut stream: S, socket: &TcpStream, deadline: Instant, timing: &mut HttpTiming) -> io::Result<Vec<u8>> {
+        let host = if (self.url.tls && self.url.port == 443) || (!self.url.tls && self.url.port == 80) {
+            self.url.host.clone()
+        } else {
+            format!("{}:{}", self.url.host, self.url.port)
+        };
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: pingwatch\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+            self.url.path, host
+        );
+        shorten_timeouts(socket, deadline)?;
+        stream.write_all(request.as_bytes())?;
+        stream.flush()?;
+        let sent = Instant::now();
+
+        let mut response = Vec::new();
+        let mut buf = [0u8; 8192];
+        let mut header_end = None;
+        let mut content_length = None;
+        loop {
+            shorten_timeouts(socket, deadline)?;
+            let n = match stream.read(&mut buf) {
+                Ok(n) => n,
+                // servers often close without a TLS close_notify when asked for `Connection: close`
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof && !response.is_empty() => 0,
+                Err(err) => return Err(err),
+            };
+            if n == 0 {
+                break;
+            }
+            if response.is_empty() {
+                timing.ttfb = sent.elapsed();
+            }
+            response.extend_from_slice(&buf[..n]);
+
+            if header_end.is_none() {
+                if let Some(pos) = find(&response, b"\r\n\r\n") {
+                    header_end = Some(pos + 4);
+                    let head = String::from_utf8_lossy(&response[..pos]).to_string();
+                    timing.status = parse_status(&head)?;
+                    content_length = header_value(&head, "content-length").and_then(|v| v.parse::<usize>().ok());
+                }
+            }
+            if let Some(end) = header_end {
+                let body_len = response.len() - end;
+                if content_length.is_some_and(|len| body_len >= len) || body_len >= MAX_BODY_SIZE {
+                    break;
+                }
+            }
+        }
+
+        let end = header_end.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "incomplete http response"))?;
+        let head = String::from_utf8_lossy(&response[..end]).to_string();
+        let body = &response[end..];
+        if header_value(&head, "transfer-encoding").is_some_and(|v| v.eq_ignore_ascii_case("chunked")) {
+            Ok(decode_chunked(body).unwrap_or_else(|| body.to_vec()))
+        } else {
+            Ok(body.to_vec())
+        }
+    }
+}
+
+fn remaining(deadline: Instant) -> io::Result<Duration> {
+    deadline
+        .checked_duration_since(Instant::now())
+        .filter(|d| !d.is_zero())
+        .ok_or_else(|| io::Error::from(io::ErrorKind::TimedOut))
+}
+
+// no single read or write may wait past the probe deadline
+fn shorten_timeouts(socket: &TcpStream, deadline: Instant) -> io::Result<()> {
+    let budget = remaining(deadline)?;
+    socket.set_read_timeout(Some(budget))?;
+    socket.set_write_timeout(Some(budget))
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(err.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn parse_status(head: &str) -> io::Result<u16> {
+    head.lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed http status line"))
+}
+
+fn header_value<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+fn decode_chunked(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = find(data, b"\r\n")?;
+        let size_str = std::str::from_utf8(&data[..line_end]).ok()?;
+        let size = usize::from_str_radix(size_str.split(';').next()?.trim(), 16).ok()?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Some(body);
+        }
+        body.extend_from_slice(data.get(..size)?);
+        data = data.get(size + 2..)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use std::net::Ipv4Addr;
+    use http_body_util::Full;
+    use hyper::body::Bytes;
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use hyper::{Request, Response, StatusCode};
+    use hyper_util::rt::TokioIo;
+
+    const SLOW: Duration = Duration::from_millis(50);
+    const WAIT: Duration = Duration::from_secs(2);
+
+    async fn respond(req: Request<hyper::body::Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
+        let (status, body) = match req.uri().path() {
+            "/ok" => (StatusCode::OK, "pingwatch is healthy"),
+            "/slow" => {
+                tokio::time::sleep(SLOW).await;
+                (StatusCode::OK, "late but fine")
+            }
+            _ => (StatusCode::NOT_FOUND, "not found"),
+        };
+        Ok(Response::builder().status(status).body(Full::new(Bytes::from(body))).unwrap())
+    }
+
+    // a hyper server on an ephemeral loopback port, running until the test process exits
+    fn serve() -> u16 {
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        listener.set_nonblocking(true).unwrap();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    tokio::spawn(http1::Builder::new().serve_connection(TokioIo::new(stream), service_fn(respond)));
+                }
+            });
+        });
+        port
+    }
+
+    fn pinger(port: u16, path: &str, expect: HttpExpect) -> HttpPinger {
+        let url = HttpUrl { tls: false, host: String::from("127.0.0.1"), port, path: path.to_string() };
+        HttpPinger::new(url, IpAddr::V4(Ipv4Addr::LOCALHOST), expect, PacketParams::default(), None).unwrap()
+    }
+
+    fn probe(pinger: &mut HttpPinger) -> (HttpTiming, HttpCheck) {
+        match pinger.ping(0, WAIT).unwrap() {
+            ProbeResult::Http { timing, check, .. } => (timing, check),
+            other => panic!("expected an http result, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn times_every_phase() {
+        let port = serve();
+        let (timing, check) = probe(&mut pinger(port, "/slow", HttpExpect::default()));
+        assert_eq!(check, HttpCheck::Passed);
+        assert_eq!(timing.status, 200);
+        // an address needs no lookup and plain http no handshake
+        assert_eq!(timing.tls, Duration::ZERO);
+        assert!(timing.dns < SLOW);
+        assert!(timing.connect < SLOW);
+        assert!(timing.ttfb >= SLOW);
+        assert!(timing.total >= timing.dns + timing.connect + timing.ttfb);
+    }
+
+    #[test]
+    fn checks_the_status() {
+        let port = serve();
+        let (timing, check) = probe(&mut pinger(port, "/missing", HttpExpect::default()));
+        assert_eq!(timing.status, 404);
+        assert_eq!(check, HttpCheck::UnexpectedStatus);
+
+        let expect = HttpExpect { status: vec![404], body: None };
+        assert_eq!(probe(&mut pinger(port, "/missing", expect)).1, HttpCheck::Passed);
+        let expect = HttpExpect { status: vec![204], body: None };
+        assert_eq!(probe(&mut pinger(port, "/ok", expect)).1, HttpCheck::UnexpectedStatus);
+    }
+
+    #[test]
+    fn checks_the_body() {
+        let port = serve();
+        let expect = HttpExpect { status: Vec::new(), body: Some(String::from("healthy")) };
+        assert_eq!(probe(&mut pinger(port, "/ok", expect)).1, HttpCheck::Passed);
+        let expect = HttpExpect { status: Vec::new(), body: Some(String::from("degraded")) };
+        assert_eq!(probe(&mut pinger(port, "/ok", expect)).1, HttpCheck::BodyMismatch);
+    }
+
+    #[test]
+    fn closed_port_is_refused() {
+        // a port that was free a moment ago, connections to it are reset
+        let port = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port();
+        let result = pinger(port, "/", HttpExpect::default()).ping(0, WAIT).unwrap();
+        assert_eq!(result, ProbeResult::Refused { seq: 0 });
+    }
+
+    #[test]
+    fn stalled_response_times_out_at_the_deadline() {
+        let listener = std::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // half a status line late in the probe, then nothing until the client gives up
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            assert!(stream.read(&mut [0u8; 1024]).unwrap() > 0);
+            std::thread::sleep(WAIT / 2);
+            stream.write_all(b"HTTP/1.1 200").unwrap();
+            std::thread::sleep(WAIT * 2);
+        });
+
+        let started = Instant::now();
+        let result = pinger(port, "/", HttpExpect::default()).ping(0, WAIT).unwrap();
+        assert_eq!(result, ProbeResult::Timeout { seq: 0 });
+        assert!(started.elapsed() < WAIT + Duration::from_millis(200), "took {:?}", started.elapsed());
+    }
+
+    #[test]
+    fn decodes_chunked_bodies() {
+        assert_eq!(decode_chunked(b"5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\n\r\n").unwrap(), b"hello world");
+        assert!(decode_chunked(b"5\r\nhel").is_none());
+    }
+}
@@ -0,0 +1,675 @@
+use std::collections::{HashSet, VecDeque};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+
+use crate::probe::{self, PacketParams, ProbeResult, Source, StrayReply};
+
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_DEST_UNREACHABLE: u8 = 3;
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_TIME_EXCEEDED: u8 = 11;
+const ICMPV6_DEST_UNREACHABLE: u8 = 1;
+const ICMPV6_TIME_EXCEEDED: u8 = 3;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+
+const IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+
+const ICMP_HEADER_LEN: usize = 8;
+const DEFAULT_PAYLOAD_SIZE: usize = 56;
+// path discovery sends every TTL this many times to fill in hops that drop a reply
+const TRACE_ROUNDS: u16 = 3;
+// path MTU discovery retries every size so a single lost reply doesn't shrink the result
+const PMTU_ATTEMPTS: u16 = 2;
+// smallest MTU every link has to carry (RFC 791 / RFC 8200)
+const IPV4_MIN_MTU: u16 = 68;
+const IPV6_MIN_MTU: u16 = 1280;
+// how many recent requests are remembered to tell late and duplicate replies apart
+const SENT_WINDOW: usize = 64;
+
+// every pinger gets its own identifier so raw sockets can tell replies apart
+static NEXT_IDENT: AtomicU16 = AtomicU16::new(0);
+
+// the ICMP messages we care about, with the identifier and sequence of our echo request
+enum IcmpMessage {
+    EchoReply { ident: u16, seq: u16 },
+    TimeExceeded { ident: u16, seq: u16 },
+    Unreachable { ident: u16, seq: u16, icmp_type: u8, code: u8 },
+}
+
+// a request of the sent window and whether a reply to it came back
+struct Sent {
+    seq: u16,
+    at: Instant,
+    answered: bool,
+}
+
+/// ICMP/ICMPv6 echo client bound to a single target address.
+///
+/// Unprivileged datagram sockets are tried first, raw sockets are used when the
+/// system does not allow them (e.g. `net.ipv4.ping_group_range` excludes us).
+pub struct IcmpPinger {
+    socket: UdpSocket,
+    target: SocketAddr,
+    ident: u16,
+    // datagram sockets on Linux rewrite the identifier to the socket "port"
+    kernel_ident: Option<u16>,
+    // datagram sockets get ICMP errors through the error queue, raw ones read them
+    error_queue: bool,
+    payload_size: usize,
+    sent: VecDeque<Sent>,
+    // replies to earlier requests seen while waiting, until they are taken
+    strays: Vec<StrayReply>,
+}
+
+impl IcmpPinger {
+    pub fn new(ip: IpAddr, source: Option<&Source>) -> io::Result<Self> {
+        Self::open(ip, true, source)
+    }
+
+    /// Opens a raw socket only. Datagram sockets don't see ICMP errors such as
+    /// time exceeded on a plain read, which path discovery depends on.
+    pub fn new_raw(ip: IpAddr, source: Option<&Source>) -> io::Result<Self> {
+        Self::open(ip, false, source)
+    }
+
+    fn open(ip: IpAddr, allow_dgram: bool, source: Option<&Source>) -> io::Result<Self> {
+        let (domain, protocol, unspecified) = match ip {
+            IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4, IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+            IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6, IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+        };
+
+        let dgram = if allow_dgram {
+            Socket::new(domain, Type::DGRAM, Some(protocol))
+        } else {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "not requested"))
+        };
+        let (socket, raw) = match dgram {
+            Ok(socket) => (socket, false),
+            Err(dgram_err) => match Socket::new(domain, Type::RAW, Some(protocol)) {
+                Ok(socket) => (socket, true),
+                Err(raw_err) => {
+                    return Err(io::Error::new(
+                        raw_err.kind(),
+                        format!("unable to open icmp socket, datagram: {}, raw: {}", dgram_err, raw_err),
+                    ));
+                }
+            },
+        };
+
+        // datagram sockets get their identifier assigned on bind, so they are always bound.
+        // Windows has no datagram icmp sockets and refuses to read from unbound raw ones.
+        match source {
+            Some(source) => source.bind(&socket, ip)?,
+            None if !raw || cfg!(windows) => socket.bind(&SocketAddr::new(unspecified, 0).into())?,
+            None => {}
+        }
+        let kernel_ident = if raw {
+            None
+        } else {
+            socket.local_addr()?.as_socket().map(|addr| addr.port())
+        };
+        // without it unconnected datagram sockets never hear of unreachable destinations
+        let error_queue = !raw && enable_error_queue(&socket, ip.is_ipv6()).is_ok();
+
+        let ident = (std::process::id() as u16).wrapping_add(NEXT_IDENT.fetch_add(1, Ordering::Relaxed));
+
+        Ok(Self {
+            socket: socket.into(),
+            target: SocketAddr::new(ip, 0),
+            ident,
+            kernel_ident,
+            error_queue,
+            payload_size: DEFAULT_PAYLOAD_SIZE,
+            sent: VecDeque::new(),
+            strays: Vec::new(),
+        })
+    }
+
+    /// Sends one echo request and waits up to `timeout` for the matching reply.
+    /// Replies to older sequence numbers seen meanwhile are kept as late or duplicate,
+    /// see [`take_strays`](Self::take_strays). Destination unreachable shows up on raw
+    /// sockets and on Linux datagram sockets, elsewhere datagram sockets time out instead.
+    pub fn ping(&mut self, seq: u16, timeout: Duration) -> io::Result<ProbeResult> {
+        let packet = self.build_request(seq);
+
+        let sent_at = Instant::now();
+        self.socket.send_to(&packet, self.target)?;
+        let deadline = sent_at + timeout;
+        if self.sent.len() == SENT_WINDOW {
+            self.sent.pop_front();
+        }
+        self.sent.push_back(Sent { seq, at: sent_at, answered: false });
+
+        // big payloads come back just as big
+        let mut buf = vec![0u8; 2048.max(IPV6_HEADER_LEN + ICMP_HEADER_LEN + self.payload_size)];
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(ProbeResult::Timeout { seq });
+            }
+            self.socket.set_read_timeout(Some(deadline - now))?;
+
+            let (len, from) = match self.socket.recv_from(&mut buf) {
+                Ok(res) => res,
+                Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    return Ok(ProbeResult::Timeout { seq });
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                // an ICMP error queued for a datagram socket fails the read
+                Err(err) if self.error_queue => {
+                    let errors = self.take_queued_errors()?;
+                    if errors.is_empty() {
+                        return Err(err);
+                    }
+                    let unreachable = errors.into_iter().find_map(|error| match error {
+                        IcmpMessage::Unreachable { ident, seq: reply_seq, icmp_type, code }
+                            if self.ident_matches(ident) && reply_seq == seq => Some(ProbeResult::Unreachable { seq, icmp_type, code }),
+                        _ => None,
+                    });
+                    if let Some(result) = unreachable {
+                        return Ok(result);
+                    }
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            let received_at = Instant::now();
+
+            match self.parse_message(&buf[..len]) {
+                Some(IcmpMessage::EchoReply { ident, seq: reply_seq }) if self.ident_matches(ident) && from.ip() == self.target.ip() => {
+                    // requests that fell out of the window are too old to tell
+                    let Some(sent) = self.sent.iter_mut().rev().find(|sent| sent.seq == reply_seq) else {
+                        continue;
+                    };
+                    if sent.answered {
+                        self.strays.push(StrayReply::Duplicate { seq: reply_seq });
+                    } else if reply_seq == seq {
+                        sent.answered = true;
+                        return Ok(ProbeResult::Reply {
+                            seq,
+                            rtt: received_at.duration_since(sent_at),
+                        });
+                    } else {
+                        // its probe timed out already
+                        sent.answered = true;
+                        let rtt = received_at.duration_since(sent.at);
+                        self.strays.push(StrayReply::Late { seq: reply_seq, rtt });
+                    }
+                }
+                // sent by a router on the way, its address doesn't matter
+                Some(IcmpMessage::Unreachable { ident, seq: reply_seq, icmp_type, code }) if self.ident_matches(ident) && reply_seq == seq => {
+                    return Ok(ProbeResult::Unreachable { seq, icmp_type, code });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Takes the late and duplicate replies seen since the last call, in order of arrival
+    pub fn take_strays(&mut self) -> Vec<StrayReply> {
+        std::mem::take(&mut self.strays)
+    }
+
+    /// Sets the TTL (hop limit for IPv6) of the following requests
+    pub fn set_ttl(&self, ttl: u32) -> io::Result<()> {
+        let socket = SockRef::from(&self.socket);
+        match self.target {
+            SocketAddr::V4(_) => socket.set_ttl(ttl),
+            SocketAddr::V6(_) => socket.set_unicast_hops_v6(ttl),
+        }
+    }
+
+    /// Applies size, TTL, DSCP and DF settings to the following requests
+    pub fn set_params(&mut self, params: &PacketParams) -> io::Result<()> {
+        if let Some(size) = params.size {
+            self.payload_size = size;
+        }
+        params.apply(&SockRef::from(&self.socket), self.target.is_ipv6())
+    }
+
+    /// Sets or clears the Don't-Fragment bit of the following requests
+    pub fn set_dont_fragment(&self, on: bool) -> io::Result<()> {
+        probe::set_dont_fragment(&SockRef::from(&self.socket), self.target.is_ipv6(), on)
+    }
+
+    /// Finds the largest packet (IP header included) that reaches the target unfragmented
+    /// by binary searching the size of Don't-Fragment echo requests between the minimum
+    /// MTU of the address family and `max_mtu`. Returns `None` when not even the minimum
+    /// size got an answer, i.e. the target is down or drops pings.
+    pub fn discover_pmtu(&mut self, max_mtu: u16, timeout: Duration) -> io::Result<Option<u16>> {
+        let (header_len, min_mtu) = match self.target {
+            SocketAddr::V4(_) => ((IPV4_HEADER_LEN + ICMP_HEADER_LEN) as u16, IPV4_MIN_MTU),
+            SocketAddr::V6(_) => ((IPV6_HEADER_LEN + ICMP_HEADER_LEN) as u16, IPV6_MIN_MTU),
+        };
+        let default_payload = self.payload_size;
+        self.set_dont_fragment(true)?;
+
+        let mut seq: u16 = 0;
+        let mut fits = |pinger: &mut Self, mtu: u16| -> io::Result<bool> {
+            pinger.payload_size = (mtu - header_len) as usize;
+            for _ in 0..PMTU_ATTEMPTS {
+                seq = seq.wrapping_add(1);
+                match pinger.ping(seq, timeout) {
+                    Ok(ProbeResult::Reply { .. }) => return Ok(true),
+                    Ok(_) => {}
+                    // larger than the MTU of the outgoing interface
+                    Err(err) if err.raw_os_error() == Some(libc::EMSGSIZE) => return Ok(false),
+                    Err(err) => return Err(err),
+                }
+            }
+            Ok(false)
+        };
+
+        let result = (|| {
+            if !fits(self, min_mtu)? {
+                return Ok(None);
+            }
+            // invariant: lo gets through, everything above hi doesn't
+            let (mut lo, mut hi) = (min_mtu, max_mtu.max(min_mtu));
+            while lo < hi {
+                let mid = lo + (hi - lo).div_ceil(2);
+                if fits(self, mid)? {
+                    lo = mid;
+                } else {
+                    hi = mid - 1;
+                }
+            }
+            Ok(Some(lo))
+        })();
+
+        self.payload_size = default_payload;
+        self.set_dont_fragment(false)?;
+        result
+    }
+
+    fn ttl(&self) -> io::Result<u32> {
+        let socket = SockRef::from(&self.socket);
+        match self.target {
+            SocketAddr::V4(_) => socket.ttl(),
+            SocketAddr::V6(_) => socket.unicast_hops_v6(),
+        }
+    }
+
+    /// Discovers the routers on the way to the target by sending echo requests with
+    /// increasing TTLs and collecting the time exceeded replies. The returned list holds
+    /// one entry per hop, `None` for hops that never answered, and ends with the target
+    /// itself when it was reached within `max_hops`.
+    pub fn discover_path(&mut self, max_hops: u8, timeout: Duration) -> io::Result<Vec<Option<IpAddr>>> {
+        let max_hops = max_hops.max(1);
+        let default_ttl = self.ttl()?;
+        let round_timeout = timeout / TRACE_ROUNDS as u32;
+
+        let mut hops: Vec<Option<IpAddr>> = vec![None; max_hops as usize];
+        let mut reached: Option<u8> = None;
+        let mut buf = [0u8; 2048];
+
+        for round in 0..TRACE_ROUNDS {
+            // the low byte of the sequence carries the TTL the request was sent with
+            for ttl in 1..=max_hops {
+                if reached.is_some_and(|r| ttl > r) {
+                    break;
+                }
+                self.set_ttl(ttl as u32)?;
+                let packet = self.build_request(round << 8 | ttl as u16);
+                self.socket.send_to(&packet, self.target)?;
+            }
+            self.set_ttl(default_ttl)?;
+
+            let deadline = Instant::now() + round_timeout;
+            loop {
+                let last = reached.unwrap_or(max_hops) as usize;
+                if reached.is_some() && hops[..last - 1].iter().all(Option::is_some) {
+                    break;
+                }
+                let Some(left) = deadline.checked_duration_since(Instant::now()).filter(|d| !d.is_zero()) else {
+                    break;
+                };
+                self.socket.set_read_timeout(Some(left))?;
+                let (len, from) = match self.socket.recv_from(&mut buf) {
+                    Ok(res) => res,
+                    Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => return Err(err),
+                };
+
+                match self.parse_message(&buf[..len]) {
+                    Some(IcmpMessage::TimeExceeded { ident, seq }) if self.ident_matches(ident) => {
+                        let ttl = (seq & 0xff) as u8;
+                        if (1..=max_hops).contains(&ttl) {
+                            hops[ttl as usize - 1].get_or_insert(from.ip());
+                        }
+                    }
+                    Some(IcmpMessage::EchoReply { ident, seq }) if self.ident_matches(ident) && from.ip() == self.target.ip() => {
+                        let ttl = (seq & 0xff) as u8;
+                        if reached.is_none_or(|r| ttl < r) {
+                            reached = Some(ttl);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        match reached {
+            Some(ttl) => {
+                hops.truncate(ttl as usize);
+                hops[ttl as usize - 1] = Some(self.target.ip());
+            }
+            None => {
+                // drop the silent tail after the last router that answered
+                let len = hops.iter().rposition(Option::is_some).map_or(0, |i| i + 1);
+                hops.truncate(len);
+            }
+        }
+
+        // a router answering for several TTLs is a forwarding loop, keep its first position
+        let mut seen = HashSet::new();
+        for hop in hops.iter_mut() {
+            if let Some(ip) = hop {
+                if !seen.insert(*ip) {
+                    *hop = None;
+                }
+            }
+        }
+        Ok(hops)
+    }
+
+    // drains the error queue, keeping the ICMP errors about echo requests. The queue hands
+    // out the request itself, the message type and code come with it.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn take_queued_errors(&self) -> io::Result<Vec<IcmpMessage>> {
+        use std::os::fd::AsRawFd;
+
+        let request = match self.target {
+            SocketAddr::V4(_) => ICMP_ECHO_REQUEST,
+            SocketAddr::V6(_) => ICMPV6_ECHO_REQUEST,
+        };
+        let mut errors = Vec::new();
+        let mut buf = [0u8; ICMP_HEADER_LEN];
+        // u64 keeps the control messages aligned
+        let mut control = [0u64; 64];
+        loop {
+            let mut iov = libc::iovec { iov_base: buf.as_mut_ptr().cast(), iov_len: buf.len() };
+            // SAFETY: msghdr is plain data, every pointer set below outlives the call
+            let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+            msg.msg_iov = &mut iov;
+            msg.msg_iovlen = 1;
+            msg.msg_control = control.as_mut_ptr().cast();
+            msg.msg_controllen = std::mem::size_of_val(&control) as _;
+            // SAFETY: the descriptor is owned by the socket and msg points to live buffers
+            let len = unsafe { libc::recvmsg(self.socket.as_raw_fd(), &mut msg, libc::MSG_ERRQUEUE | libc::MSG_DONTWAIT) };
+            if len < 0 {
+                let err = io::Error::last_os_error();
+                return match err.kind() {
+                    io::ErrorKind::WouldBlock => Ok(errors),
+                    io::ErrorKind::Interrupted => continue,
+                    _ => Err(err),
+                };
+            }
+            let data = &buf[..len as usize];
+
+            // SAFETY: the control messages were filled in by recvmsg and stay within msg_controllen
+            let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+            while !cmsg.is_null() {
+                let header = unsafe { &*cmsg };
+                let recverr = (header.cmsg_level == libc::SOL_IP && header.cmsg_type == libc::IP_RECVERR)
+                    || (header.cmsg_level == libc::SOL_IPV6 && header.cmsg_type == libc::IPV6_RECVERR);
+                if recverr {
+                    let ee: libc::sock_extended_err = unsafe { std::ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast()) };
+                    // local errors such as EMSGSIZE were returned by send already
+                    let icmp = ee.ee_origin == libc::SO_EE_ORIGIN_ICMP || ee.ee_origin == libc::SO_EE_ORIGIN_ICMP6;
+                    let quoted = if icmp && data.first() == Some(&request) {
+                        read_ident(data).zip(read_seq(data))
+                    } else {
+                        None
+                    };
+                    if let Some((ident, seq)) = quoted {
+                        errors.push(IcmpMessage::Unreachable { ident, seq, icmp_type: ee.ee_type, code: ee.ee_code });
+                    }
+                }
+                cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+            }
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn take_queued_errors(&self) -> io::Result<Vec<IcmpMessage>> {
+        Ok(Vec::new())
+    }
+
+    fn ident_matches(&self, ident: u16) -> bool {
+        ident == self.ident || Some(ident) == self.kernel_ident
+    }
+
+    fn build_request(&self, seq: u16) -> Vec<u8> {
+        echo_request(self.target.is_ipv4(), self.ident, seq, self.payload_size)
+    }
+
+    fn parse_message(&self, data: &[u8]) -> Option<IcmpMessage> {
+        parse_message(self.target.is_ipv4(), data)
+    }
+}
+
+fn echo_request(v4: bool, ident: u16, seq: u16, payload_size: usize) -> Vec<u8> {
+    let mut packet = vec![0u8; ICMP_HEADER_LEN + payload_size];
+    packet[0] = if v4 { ICMP_ECHO_REQUEST } else { ICMPV6_ECHO_REQUEST };
+    packet[4..6].copy_from_slice(&ident.to_be_bytes());
+    packet[6..8].copy_from_slice(&seq.to_be_bytes());
+    for (i, byte) in packet[ICMP_HEADER_LEN..].iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+
+    // the kernel fills in the ICMPv6 checksum, since it covers the pseudo header
+    if v4 {
+        let sum = checksum(&packet);
+        packet[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+    packet
+}
+
+fn parse_message(v4: bool, data: &[u8]) -> Option<IcmpMessage> {
+    // raw sockets (and datagram sockets on BSD) hand us the IPv4 header as well
+    let data = if v4 { strip_ipv4_header(data)? } else { data };
+    if data.len() < ICMP_HEADER_LEN {
+        return None;
+    }
+
+    let (icmp_type, code) = (data[0], data[1]);
+    match (v4, icmp_type) {
+        (true, ICMP_ECHO_REPLY) | (false, ICMPV6_ECHO_REPLY) => {
+            Some(IcmpMessage::EchoReply { ident: read_ident(data)?, seq: read_seq(data)? })
+        }
+        (true, ICMP_TIME_EXCEEDED) | (false, ICMPV6_TIME_EXCEEDED) => {
+            let (ident, seq) = quoted_request(v4, data)?;
+            Some(IcmpMessage::TimeExceeded { ident, seq })
+        }
+        (true, ICMP_DEST_UNREACHABLE) | (false, ICMPV6_DEST_UNREACHABLE) => {
+            let (ident, seq) = quoted_request(v4, data)?;
+            Some(IcmpMessage::Unreachable { ident, seq, icmp_type, code })
+        }
+        _ => None,
+    }
+}
+
+// ICMP errors quote the header of the packet they are about, ours is an echo request
+fn quoted_request(v4: bool, data: &[u8]) -> Option<(u16, u16)> {
+    let (inner, request) = if v4 {
+        (strip_ipv4_header(&data[ICMP_HEADER_LEN..])?, ICMP_ECHO_REQUEST)
+    } else {
+        (data.get(ICMP_HEADER_LEN + IPV6_HEADER_LEN..)?, ICMPV6_ECHO_REQUEST)
+    };
+    if inner.first() != Some(&request) {
+        return None;
+    }
+    Some((read_ident(inner)?, read_seq(inner)?))
+}
+
+// has the kernel queue ICMP errors about the packets sent through `socket`
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn enable_error_queue(socket: &Socket, v6: bool) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let (level, name) = if v6 {
+        (libc::IPPROTO_IPV6, libc::IPV6_RECVERR)
+    } else {
+        (libc::IPPROTO_IP, libc::IP_RECVERR)
+    };
+    let on: libc::c_int = 1;
+    // SAFETY: the descriptor is owned by socket and on outlives the call
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &on as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn enable_error_queue(_socket: &Socket, _v6: bool) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "icmp error queues are not supported on this platform"))
+}
+
+fn strip_ipv4_header(data: &[u8]) -> Option<&[u8]> {
+    if data.len() >= 20 && data[0] >> 4 == 4 {
+        data.get((data[0] & 0x0f) as usize * 4..)
+    } else {
+        Some(data)
+    }
+}
+
+fn read_ident(icmp: &[u8]) -> Option<u16> {
+    Some(u16::from_be_bytes([*icmp.get(4)?, *icmp.get(5)?]))
+}
+
+fn read_seq(icmp: &[u8]) -> Option<u16> {
+    Some(u16::from_be_bytes([*icmp.get(6)?, *icmp.get(7)?]))
+}
+
+// RFC 1071 internet checksum
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    for chunk in data.chunks(2) {
+        let word = if chunk.len() == 2 {
+            u16::from_be_bytes([chunk[0], chunk[1]])
+        } else {
+            u16::from_be_bytes([chunk[0], 0])
+        };
+        sum += word as u32;
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOOPBACK: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    const WAIT: Duration = Duration::from_secs(1);
+
+    // icmp sockets need ping_group_range or CAP_NET_RAW, without them the tests are skipped
+    fn pinger() -> Option<IcmpPinger> {
+        match IcmpPinger::new(LOOPBACK, None) {
+            Ok(pinger) => Some(pinger),
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => None,
+            Err(err) => panic!("unable to open icmp socket: {}", err),
+        }
+    }
+
+    const IPV4_HEADER: [u8; 20] = [0x45, 0, 0, 0, 0, 0, 0, 0, 64, 1, 0, 0, 127, 0, 0, 1, 127, 0, 0, 1];
+
+    // what a raw socket reads, the IPv4 header followed by the reply
+    fn reply_packet(ident: u16, seq: u16) -> Vec<u8> {
+        let mut reply = echo_request(true, ident, seq, DEFAULT_PAYLOAD_SIZE);
+        reply[0] = ICMP_ECHO_REPLY;
+        let mut packet = IPV4_HEADER.to_vec();
+        packet.extend_from_slice(&reply);
+        packet
+    }
+
+    #[test]
+    fn echo_reply_matches_its_request() {
+        let Some(mut pinger) = pinger() else { return };
+        match pinger.ping(7, WAIT).unwrap() {
+            ProbeResult::Reply { seq, rtt } => {
+                assert_eq!(seq, 7);
+                assert!(rtt < WAIT);
+            }
+            other => panic!("expected a reply, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn no_reply_within_the_timeout_is_a_timeout() {
+        let Some(mut pinger) = pinger() else { return };
+        assert_eq!(pinger.ping(3, Duration::ZERO).unwrap(), ProbeResult::Timeout { seq: 3 });
+    }
+
+    #[test]
+    fn reply_to_an_earlier_request_is_not_taken_for_the_current_one() {
+        let Some(mut pinger) = pinger() else { return };
+        assert_eq!(pinger.ping(1, Duration::ZERO).unwrap(), ProbeResult::Timeout { seq: 1 });
+        // the reply to 1 is waiting in the socket when 2 is sent
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(matches!(pinger.ping(2, WAIT).unwrap(), ProbeResult::Reply { seq: 2, .. }));
+    }
+
+    #[test]
+    fn replies_of_other_pingers_are_ignored() {
+        let (Some(mut pinger), Some(mut other)) = (pinger(), pinger()) else { return };
+        assert_ne!(pinger.ident, other.ident);
+        assert_eq!(other.ping(5, Duration::ZERO).unwrap(), ProbeResult::Timeout { seq: 5 });
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(matches!(pinger.ping(5, WAIT).unwrap(), ProbeResult::Reply { seq: 5, .. }));
+        // had the reply of the other pinger been taken, ours would show up as a duplicate now
+        assert!(matches!(pinger.ping(6, WAIT).unwrap(), ProbeResult::Reply { seq: 6, .. }));
+        assert!(pinger.take_strays().is_empty());
+    }
+
+    #[test]
+    fn parses_echo_replies_behind_an_ipv4_header() {
+        match parse_message(true, &reply_packet(0x1234, 42)) {
+            Some(IcmpMessage::EchoReply { ident, seq }) => {
+                assert_eq!(ident, 0x1234);
+                assert_eq!(seq, 42);
+            }
+            _ => panic!("echo reply not recognized"),
+        }
+        // our own request, seen by raw sockets on loopback
+        assert!(parse_message(true, &echo_request(true, 0x1234, 42, DEFAULT_PAYLOAD_SIZE)).is_none());
+    }
+
+    #[test]
+    fn parses_unreachable_quoting_our_request() {
+        let request = echo_request(true, 0x1234, 7, DEFAULT_PAYLOAD_SIZE);
+        let mut packet = IPV4_HEADER.to_vec();
+        packet.extend_from_slice(&[ICMP_DEST_UNREACHABLE, 1, 0, 0, 0, 0, 0, 0]);
+        packet.extend_from_slice(&IPV4_HEADER);
+        packet.extend_from_slice(&request[..ICMP_HEADER_LEN]);
+        match parse_message(true, &packet) {
+            Some(IcmpMessage::Unreachable { ident, seq, icmp_type, code }) => {
+                assert_eq!((ident, seq, icmp_type, code), (0x1234, 7, ICMP_DEST_UNREACHABLE, 1));
+            }
+            _ => panic!("unreachable not recognized"),
+        }
+    }
+
+    #[test]
+    fn checksum_of_a_checksummed_packet_is_zero() {
+        assert_eq!(checksum(&echo_request(true, 0x1234, 9, DEFAULT_PAYLOAD_SIZE)), 0);
+        // odd lengths are padded with a zero byte
+        assert_eq!(checksum(&echo_request(true, 0x1234, 9, 7)), 0);
+    }
+}
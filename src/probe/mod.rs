@@ -0,0 +1,589 @@
+mod dns;
+mod http;
+mod icmp;
+mod tcp;
+mod udp;
+
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::time::Duration;
+use anyhow::{anyhow, Context};
+use socket2::{Domain, Protocol, Socket, Type};
+use crate::outage::OutageThresholds;
+
+pub use dns::{rcode_name, DnsAnswer, DnsPinger, DnsQuery};
+pub use http::{HttpCheck, HttpExpect, HttpPinger, HttpTiming, HttpUrl};
+pub use icmp::IcmpPinger;
+pub use tcp::TcpPinger;
+pub use udp::{run_reflector, UdpPinger, DEFAULT_REFLECT_PORT};
+
+/// Largest payload of an ICMP or UDP probe, a 65535 byte IPv4 packet less its headers
+pub const MAX_PAYLOAD_SIZE: usize = 65507;
+
+/// How a target is probed, selected by the scheme of the target string
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProbeKind {
+    Icmp,
+    Tcp { port: u16 },
+    Http(HttpUrl),
+    Dns(DnsQuery),
+    Udp { port: u16 },
+}
+
+/// Settings shared by every probe of a run
+#[derive(Debug, Clone, Default)]
+pub struct ProbeOptions {
+    pub http_expect: HttpExpect,
+    /// hop limit of path discovery
+    pub max_hops: u8,
+    /// how often paths are re-traced to detect route changes, zero disables it
+    pub trace_interval: Duration,
+    /// how often the path MTU is re-checked, zero disables discovery
+    pub pmtu_interval: Duration,
+    /// how often hostnames are resolved again, zero keeps the startup address
+    pub resolve_interval: Duration,
+    /// probe the IPv4 and the IPv6 address of every hostname
+    pub dual_stack: bool,
+    /// packet settings of every target, per target settings take precedence
+    pub packet: PacketParams,
+    /// every target is probed once from each of these, from the default route when empty
+    pub sources: Vec<Source>,
+    /// when lost probes make an outage
+    pub outage: OutageThresholds,
+}
+
+impl ProbeOptions {
+    /// The sources every target is probed from, a single `None` for the default route
+    pub fn probe_sources(&self) -> Vec<Option<Source>> {
+        if self.sources.is_empty() {
+            vec![None]
+        } else {
+            self.sources.iter().cloned().map(Some).collect()
+        }
+    }
+}
+
+/// Per packet settings, given on the command line for all targets or after a `#` for a
+/// single one, e.g. `8.8.8.8#size=1400,dscp=ef,df`. Unset fields keep the system default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PacketParams {
+    /// payload bytes of icmp and udp probes, the other probes ignore it
+    pub size: Option<usize>,
+    /// TTL (IPv4) or hop limit (IPv6)
+    pub ttl: Option<u32>,
+    /// DiffServ code point, the upper six bits of the ToS / traffic class byte
+    pub dscp: Option<u8>,
+    /// Don't-Fragment flag
+    pub df: Option<bool>,
+}
+
+impl PacketParams {
+    /// Fills the fields left unset from `fallback`
+    pub fn or(self, fallback: PacketParams) -> Self {
+        Self {
+            size: self.size.or(fallback.size),
+            ttl: self.ttl.or(fallback.ttl),
+            dscp: self.dscp.or(fallback.dscp),
+            df: self.df.or(fallback.df),
+        }
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Applies TTL, DSCP and DF to a socket talking to an address of the given family
+    pub(crate) fn apply(&self, socket: &Socket, v6: bool) -> io::Result<()> {
+        if let Some(ttl) = self.ttl {
+            if v6 {
+                socket.set_unicast_hops_v6(ttl)?;
+            } else {
+                socket.set_ttl(ttl)?;
+            }
+        }
+        if let Some(dscp) = self.dscp {
+            set_traffic_class(socket, v6, (dscp as u32) << 2)?;
+        }
+        if let Some(df) = self.df {
+            set_dont_fragment(socket, v6, df)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PacketParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(size) = self.size {
+            parts.push(format!("size={}", size));
+        }
+        if let Some(ttl) = self.ttl {
+            parts.push(format!("ttl={}", ttl));
+        }
+        if let Some(dscp) = self.dscp {
+            parts.push(format!("dscp={}", dscp));
+        }
+        if self.df == Some(true) {
+            parts.push("df".to_string());
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// Where probes leave from on multi-homed hosts, a local address or an interface name
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Source {
+    Addr(IpAddr),
+    Interface(String),
+}
+
+impl Source {
+    /// Binds a socket that is about to talk to `target` to this source
+    pub(crate) fn bind(&self, socket: &Socket, target: IpAddr) -> io::Result<()> {
+        match self {
+            Source::Addr(addr) => {
+                if addr.is_ipv4() != target.is_ipv4() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("source {} and target {} are of different address families", addr, target),
+                    ));
+                }
+                socket.bind(&SocketAddr::new(*addr, 0).into())
+            }
+            Source::Interface(name) => bind_interface(socket, name),
+        }
+    }
+}
+
+impl FromStr for Source {
+    type Err = anyhow::Error;
+
+    /// Anything that parses as an address is a source address, the rest an interface name
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if s.is_empty() {
+            return Err(anyhow!("empty source"));
+        }
+        Ok(s.parse().map(Source::Addr).unwrap_or_else(|_| Source::Interface(s.to_string())))
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Addr(addr) => write!(f, "{}", addr),
+            Source::Interface(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// Parses a payload size, at most what fits into one IPv4 packet
+pub fn parse_size(value: &str) -> anyhow::Result<usize> {
+    match value.parse::<usize>() {
+        Ok(size) if size <= MAX_PAYLOAD_SIZE => Ok(size),
+        _ => Err(anyhow!("invalid size, expected 0-{}: {}", MAX_PAYLOAD_SIZE, value)),
+    }
+}
+
+/// Parses a DSCP given as number (0-63) or class name (`ef`, `af11`..`af43`, `cs0`..`cs7`)
+pub fn parse_dscp(value: &str) -> anyhow::Result<u8> {
+    let lower = value.to_ascii_lowercase();
+    let dscp = if lower == "ef" {
+        46
+    } else if let Some(class) = lower.strip_prefix("cs") {
+        match class.parse::<u8>() {
+            Ok(class) if class <= 7 => class << 3,
+            _ => return Err(anyhow!("invalid dscp class: {}", value)),
+        }
+    } else if let Some(af) = lower.strip_prefix("af") {
+        match af.as_bytes() {
+            [class @ b'1'..=b'4', drop @ b'1'..=b'3'] => ((class - b'0') << 3) | ((drop - b'0') << 1),
+            _ => return Err(anyhow!("invalid dscp class: {}", value)),
+        }
+    } else {
+        match value.parse::<u8>() {
+            Ok(dscp) if dscp <= 63 => dscp,
+            _ => return Err(anyhow!("invalid dscp, expected 0-63 or a class name: {}", value)),
+        }
+    };
+    Ok(dscp)
+}
+
+/// Splits the per target packet settings off a target, `host#size=1400,ttl=8,dscp=ef,df`
+pub fn split_params(target: &str) -> anyhow::Result<(&str, PacketParams)> {
+    let Some((target, settings)) = target.rsplit_once('#') else {
+        return Ok((target, PacketParams::default()));
+    };
+
+    let mut params = PacketParams::default();
+    let mut seen = Vec::new();
+    for setting in settings.split(',').filter(|s| !s.is_empty()) {
+        let (key, value) = setting.split_once('=').map_or((setting, None), |(k, v)| (k, Some(v)));
+        if seen.contains(&key) {
+            return Err(anyhow!("target setting given twice: {}", key));
+        }
+        seen.push(key);
+        let value_of = |what: &str| value.ok_or_else(|| anyhow!("target setting {} requires a value: {}", what, setting));
+        match key {
+            "size" => params.size = Some(parse_size(value_of(key)?)?),
+            "ttl" => match value_of(key)?.parse::<u32>() {
+                Ok(ttl) if (1..=255).contains(&ttl) => params.ttl = Some(ttl),
+                _ => return Err(anyhow!("invalid ttl, expected 1-255: {}", setting)),
+            },
+            "dscp" => params.dscp = Some(parse_dscp(value_of(key)?)?),
+            "df" => params.df = Some(match value {
+                None | Some("1") | Some("true") | Some("on") => true,
+                Some("0") | Some("false") | Some("off") => false,
+                Some(_) => return Err(anyhow!("invalid df setting: {}", setting)),
+            }),
+            _ => return Err(anyhow!("unsupported target setting: {}", setting)),
+        }
+    }
+    Ok((target, params))
+}
+
+/// The packet settings a target is probed with, its own over the global ones
+pub fn packet_params(target: &str, options: &ProbeOptions) -> anyhow::Result<PacketParams> {
+    Ok(split_params(target)?.1.or(options.packet))
+}
+
+/// Result of a single probe
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProbeResult {
+    Reply { seq: u16, rtt: Duration },
+    Timeout { seq: u16 },
+    /// the target actively rejected the probe (e.g. TCP RST)
+    Refused { seq: u16 },
+    /// a completed HTTP request, `check` tells whether it met the expectations
+    Http { seq: u16, timing: HttpTiming, check: HttpCheck },
+    /// a resolver answer, whatever its rcode
+    Dns { seq: u16, rtt: Duration, answer: DnsAnswer },
+    /// a UDP echo probe, `rtt` is `None` when the echo did not come back in time.
+    /// Stray echoes of other probes seen while waiting are counted alongside.
+    Udp { seq: u16, rtt: Option<Duration>, duplicates: u16, reordered: u16 },
+    /// an ICMP error about the probe, e.g. host or port unreachable
+    Unreachable { seq: u16, icmp_type: u8, code: u8 },
+}
+
+/// A reply to an earlier probe, seen while waiting for the current one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StrayReply {
+    /// the probe was answered already
+    Duplicate { seq: u16 },
+    /// the probe had timed out, `rtt` is measured from when it was sent
+    Late { seq: u16, rtt: Duration },
+}
+
+/// Splits a target such as `tcp://db01:5432` into the host to resolve and its probe kind.
+/// Targets without a scheme are pinged with ICMP.
+pub fn parse_target(target: &str) -> anyhow::Result<(String, ProbeKind)> {
+    let (target, _) = split_params(target)?;
+    let Some((scheme, rest)) = target.split_once("://") else {
+        return Ok((target.to_string(), ProbeKind::Icmp));
+    };
+
+    match scheme.to_ascii_lowercase().as_str() {
+        "icmp" => Ok((rest.trim_end_matches('/').to_string(), ProbeKind::Icmp)),
+        "tcp" => {
+            let (host, port) = split_host_port(rest.trim_end_matches('/'))
+                .with_context(|| format!("invalid tcp target: {}", target))?;
+            let port = port.ok_or_else(|| anyhow!("tcp target requires a port: {}", target))?;
+            Ok((host, ProbeKind::Tcp { port }))
+        }
+        "udp" => {
+            let (host, port) = split_host_port(rest.trim_end_matches('/'))
+                .with_context(|| format!("invalid udp target: {}", target))?;
+            Ok((host, ProbeKind::Udp { port: port.unwrap_or(DEFAULT_REFLECT_PORT) }))
+        }
+        "http" | "https" => {
+            let tls = scheme.eq_ignore_ascii_case("https");
+            let (authority, path) = match rest.find(['/', '?']) {
+                Some(pos) if rest[pos..].starts_with('?') => (&rest[..pos], format!("/{}", &rest[pos..])),
+                Some(pos) => (&rest[..pos], rest[pos..].to_string()),
+                None => (rest, "/".to_string()),
+            };
+            let (host, port) = split_host_port(authority)
+                .with_context(|| format!("invalid http target: {}", target))?;
+            if host.is_empty() {
+                return Err(anyhow!("http target requires a host: {}", target));
+            }
+            let port = port.unwrap_or(if tls { 443 } else { 80 });
+            Ok((host.clone(), ProbeKind::Http(HttpUrl { tls, host, port, path })))
+        }
+        "dns" => {
+            // dns://resolver[:port]/name?type=A
+            let (resolver, query) = rest
+                .split_once('/')
+                .ok_or_else(|| anyhow!("dns target requires a name to query: {}", target))?;
+            let (name, params) = query.split_once('?').unwrap_or((query, ""));
+            if name.is_empty() {
+                return Err(anyhow!("dns target requires a name to query: {}", target));
+            }
+            let mut qtype = 1;
+            for param in params.split('&').filter(|p| !p.is_empty()) {
+                match param.split_once('=') {
+                    Some(("type", value)) => qtype = dns::parse_qtype(value)?,
+                    _ => return Err(anyhow!("unsupported dns target parameter: {}", param)),
+                }
+            }
+            let (host, port) = split_host_port(resolver)
+                .with_context(|| format!("invalid dns target: {}", target))?;
+            let query = DnsQuery { port: port.unwrap_or(53), name: name.to_string(), qtype };
+            Ok((host, ProbeKind::Dns(query)))
+        }
+        _ => Err(anyhow!("unsupported probe type: {}", scheme)),
+    }
+}
+
+/// Opens a TCP connection from `source` with the given packet settings applied before the handshake
+pub(crate) fn connect_tcp(addr: SocketAddr, timeout: Duration, params: &PacketParams, source: Option<&Source>) -> io::Result<TcpStream> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if let Some(source) = source {
+        source.bind(&socket, addr.ip())?;
+    }
+    params.apply(&socket, addr.is_ipv6())?;
+    socket.connect_timeout(&addr.into(), timeout)?;
+    Ok(socket.into())
+}
+
+/// Opens a UDP socket from `source` connected to `addr` with the given packet settings applied
+pub(crate) fn connect_udp(addr: SocketAddr, params: &PacketParams, source: Option<&Source>) -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    match source {
+        Some(source) => source.bind(&socket, addr.ip())?,
+        None => socket.bind(&SocketAddr::new(unspecified(addr.ip()), 0).into())?,
+    }
+    params.apply(&socket, addr.is_ipv6())?;
+    socket.connect(&addr.into())?;
+    Ok(socket.into())
+}
+
+/// The wildcard address of the family of `ip`
+pub(crate) fn unspecified(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bind_interface(socket: &Socket, name: &str) -> io::Result<()> {
+    socket.bind_device(Some(name.as_bytes())).map_err(|err| {
+        io::Error::new(err.kind(), format!("failed to bind to interface {}: {}", name, err))
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn bind_interface(_socket: &Socket, name: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("binding to interface {} is not supported on this platform, use a source address", name),
+    ))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "freebsd"))]
+fn set_traffic_class(socket: &Socket, v6: bool, tos: u32) -> io::Result<()> {
+    if v6 { socket.set_tclass_v6(tos) } else { socket.set_tos(tos) }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "freebsd")))]
+fn set_traffic_class(_socket: &Socket, _v6: bool, _tos: u32) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "setting the dscp is not supported on this platform"))
+}
+
+/// Sets or clears the Don't-Fragment bit of packets sent through `socket`. While set the
+/// kernel neither fragments nor applies its cached path MTU, so oversized packets either
+/// fail to send or get dropped on the way. Clearing it restores the default.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn set_dont_fragment(socket: &Socket, v6: bool, on: bool) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let (level, name, value) = match (v6, on) {
+        (false, true) => (libc::IPPROTO_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_PROBE),
+        (false, false) => (libc::IPPROTO_IP, libc::IP_MTU_DISCOVER, libc::IP_PMTUDISC_WANT),
+        (true, true) => (libc::IPPROTO_IPV6, libc::IPV6_MTU_DISCOVER, libc::IPV6_PMTUDISC_PROBE),
+        (true, false) => (libc::IPPROTO_IPV6, libc::IPV6_MTU_DISCOVER, libc::IPV6_PMTUDISC_WANT),
+    };
+    // SAFETY: the descriptor is owned by socket and value outlives the call
+    let res = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn set_dont_fragment(_socket: &Socket, _v6: bool, _on: bool) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "setting the don't fragment bit is not supported on this platform"))
+}
+
+// splits `host`, `host:port`, `[v6]` or `[v6]:port`
+fn split_host_port(s: &str) -> anyhow::Result<(String, Option<u16>)> {
+    if let Some(rest) = s.strip_prefix('[') {
+        let (host, tail) = rest.split_once(']').ok_or_else(|| anyhow!("missing ']'"))?;
+        let port = match tail.strip_prefix(':') {
+            Some(port) => Some(port.parse().with_context(|| format!("invalid port: {}", port))?),
+            None if tail.is_empty() => None,
+            None => return Err(anyhow!("unexpected characters after ']': {}", tail)),
+        };
+        return Ok((host.to_string(), port));
+    }
+
+    match s.rsplit_once(':') {
+        // a bare IPv6 address has more than one colon and no port
+        Some((host, _)) if host.contains(':') => Ok((s.to_string(), None)),
+        Some((host, port)) => {
+            let port = port.parse().with_context(|| format!("invalid port: {}", port))?;
+            Ok((host.to_string(), Some(port)))
+        }
+        None => Ok((s.to_string(), None)),
+    }
+}
+
+/// A probe engine for one resolved target
+pub enum Prober {
+    Icmp(IcmpPinger),
+    Tcp(TcpPinger),
+    Http(HttpPinger),
+    Dns(DnsPinger),
+    Udp(UdpPinger),
+}
+
+impl Prober {
+    /// Builds the engine matching the scheme of `target` (as given on the command line),
+    /// sending from `source` when given
+    pub fn new(target: &str, ip: IpAddr, options: &ProbeOptions, source: Option<&Source>) -> anyhow::Result<Self> {
+        let (_, kind) = parse_target(target)?;
+        let params = packet_params(target, options)?;
+        let source = source.cloned();
+        Ok(match kind {
+            ProbeKind::Icmp => {
+                let mut pinger = IcmpPinger::new(ip, source.as_ref())?;
+                pinger.set_params(&params).context("failed to apply packet settings")?;
+                Prober::Icmp(pinger)
+            }
+            ProbeKind::Tcp { port } => Prober::Tcp(TcpPinger::new(SocketAddr::new(ip, port), params, source)),
+            ProbeKind::Http(url) => Prober::Http(HttpPinger::new(url, ip, options.http_expect.clone(), params, source)?),
+            ProbeKind::Dns(query) => Prober::Dns(DnsPinger::new(ip, query, params, source)?),
+            ProbeKind::Udp { port } => Prober::Udp(UdpPinger::new(SocketAddr::new(ip, port), params, source.as_ref())?),
+        })
+    }
+
+    pub fn probe(&mut self, seq: u16, timeout: Duration) -> io::Result<ProbeResult> {
+        match self {
+            Prober::Icmp(pinger) => pinger.ping(seq, timeout),
+            Prober::Tcp(pinger) => pinger.ping(seq, timeout),
+            Prober::Http(pinger) => pinger.ping(seq, timeout),
+            Prober::Dns(pinger) => pinger.ping(seq, timeout),
+            Prober::Udp(pinger) => pinger.ping(seq, timeout),
+        }
+    }
+
+    /// Takes the stray replies seen by the probes so far, only icmp probes report them
+    pub fn take_strays(&mut self) -> Vec<StrayReply> {
+        match self {
+            Prober::Icmp(pinger) => pinger.take_strays(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_without_a_scheme_are_pinged() {
+        assert_eq!(parse_target("db01").unwrap(), (String::from("db01"), ProbeKind::Icmp));
+        assert_eq!(parse_target("::1").unwrap(), (String::from("::1"), ProbeKind::Icmp));
+    }
+
+    #[test]
+    fn tcp_targets_need_a_valid_port() {
+        assert_eq!(parse_target("tcp://db01:5432").unwrap(), (String::from("db01"), ProbeKind::Tcp { port: 5432 }));
+        assert_eq!(parse_target("tcp://[::1]:443").unwrap(), (String::from("::1"), ProbeKind::Tcp { port: 443 }));
+        assert_eq!(parse_target("TCP://db01:22/").unwrap(), (String::from("db01"), ProbeKind::Tcp { port: 22 }));
+        assert!(parse_target("tcp://db01").is_err());
+        assert!(parse_target("tcp://[::1]").is_err());
+        assert!(parse_target("tcp://db01:ssh").is_err());
+        assert!(parse_target("tcp://db01:65536").is_err());
+        assert!(parse_target("sctp://db01:22").is_err());
+    }
+
+    fn params(target: &str) -> PacketParams {
+        split_params(target).unwrap().1
+    }
+
+    #[test]
+    fn parses_dscp_names_and_numbers() {
+        assert_eq!(parse_dscp("ef").unwrap(), 46);
+        assert_eq!(parse_dscp("AF41").unwrap(), 34);
+        assert_eq!(parse_dscp("af11").unwrap(), 10);
+        assert_eq!(parse_dscp("cs1").unwrap(), 8);
+        assert_eq!(parse_dscp("cs7").unwrap(), 56);
+        assert_eq!(parse_dscp("0").unwrap(), 0);
+        assert_eq!(parse_dscp("63").unwrap(), 63);
+        for invalid in ["64", "-1", "cs8", "af51", "af14", "af1", "be", ""] {
+            assert!(parse_dscp(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn splits_packet_settings_off_the_target() {
+        let (target, all) = split_params("8.8.8.8#size=1400,ttl=8,dscp=ef,df").unwrap();
+        assert_eq!(target, "8.8.8.8");
+        assert_eq!(all, PacketParams { size: Some(1400), ttl: Some(8), dscp: Some(46), df: Some(true) });
+        assert_eq!(split_params("8.8.8.8").unwrap(), ("8.8.8.8", PacketParams::default()));
+        assert_eq!(params("8.8.8.8#df=off").df, Some(false));
+    }
+
+    #[test]
+    fn rejects_unknown_repeated_and_incomplete_settings() {
+        for invalid in ["db01#mtu=1400", "db01#ttl=8,ttl=9", "db01#df,df=off", "db01#ttl", "db01#dscp=", "db01#df=maybe"] {
+            assert!(split_params(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn size_and_ttl_are_bounded() {
+        assert_eq!(params("db01#size=0").size, Some(0));
+        assert_eq!(params("db01#size=65507").size, Some(MAX_PAYLOAD_SIZE));
+        assert!(split_params("db01#size=65508").is_err());
+        assert!(split_params("db01#size=-1").is_err());
+        assert_eq!(params("db01#ttl=1").ttl, Some(1));
+        assert_eq!(params("db01#ttl=255").ttl, Some(255));
+        assert!(split_params("db01#ttl=0").is_err());
+        assert!(split_params("db01#ttl=256").is_err());
+    }
+
+    #[test]
+    fn settings_follow_ipv6_literals_and_urls() {
+        assert_eq!(split_params("2001:db8::1#ttl=8").unwrap(), ("2001:db8::1", PacketParams { ttl: Some(8), ..Default::default() }));
+        assert_eq!(parse_target("tcp://[::1]:443#dscp=ef").unwrap(), (String::from("::1"), ProbeKind::Tcp { port: 443 }));
+
+        let (target, http) = split_params("http://example.test:8080/health?full=1#dscp=cs1").unwrap();
+        assert_eq!(target, "http://example.test:8080/health?full=1");
+        assert_eq!(http.dscp, Some(8));
+        let url = HttpUrl { tls: false, host: String::from("example.test"), port: 8080, path: String::from("/health?full=1") };
+        assert_eq!(parse_target("http://example.test:8080/health?full=1#dscp=cs1").unwrap(), (String::from("example.test"), ProbeKind::Http(url)));
+    }
+
+    #[test]
+    fn splits_hosts_and_ports() {
+        assert_eq!(split_host_port("db01:80").unwrap(), (String::from("db01"), Some(80)));
+        assert_eq!(split_host_port("db01").unwrap(), (String::from("db01"), None));
+        assert_eq!(split_host_port("[::1]:53").unwrap(), (String::from("::1"), Some(53)));
+        assert_eq!(split_host_port("[::1]").unwrap(), (String::from("::1"), None));
+        // without brackets the last group is part of the address
+        assert_eq!(split_host_port("fe80::1").unwrap(), (String::from("fe80::1"), None));
+        assert!(split_host_port("[::1").is_err());
+        assert!(split_host_port("[::1]53").is_err());
+        assert!(split_host_port("db01:").is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::probe::{connect_tcp, PacketParams, ProbeResult, Source};
+
+/// Measures TCP handshake latency against a single `ip:port`
+pub struct TcpPinger {
+    target: SocketAddr,
+    params: PacketParams,
+    source: Option<Source>,
+}
+
+impl TcpPinger {
+    pub fn new(target: SocketAddr, params: PacketParams, source: Option<Source>) -> Self {
+        Self { target, params, source }
+    }
+
+    /// Opens a connection and closes it as soon as the handshake completes
+    pub fn ping(&mut self, seq: u16, timeout: Duration) -> io::Result<ProbeResult> {
+        let started = Instant::now();
+        match connect_tcp(self.target, timeout, &self.params, self.source.as_ref()) {
+            Ok(stream) => {
+                let rtt = started.elapsed();
+                drop(stream);
+                Ok(ProbeResult::Reply { seq, rtt })
+            }
+            Err(err) => match err.kind() {
+                io::ErrorKind::ConnectionRefused => Ok(ProbeResult::Refused { seq }),
+                io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => Ok(ProbeResult::Timeout { seq }),
+                _ => Err(err),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    const WAIT: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn completed_handshake_is_a_reply() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut pinger = TcpPinger::new(listener.local_addr().unwrap(), PacketParams::default(), None);
+        match pinger.ping(3, WAIT).unwrap() {
+            ProbeResult::Reply { seq, rtt } => {
+                assert_eq!(seq, 3);
+                assert!(rtt < WAIT);
+            }
+            other => panic!("expected a reply, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn closed_port_is_refused() {
+        // nothing listens on the port once the listener is gone
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let mut pinger = TcpPinger::new(addr, PacketParams::default(), None);
+        assert_eq!(pinger.ping(4, WAIT).unwrap(), ProbeResult::Refused { seq: 4 });
+    }
+}
@@ -0,0 +1,251 @@
+use std::collections::{HashSet, VecDeque};
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::probe::{connect_udp, PacketParams, ProbeResult, Source};
+
+/// Port used by `pingwatch reflect` and by `udp://` targets without a port
+pub const DEFAULT_REFLECT_PORT: u16 = 7777;
+
+// magic, session, seq, send timestamp (ns since the session started)
+const MAGIC: &[u8; 4] = b"PWUE";
+const PAYLOAD_LEN: usize = 4 + 4 + 2 + 8;
+// how many recent sequence numbers are remembered for duplicate detection
+const SEQ_WINDOW: usize = 1024;
+
+/// Sends sequenced, timestamped datagrams to a reflector and matches the echoes
+pub struct UdpPinger {
+    socket: UdpSocket,
+    session: u32,
+    epoch: Instant,
+    received: HashSet<u16>,
+    received_order: VecDeque<u16>,
+    highest_seq: Option<u16>,
+    // datagrams are padded up to this many bytes
+    payload_size: usize,
+}
+
+impl UdpPinger {
+    pub fn new(target: SocketAddr, params: PacketParams, source: Option<&Source>) -> io::Result<Self> {
+        let socket = connect_udp(target, &params, source)?;
+
+        // the session tells our echoes apart from those of an earlier run using the same port
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+        let session = std::process::id().rotate_left(16) ^ nanos ^ socket.local_addr()?.port() as u32;
+        let epoch = Instant::now();
+
+        Ok(Self {
+            socket,
+            session,
+            epoch,
+            received: HashSet::new(),
+            received_order: VecDeque::new(),
+            highest_seq: None,
+            payload_size: params.size.unwrap_or(PAYLOAD_LEN).max(PAYLOAD_LEN),
+        })
+    }
+
+    /// Sends one datagram and waits up to `timeout` for its echo. Echoes of other
+    /// sequence numbers seen meanwhile are counted as duplicates or reordered.
+    pub fn ping(&mut self, seq: u16, timeout: Duration) -> io::Result<ProbeResult> {
+        let mut payload = Vec::with_capacity(self.payload_size);
+        payload.extend_from_slice(MAGIC);
+        payload.extend_from_slice(&self.session.to_be_bytes());
+        payload.extend_from_slice(&seq.to_be_bytes());
+        payload.extend_from_slice(&(self.epoch.elapsed().as_nanos() as u64).to_be_bytes());
+        payload.resize(self.payload_size, 0);
+
+        let deadline = Instant::now() + timeout;
+        self.socket.send(&payload)?;
+
+        let mut duplicates = 0;
+        let mut reordered = 0;
+        let mut buf = vec![0u8; self.payload_size.max(512)];
+        loop {
+            let Some(left) = deadline.checked_duration_since(Instant::now()).filter(|d| !d.is_zero()) else {
+                return Ok(ProbeResult::Udp { seq, rtt: None, duplicates, reordered });
+            };
+            self.socket.set_read_timeout(Some(left))?;
+            let len = match self.socket.recv(&mut buf) {
+                Ok(len) => len,
+                Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    return Ok(ProbeResult::Udp { seq, rtt: None, duplicates, reordered });
+                }
+                // ICMP port unreachable, nothing is listening
+                Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => return Ok(ProbeResult::Refused { seq }),
+                Err(err) => return Err(err),
+            };
+            let now = self.epoch.elapsed().as_nanos() as u64;
+
+            let Some((session, reply_seq, sent_ns)) = parse_payload(&buf[..len]) else {
+                continue;
+            };
+            if session != self.session {
+                continue;
+            }
+
+            // sequence numbers wrap, compare them as a signed distance
+            let behind = self.highest_seq.is_some_and(|highest| (reply_seq.wrapping_sub(highest) as i16) < 0);
+            if !self.mark_received(reply_seq) {
+                duplicates += 1;
+                continue;
+            }
+            if reply_seq != seq {
+                // an echo arriving after that of a later probe is reordered,
+                // one that is merely late was already counted as lost
+                if behind {
+                    reordered += 1;
+                }
+                continue;
+            }
+
+            let rtt = Duration::from_nanos(now.saturating_sub(sent_ns));
+            return Ok(ProbeResult::Udp { seq, rtt: Some(rtt), duplicates, reordered });
+        }
+    }
+
+    // returns false if the sequence number was seen before
+    fn mark_received(&mut self, seq: u16) -> bool {
+        if !self.received.insert(seq) {
+            return false;
+        }
+        self.received_order.push_back(seq);
+        if self.received_order.len() > SEQ_WINDOW {
+            if let Some(old) = self.received_order.pop_front() {
+                self.received.remove(&old);
+            }
+        }
+        if self.highest_seq.is_none_or(|highest| (seq.wrapping_sub(highest) as i16) > 0) {
+            self.highest_seq = Some(seq);
+        }
+        true
+    }
+}
+
+fn parse_payload(data: &[u8]) -> Option<(u32, u16, u64)> {
+    if data.len() < PAYLOAD_LEN || &data[..4] != MAGIC {
+        return None;
+    }
+    let session = u32::from_be_bytes(data[4..8].try_into().ok()?);
+    let seq = u16::from_be_bytes(data[8..10].try_into().ok()?);
+    let sent_ns = u64::from_be_bytes(data[10..18].try_into().ok()?);
+    Some((session, seq, sent_ns))
+}
+
+/// Echoes every pingwatch datagram back to its sender until `running` is cleared.
+/// Returns the number of datagrams reflected.
+pub fn run_reflector(bind: SocketAddr, running: Arc<AtomicBool>) -> io::Result<u64> {
+    let socket = UdpSocket::bind(bind)?;
+    // wake up regularly to notice shutdown
+    socket.set_read_timeout(Some(Duration::from_millis(200)))?;
+
+    let mut reflected = 0;
+    // large enough for padded probes, echoes keep their size
+    let mut buf = vec![0u8; 65536];
+    while running.load(Ordering::Relaxed) {
+        match socket.recv_from(&mut buf) {
+            Ok((len, from)) => {
+                // ignore anything that isn't ours, so we can't be used to bounce traffic
+                if parse_payload(&buf[..len]).is_none() {
+                    continue;
+                }
+                if socket.send_to(&buf[..len], from).is_ok() {
+                    reflected += 1;
+                }
+            }
+            Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted) => {}
+            // a previous reply bounced with ICMP port unreachable
+            Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(reflected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const WAIT: Duration = Duration::from_millis(500);
+
+    fn free_port() -> u16 {
+        UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap().local_addr().unwrap().port()
+    }
+
+    fn pinger(port: u16) -> UdpPinger {
+        UdpPinger::new(SocketAddr::from((Ipv4Addr::LOCALHOST, port)), PacketParams::default(), None).unwrap()
+    }
+
+    // a reflector that echoes every datagram twice and holds the echo of sequence 1
+    // back until that of sequence 2 went out
+    fn serve_misbehaving() -> u16 {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let port = socket.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let mut held = None;
+            let mut buf = [0u8; 512];
+            while let Ok((len, from)) = socket.recv_from(&mut buf) {
+                let datagram = buf[..len].to_vec();
+                match parse_payload(&datagram).map(|(_, seq, _)| seq) {
+                    Some(1) => held = Some(datagram),
+                    Some(_) => {
+                        socket.send_to(&datagram, from).unwrap();
+                        socket.send_to(&datagram, from).unwrap();
+                        if let Some(held) = held.take() {
+                            socket.send_to(&held, from).unwrap();
+                        }
+                    }
+                    None => {}
+                }
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn measures_rtt_through_the_reflector() {
+        let port = free_port();
+        let running = Arc::new(AtomicBool::new(true));
+        let reflector = {
+            let running = running.clone();
+            std::thread::spawn(move || run_reflector(SocketAddr::from((Ipv4Addr::LOCALHOST, port)), running))
+        };
+        // give the reflector time to bind
+        std::thread::sleep(Duration::from_millis(50));
+
+        let mut pinger = pinger(port);
+        for seq in 0..3 {
+            match pinger.ping(seq, WAIT).unwrap() {
+                ProbeResult::Udp { seq: reply_seq, rtt: Some(rtt), duplicates: 0, reordered: 0 } => {
+                    assert_eq!(reply_seq, seq);
+                    assert!(rtt < WAIT);
+                }
+                other => panic!("expected an echo, got {:?}", other),
+            }
+        }
+
+        running.store(false, Ordering::Relaxed);
+        assert_eq!(reflector.join().unwrap().unwrap(), 3);
+    }
+
+    #[test]
+    fn counts_duplicated_and_reordered_echoes() {
+        let mut pinger = pinger(serve_misbehaving());
+        assert!(matches!(pinger.ping(0, WAIT).unwrap(), ProbeResult::Udp { rtt: Some(_), duplicates: 0, .. }));
+        // held back, lost as far as this probe goes, while the second echo of 0 comes in
+        assert!(matches!(pinger.ping(1, Duration::from_millis(100)).unwrap(), ProbeResult::Udp { rtt: None, duplicates: 1, .. }));
+        assert!(matches!(pinger.ping(2, WAIT).unwrap(), ProbeResult::Udp { rtt: Some(_), duplicates: 0, reordered: 0, .. }));
+        // the second echo of 2, then that of 1 arriving after 2
+        assert!(matches!(pinger.ping(3, WAIT).unwrap(), ProbeResult::Udp { rtt: Some(_), duplicates: 1, reordered: 1, .. }));
+    }
+
+    #[test]
+    fn closed_port_is_refused() {
+        let mut pinger = pinger(free_port());
+        assert_eq!(pinger.ping(0, WAIT).unwrap(), ProbeResult::Refused { seq: 0 });
+    }
+}
@@ -0,0 +1,673 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The latency percentiles pingwatch tracks, by name and quantile
+pub const PERCENTILES: [(&str, f64); 4] = [("p50", 0.5), ("p90", 0.9), ("p95", 0.95), ("p99", 0.99)];
+
+/// Position of a percentile name such as `p95` in [`PERCENTILES`]
+pub fn parse_percentile(name: &str) -> anyhow::Result<usize> {
+    PERCENTILES
+        .iter()
+        .position(|(n, _)| n.eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| anyhow::anyhow!("unknown percentile {}, expected one of p50, p90, p95, p99", name))
+}
+
+/// Streaming estimate of one quantile in constant memory, the P² algorithm of Jain and Chlamtac.
+/// Five markers follow the minimum, the quantile, the maximum and the points halfway between.
+#[derive(Debug, Clone)]
+pub struct P2Quantile {
+    quantile: f64,
+    count: usize,
+    /// marker heights, the first observations until there are five of them
+    heights: [f64; 5],
+    /// actual and desired marker positions, 1-based
+    positions: [f64; 5],
+    desired: [f64; 5],
+    increments: [f64; 5],
+}
+
+impl P2Quantile {
+    pub fn new(quantile: f64) -> Self {
+        Self {
+            quantile,
+            count: 0,
+            heights: [0.0; 5],
+            positions: [1.0, 2.0, 3.0, 4.0, 5.0],
+            desired: [1.0, 1.0 + 2.0 * quantile, 1.0 + 4.0 * quantile, 3.0 + 2.0 * quantile, 5.0],
+            increments: [0.0, quantile / 2.0, quantile, (1.0 + quantile) / 2.0, 1.0],
+        }
+    }
+
+    pub fn observe(&mut self, x: f64) {
+        if self.count < 5 {
+            self.heights[self.count] = x;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(f64::total_cmp);
+            }
+            return;
+        }
+        self.count += 1;
+
+        // the cell the observation falls into, the outer markers move along with new extremes
+        let h = &mut self.heights;
+        let cell = if x < h[0] {
+            h[0] = x;
+            0
+        } else if x >= h[4] {
+            h[4] = x;
+            3
+        } else {
+            (1..5).find(|&i| x < h[i]).unwrap_or(4) - 1
+        };
+        for position in &mut self.positions[cell + 1..] {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments) {
+            *desired += increment;
+        }
+
+        // nudge the inner markers towards their desired positions
+        for i in 1..4 {
+            let d = self.desired[i] - self.positions[i];
+            if (d >= 1.0 && self.positions[i + 1] - self.positions[i] > 1.0)
+                || (d <= -1.0 && self.positions[i - 1] - self.positions[i] < -1.0)
+            {
+                let s = d.signum();
+                let q = self.parabolic(i, s);
+                self.heights[i] = if self.heights[i - 1] < q && q < self.heights[i + 1] {
+                    q
+                } else {
+                    self.linear(i, s)
+                };
+                self.positions[i] += s;
+            }
+        }
+    }
+
+    /// The estimate, exact while there are five observations or less
+    pub fn value(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            n if n < 5 => {
+                let mut seen = self.heights[..n].to_vec();
+                seen.sort_by(f64::total_cmp);
+                Some(seen[((n - 1) as f64 * self.quantile).round() as usize])
+            }
+            _ => Some(self.heights[2]),
+        }
+    }
+
+    fn parabolic(&self, i: usize, s: f64) -> f64 {
+        let (n, q) = (&self.positions, &self.heights);
+        q[i] + s / (n[i + 1] - n[i - 1])
+            * ((n[i] - n[i - 1] + s) * (q[i + 1] - q[i]) / (n[i + 1] - n[i])
+                + (n[i + 1] - n[i] - s) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, s: f64) -> f64 {
+        let j = if s > 0.0 { i + 1 } else { i - 1 };
+        self.heights[i] + s * (self.heights[j] - self.heights[i]) / (self.positions[j] - self.positions[i])
+    }
+}
+
+/// Estimates of every percentile in [`PERCENTILES`] over all replies of a session
+#[derive(Debug, Clone)]
+pub struct SessionPercentiles {
+    estimators: [P2Quantile; 4],
+}
+
+impl Default for SessionPercentiles {
+    fn default() -> Self {
+        Self {
+            estimators: PERCENTILES.map(|(_, quantile)| P2Quantile::new(quantile)),
+        }
+    }
+}
+
+impl SessionPercentiles {
+    pub fn observe(&mut self, rtt: f64) {
+        for estimator in &mut self.estimators {
+            estimator.observe(rtt);
+        }
+    }
+
+    /// Estimate of the percentile at `index` of [`PERCENTILES`], `None` before the first reply
+    pub fn get(&self, index: usize) -> Option<f64> {
+        self.estimators.get(index).and_then(P2Quantile::value)
+    }
+}
+
+/// Longest rolling window, probes older than this are only part of the lifetime and
+/// since-reset aggregates
+const ROLLING_SPAN: Duration = Duration::from_secs(15 * 60);
+
+/// The span of probes the views summarize, cycled with `w`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StatsWindow {
+    #[default]
+    Lifetime,
+    Last1m,
+    Last5m,
+    Last15m,
+    SinceReset,
+}
+
+impl StatsWindow {
+    pub fn next(self) -> Self {
+        match self {
+            StatsWindow::Lifetime => StatsWindow::Last1m,
+            StatsWindow::Last1m => StatsWindow::Last5m,
+            StatsWindow::Last5m => StatsWindow::Last15m,
+            StatsWindow::Last15m => StatsWindow::SinceReset,
+            StatsWindow::SinceReset => StatsWindow::Lifetime,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StatsWindow::Lifetime => "lifetime",
+            StatsWindow::Last1m => "last 1m",
+            StatsWindow::Last5m => "last 5m",
+            StatsWindow::Last15m => "last 15m",
+            StatsWindow::SinceReset => "since reset",
+        }
+    }
+
+    // length of a rolling window
+    fn span(self) -> Option<Duration> {
+        match self {
+            StatsWindow::Last1m => Some(Duration::from_secs(60)),
+            StatsWindow::Last5m => Some(Duration::from_secs(5 * 60)),
+            StatsWindow::Last15m => Some(ROLLING_SPAN),
+            StatsWindow::Lifetime | StatsWindow::SinceReset => None,
+        }
+    }
+}
+
+/// Summary of the probes of a row over one window
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Aggregate {
+    pub sent: usize,
+    pub received: usize,
+    /// 0 until the first reply
+    pub min_rtt: f64,
+    pub max_rtt: f64,
+    sum_rtt: f64,
+    // squared deviations from the mean, kept up with Welford's method
+    deviations: f64,
+    // smoothed difference between consecutive replies
+    jitter: f64,
+    last_rtt: Option<f64>,
+}
+
+impl Aggregate {
+    /// Adds a probe, `None` when it got no usable reply. Lost probes count for the loss only,
+    /// jitter compares the replies around them.
+    pub fn record(&mut self, rtt: Option<f64>) {
+        self.sent += 1;
+        let Some(rtt) = rtt else {
+            return;
+        };
+        let mean = self.avg_rtt();
+        self.received += 1;
+        if self.min_rtt == 0.0 || rtt < self.min_rtt {
+            self.min_rtt = rtt;
+        }
+        self.max_rtt = self.max_rtt.max(rtt);
+        self.sum_rtt += rtt;
+        self.deviations += (rtt - mean) * (rtt - self.avg_rtt());
+        if let Some(last) = self.last_rtt {
+            // RFC 3550 section 6.4.1, the rtt difference stands in for the transit time difference
+            self.jitter += ((rtt - last).abs() - self.jitter) / 16.0;
+        }
+        self.last_rtt = Some(rtt);
+    }
+
+    pub fn lost(&self) -> usize {
+        self.sent - self.received
+    }
+
+    pub fn avg_rtt(&self) -> f64 {
+        if self.received == 0 { 0.0 } else { self.sum_rtt / self.received as f64 }
+    }
+
+    /// RFC 3550 interarrival jitter, the difference between consecutive replies smoothed over
+    /// the last 16 or so
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// population standard deviation of the rtt
+    pub fn stddev(&self) -> f64 {
+        if self.received == 0 { 0.0 } else { (self.deviations / self.received as f64).sqrt() }
+    }
+
+    /// lost probes in percent
+    pub fn loss(&self) -> f64 {
+        if self.sent == 0 { 0.0 } else { self.lost() as f64 / self.sent as f64 * 100.0 }
+    }
+
+    /// Estimated call quality, `None` before the first reply
+    pub fn quality(&self) -> Option<Quality> {
+        (self.received > 0).then(|| Quality::estimate(self.avg_rtt(), self.jitter(), self.loss()))
+    }
+}
+
+/// Voice call quality a path with this latency, jitter and loss would give
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quality {
+    /// transmission rating of the E-model (ITU-T G.107), 0 to 93.2
+    pub r_factor: f64,
+    /// mean opinion score, 1 (bad) to 4.5 (excellent)
+    pub mos: f64,
+}
+
+impl Quality {
+    /// The simplified E-model common to network monitors. Half the rtt stands in for the one way
+    /// delay, jitter buffers add twice the jitter and the codec 10 ms.
+    pub fn estimate(rtt: f64, jitter: f64, loss: f64) -> Self {
+        let delay = rtt / 2.0 + jitter * 2.0 + 10.0;
+        let r_factor = if delay < 160.0 {
+            93.2 - delay / 40.0
+        } else {
+            93.2 - (delay - 120.0) / 10.0
+        };
+        let r_factor = (r_factor - loss * 2.5).clamp(0.0, 93.2);
+        let mos = 1.0 + 0.035 * r_factor + 0.000007 * r_factor * (r_factor - 60.0) * (100.0 - r_factor);
+        Self { r_factor, mos: mos.clamp(1.0, 4.5) }
+    }
+
+    /// good enough for calls, above 4.0 most users are satisfied
+    pub fn is_good(&self) -> bool {
+        self.mos >= 4.0
+    }
+
+    /// noticeably degraded, below 3.6 many users are dissatisfied
+    pub fn is_poor(&self) -> bool {
+        self.mos < 3.6
+    }
+}
+
+/// What is known about the probes of a row: lifetime and since-reset aggregates kept up
+/// incrementally, and the outcomes of the last 15 minutes for the rolling windows
+#[derive(Debug, Clone, Default)]
+pub struct Statistics {
+    lifetime: Aggregate,
+    since_reset: Aggregate,
+    lifetime_percentiles: SessionPercentiles,
+    since_reset_percentiles: SessionPercentiles,
+    samples: Samples,
+}
+
+/// Probes in a row per sample chunk
+const CHUNK_LEN: usize = 64;
+
+/// When a probe was sent and its rtt
+type Sample = (Instant, Option<f64>);
+
+/// Outcomes of the probes, oldest first and `None` for probes without a usable reply.
+/// Every update of a row hands a copy to the ui, so full chunks are shared between the
+/// copies and only the chunk being filled is copied.
+#[derive(Debug, Clone, Default)]
+struct Samples {
+    chunks: VecDeque<Arc<Vec<Sample>>>,
+}
+
+impl Samples {
+    fn push(&mut self, at: Instant, rtt: Option<f64>) {
+        match self.chunks.back_mut() {
+            Some(chunk) if chunk.len() < CHUNK_LEN => Arc::make_mut(chunk).push((at, rtt)),
+            _ => {
+                let mut chunk = Vec::with_capacity(CHUNK_LEN);
+                chunk.push((at, rtt));
+                self.chunks.push_back(Arc::new(chunk));
+            }
+        }
+    }
+
+    /// Drops the chunks with nothing newer than `since`
+    fn expire(&mut self, since: Instant) {
+        while self.chunks.front().and_then(|chunk| chunk.last()).is_some_and(|(at, _)| *at < since) {
+            self.chunks.pop_front();
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Sample> {
+        self.chunks.iter().flat_map(|chunk| chunk.iter())
+    }
+}
+
+impl Statistics {
+    pub fn record(&mut self, rtt: Option<f64>) {
+        let now = Instant::now();
+        self.lifetime.record(rtt);
+        self.since_reset.record(rtt);
+        if let Some(rtt) = rtt {
+            self.lifetime_percentiles.observe(rtt);
+            self.since_reset_percentiles.observe(rtt);
+        }
+        self.samples.push(now, rtt);
+        if let Some(since) = now.checked_sub(ROLLING_SPAN) {
+            self.samples.expire(since);
+        }
+    }
+
+    /// Starts the since-reset aggregates over
+    pub fn reset(&mut self) {
+        self.since_reset = Aggregate::default();
+        self.since_reset_percentiles = SessionPercentiles::default();
+    }
+
+    pub fn aggregate(&self, window: StatsWindow) -> Aggregate {
+        match window {
+            StatsWindow::Lifetime => self.lifetime,
+            StatsWindow::SinceReset => self.since_reset,
+            _ => {
+                let mut aggregate = Aggregate::default();
+                for (_, rtt) in self.rolling(window) {
+                    aggregate.record(*rtt);
+                }
+                aggregate
+            }
+        }
+    }
+
+    /// The percentile at `index` of [`PERCENTILES`], estimated for the lifetime and since-reset
+    /// windows and exact for the rolling ones
+    pub fn percentile(&self, window: StatsWindow, index: usize) -> Option<f64> {
+        match window {
+            StatsWindow::Lifetime => self.lifetime_percentiles.get(index),
+            StatsWindow::SinceReset => self.since_reset_percentiles.get(index),
+            _ => {
+                let mut rtts: Vec<f64> = self.rolling(window).filter_map(|(_, rtt)| *rtt).collect();
+                if rtts.is_empty() {
+                    return None;
+                }
+                rtts.sort_by(f64::total_cmp);
+                // interpolated between the closest ranks
+                let rank = (rtts.len() - 1) as f64 * PERCENTILES.get(index)?.1;
+                let (lower, upper) = (rtts[rank.floor() as usize], rtts[rank.ceil() as usize]);
+                Some(lower + (upper - lower) * rank.fract())
+            }
+        }
+    }
+
+    // samples of a rolling window, older ones may still be around in the oldest chunk
+    fn rolling(&self, window: StatsWindow) -> impl Iterator<Item = &Sample> {
+        let since = window.span().and_then(|span| Instant::now().checked_sub(span));
+        self.samples.iter().filter(move |(at, _)| since.is_none_or(|since| *at >= since))
+    }
+}
+
+/// Runs of consecutive lost probes, to tell random loss from bursty loss
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LossBursts {
+    sent: usize,
+    lost: usize,
+    /// runs so far, including the current one
+    bursts: usize,
+    /// length of the current run, 0 after a reply
+    current: usize,
+    pub longest: usize,
+}
+
+impl LossBursts {
+    pub fn record(&mut self, lost: bool) {
+        self.sent += 1;
+        if !lost {
+            self.current = 0;
+            return;
+        }
+        self.lost += 1;
+        if self.current == 0 {
+            self.bursts += 1;
+        }
+        self.current += 1;
+        self.longest = self.longest.max(self.current);
+    }
+
+    pub fn count(&self) -> usize {
+        self.bursts
+    }
+
+    /// mean length of the runs, 0 without loss
+    pub fn mean(&self) -> f64 {
+        if self.bursts == 0 { 0.0 } else { self.lost as f64 / self.bursts as f64 }
+    }
+
+    /// Gilbert-Elliott burst ratio: the mean run length over the one random loss at the same
+    /// rate would give. 1 for random loss, above for bursty and below for spread out loss.
+    /// `None` without both losses and replies.
+    pub fn ratio(&self) -> Option<f64> {
+        if self.bursts == 0 || self.lost == self.sent {
+            return None;
+        }
+        let loss = self.lost as f64 / self.sent as f64;
+        // random loss at rate p makes runs of 1 / (1 - p) on average
+        Some(self.mean() * (1.0 - loss))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // uniform numbers in [0, 1) from a fixed seed, so the tests see the same sample every run
+    fn uniform(n: usize) -> Vec<f64> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        (0..n)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (state >> 11) as f64 / (1u64 << 53) as f64
+            })
+            .collect()
+    }
+
+    // the quantile of the sorted sample, interpolated between the closest ranks
+    fn exact(sample: &[f64], quantile: f64) -> f64 {
+        let mut sorted = sample.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let rank = (sorted.len() - 1) as f64 * quantile;
+        let (lower, upper) = (sorted[rank.floor() as usize], sorted[rank.ceil() as usize]);
+        lower + (upper - lower) * rank.fract()
+    }
+
+    fn estimate(sample: &[f64], quantile: f64) -> f64 {
+        let mut estimator = P2Quantile::new(quantile);
+        for &x in sample {
+            estimator.observe(x);
+        }
+        estimator.value().unwrap()
+    }
+
+    #[test]
+    fn p2_follows_the_exact_quantiles_of_a_uniform_sample() {
+        let sample: Vec<f64> = uniform(10_000).into_iter().map(|u| 20.0 + u * 80.0).collect();
+        for (name, quantile) in PERCENTILES {
+            let (estimate, exact) = (estimate(&sample, quantile), exact(&sample, quantile));
+            assert!((estimate - exact).abs() < 0.8, "{}: estimate {} exact {}", name, estimate, exact);
+        }
+    }
+
+    #[test]
+    fn p2_follows_the_exact_quantiles_of_a_skewed_sample() {
+        // exponential rtts around a 10 ms base, the long tail typical of latencies
+        let sample: Vec<f64> = uniform(10_000).into_iter().map(|u| 10.0 - 5.0 * (1.0 - u).ln()).collect();
+        for (name, quantile) in PERCENTILES {
+            let (estimate, exact) = (estimate(&sample, quantile), exact(&sample, quantile));
+            assert!((estimate - exact).abs() / exact < 0.03, "{}: estimate {} exact {}", name, estimate, exact);
+        }
+    }
+
+    #[test]
+    fn p2_is_exact_up_to_five_observations() {
+        let mut estimator = P2Quantile::new(0.5);
+        assert_eq!(estimator.value(), None);
+        for x in [9.0, 1.0, 5.0] {
+            estimator.observe(x);
+        }
+        assert_eq!(estimator.value(), Some(5.0));
+        for x in [3.0, 7.0] {
+            estimator.observe(x);
+        }
+        assert_eq!(estimator.value(), Some(5.0));
+    }
+
+    fn aggregate(rtts: &[Option<f64>]) -> Aggregate {
+        let mut aggregate = Aggregate::default();
+        for &rtt in rtts {
+            aggregate.record(rtt);
+        }
+        aggregate
+    }
+
+    #[test]
+    fn jitter_is_smoothed_over_sixteen_replies() {
+        assert_eq!(aggregate(&[Some(10.0)]).jitter(), 0.0);
+        // J += (|D| - J) / 16
+        assert_eq!(aggregate(&[Some(10.0), Some(20.0)]).jitter(), 0.625);
+        assert_eq!(aggregate(&[Some(10.0), Some(20.0), Some(10.0)]).jitter(), 0.625 + (10.0 - 0.625) / 16.0);
+        // a steady rtt lets it decay by 15/16 per reply
+        assert_eq!(aggregate(&[Some(10.0), Some(20.0), Some(20.0)]).jitter(), 0.625 * 15.0 / 16.0);
+    }
+
+    #[test]
+    fn jitter_compares_the_replies_around_a_loss() {
+        assert_eq!(aggregate(&[Some(10.0), None, None, Some(20.0)]).jitter(), 0.625);
+    }
+
+    #[test]
+    fn jitter_converges_to_the_mean_difference() {
+        let rtts: Vec<Option<f64>> = (0..500).map(|i| Some(if i % 2 == 0 { 10.0 } else { 14.0 })).collect();
+        assert!((aggregate(&rtts).jitter() - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stddev_is_the_population_standard_deviation_of_the_replies() {
+        let replies = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert_eq!(aggregate(&replies.map(Some)).stddev(), 2.0);
+        assert_eq!(aggregate(&[]).stddev(), 0.0);
+        assert_eq!(aggregate(&[Some(3.0)]).stddev(), 0.0);
+
+        // losses don't count
+        let with_losses: Vec<Option<f64>> = replies.iter().flat_map(|&rtt| [Some(rtt), None]).collect();
+        let summary = aggregate(&with_losses);
+        assert_eq!(summary.stddev(), 2.0);
+        assert_eq!(summary.avg_rtt(), 5.0);
+        assert_eq!(summary.loss(), 50.0);
+    }
+
+    #[test]
+    fn stddev_is_stable_far_from_zero() {
+        // the sum of squares would lose the deviations to rounding here
+        let replies = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0].map(|rtt| Some(1e9 + rtt));
+        assert!((aggregate(&replies).stddev() - 2.0).abs() < 1e-6);
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn e_model_below_160_ms_of_delay() {
+        // 20 / 2 + 2 * 2 + 10 = 24 ms one way
+        let quality = Quality::estimate(20.0, 2.0, 0.0);
+        assert_close(quality.r_factor, 93.2 - 24.0 / 40.0);
+        assert_close(quality.mos, 1.0 + 0.035 * 92.6 + 0.000007 * 92.6 * 32.6 * 7.4);
+        assert!(quality.is_good());
+    }
+
+    #[test]
+    fn e_model_above_160_ms_of_delay() {
+        // 300 / 2 + 2 * 5 + 10 = 170 ms one way, each 10 ms above 120 cost a point
+        let quality = Quality::estimate(300.0, 5.0, 0.0);
+        assert_close(quality.r_factor, 88.2);
+        assert!(quality.r_factor < 93.2 - 170.0 / 40.0);
+        // both formulas meet at 160 ms
+        assert_close(Quality::estimate(300.0, 0.0, 0.0).r_factor, 89.2);
+        assert!((Quality::estimate(299.999, 0.0, 0.0).r_factor - 89.2).abs() < 1e-3);
+    }
+
+    #[test]
+    fn e_model_loss_penalty() {
+        // 2.5 points per percent lost
+        let lossless = Quality::estimate(20.0, 0.0, 0.0);
+        assert_close(lossless.r_factor - Quality::estimate(20.0, 0.0, 4.0).r_factor, 10.0);
+        assert!(Quality::estimate(20.0, 0.0, 10.0).is_poor());
+        // the rating bottoms out at 0, the mos at 1
+        let lost = Quality::estimate(20.0, 0.0, 50.0);
+        assert_eq!((lost.r_factor, lost.mos), (0.0, 1.0));
+    }
+
+    // `x` for a lost probe, anything else for a reply
+    fn bursts(outcomes: &str) -> LossBursts {
+        let mut bursts = LossBursts::default();
+        for outcome in outcomes.chars() {
+            bursts.record(outcome == 'x');
+        }
+        bursts
+    }
+
+    #[test]
+    fn burst_ratio_is_the_mean_run_over_the_random_one() {
+        // runs of 2, 1 and 3, half of the probes lost: random loss would make runs of 2
+        let mixed = bursts(".xx..x..xxx.");
+        assert_eq!((mixed.count(), mixed.longest, mixed.mean()), (3, 3, 2.0));
+        assert_close(mixed.ratio().unwrap(), 2.0 * (1.0 - 0.5));
+
+        // one run of 5 in 100 probes is far burstier than 5% random loss
+        let bursty = bursts(&format!("{}xxxxx{}", ".".repeat(50), ".".repeat(45)));
+        assert_close(bursty.ratio().unwrap(), 5.0 * (1.0 - 0.05));
+
+        // every other probe lost, the losses are spread out
+        assert_close(bursts(".x.x.x.x").ratio().unwrap(), 0.5);
+    }
+
+    #[test]
+    fn burst_ratio_needs_losses_and_replies() {
+        assert_eq!(bursts("").ratio(), None);
+        assert_eq!(bursts("....").ratio(), None);
+        assert_eq!(bursts("xxxx").ratio(), None);
+        assert_eq!(bursts("....").mean(), 0.0);
+    }
+
+    #[test]
+    fn copies_share_the_full_sample_chunks() {
+        let mut stats = Statistics::default();
+        for i in 0..CHUNK_LEN * 2 + 10 {
+            stats.record((i % 3 != 0).then_some(i as f64));
+        }
+        let mut copy = stats.clone();
+        assert_eq!(stats.samples.chunks.len(), 3);
+        assert!(Arc::ptr_eq(&stats.samples.chunks[0], &copy.samples.chunks[0]));
+        assert!(Arc::ptr_eq(&stats.samples.chunks[2], &copy.samples.chunks[2]));
+
+        // filling up the last chunk of one copy leaves the other alone
+        copy.record(Some(1.0));
+        assert!(!Arc::ptr_eq(&stats.samples.chunks[2], &copy.samples.chunks[2]));
+        assert_eq!(stats.samples.iter().count(), CHUNK_LEN * 2 + 10);
+        assert_eq!(copy.samples.iter().count(), CHUNK_LEN * 2 + 11);
+
+        // every sample is within the last minute, the rolling window sees them all
+        let (rolling, lifetime) = (stats.aggregate(StatsWindow::Last1m), stats.aggregate(StatsWindow::Lifetime));
+        assert_eq!((rolling.sent, rolling.received), (lifetime.sent, lifetime.received));
+        assert_eq!(rolling.avg_rtt(), lifetime.avg_rtt());
+    }
+
+    #[test]
+    fn expired_chunks_are_dropped_whole() {
+        let start = Instant::now();
+        let mut samples = Samples::default();
+        for i in 0..CHUNK_LEN * 2 {
+            samples.push(start + Duration::from_secs(i as u64), Some(1.0));
+        }
+        // the first chunk still has a sample that is recent enough
+        samples.expire(start + Duration::from_secs(CHUNK_LEN as u64 - 1));
+        assert_eq!(samples.chunks.len(), 2);
+        samples.expire(start + Duration::from_secs(CHUNK_LEN as u64));
+        assert_eq!(samples.chunks.len(), 1);
+        assert_eq!(samples.iter().next().map(|(at, _)| *at), Some(start + Duration::from_secs(CHUNK_LEN as u64)));
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::net::IpAddr;
+use anyhow::anyhow;
+use serde::de::{self, value::MapAccessDeserializer, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::config::{check_tag, split_tag, TargetSpec, Thresholds};
+use crate::network;
+use crate::probe;
+
+/// Reads the targets of a `--targets-file`. The format follows the extension: `.csv` with
+/// `address`, `name` and `tags` columns, `.yaml`/`.yml` inventories, anything else is read
+/// like `/etc/hosts`. Comments and blank lines are skipped, malformed entries are errors
+/// naming their line.
+pub fn load(path: &str, thresholds: Thresholds) -> anyhow::Result<Vec<TargetSpec>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|err| anyhow!("unable to read targets file {}: {}", path, err))?;
+    let extension = std::path::Path::new(path).extension().and_then(|ext| ext.to_str()).unwrap_or_default();
+    let entries = match extension.to_ascii_lowercase().as_str() {
+        "csv" => parse_csv(&text),
+        "yaml" | "yml" => parse_yaml(&text),
+        _ => parse_hosts(&text),
+    };
+
+    let mut specs = Vec::new();
+    let mut errors = Vec::new();
+    for (line, entry) in entries {
+        match entry {
+            Ok(entry) => specs.push(TargetSpec {
+                target: entry.address,
+                name: entry.name,
+                interval: None,
+                tags: entry.tags,
+                thresholds,
+            }),
+            Err(err) => errors.push(format!("{}:{}: {:#}", path, line, err)),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(anyhow!("{}", errors.join("\n")));
+    }
+    Ok(specs)
+}
+
+/// One target of the file, checked like a `[[targets]]` entry of the config file
+#[derive(Debug)]
+struct Entry {
+    address: String,
+    name: Option<String>,
+    tags: Vec<String>,
+}
+
+impl Entry {
+    fn new(address: String, name: Option<String>, tags: Vec<String>) -> anyhow::Result<Self> {
+        if address.is_empty() {
+            return Err(anyhow!("address is missing"));
+        }
+        probe::parse_target(&address)?;
+        network::expand_target(&address)?;
+        let mut keys = HashSet::new();
+        for tag in &tags {
+            check_tag(tag)?;
+            if !keys.insert(split_tag(tag).0) {
+                return Err(anyhow!("duplicate tag key: {}", split_tag(tag).0));
+            }
+        }
+        Ok(Self { address, name, tags })
+    }
+}
+
+// `address name [aliases...]`, the first name becomes the name of the target
+fn parse_hosts(text: &str) -> Vec<(usize, anyhow::Result<Entry>)> {
+    let mut entries = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let Some(address) = fields.next() else {
+            continue;
+        };
+        let entry = match (address.parse::<IpAddr>(), fields.next()) {
+            (Err(_), _) => Err(anyhow!("not an IP address: {}", address)),
+            (Ok(_), None) => Err(anyhow!("no hostname after {}", address)),
+            (Ok(_), Some(name)) => Entry::new(address.to_string(), Some(name.to_string()), Vec::new()),
+        };
+        entries.push((index + 1, entry));
+    }
+    entries
+}
+
+/// A CSV row, tags are separated by `;` or spaces
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CsvRow {
+    address: String,
+    name: Option<String>,
+    tags: Option<String>,
+}
+
+fn parse_csv(text: &str) -> Vec<(usize, anyhow::Result<Entry>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .trim(csv::Trim::All)
+        .from_reader(text.as_bytes());
+    let mut entries = Vec::new();
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(err) => return vec![(csv_line(&err), Err(csv_error(&err)))],
+    };
+    if !headers.iter().any(|header| header == "address") {
+        return vec![(1, Err(anyhow!("header must name an address column")))];
+    }
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                entries.push((csv_line(&err), Err(csv_error(&err))));
+                continue;
+            }
+        };
+        let line = record.position().map_or(0, |position| position.line() as usize);
+        let entry = record
+            .deserialize::<CsvRow>(Some(&headers))
+            .map_err(|err| anyhow!("{}", err))
+            .and_then(|row| {
+                let tags = row.tags.unwrap_or_default()
+                    .split(|c: char| c == ';' || c.is_whitespace())
+                    .filter(|tag| !tag.is_empty())
+                    .map(String::from)
+                    .collect();
+                Entry::new(row.address, row.name, tags)
+            });
+        entries.push((line, entry));
+    }
+    entries
+}
+
+fn csv_line(err: &csv::Error) -> usize {
+    err.position().map_or(0, |position| position.line() as usize)
+}
+
+// without the position, the caller prefixes the line already
+fn csv_error(err: &csv::Error) -> anyhow::Error {
+    match err.kind() {
+        csv::ErrorKind::UnequalLengths { expected_len, len, .. } => anyhow!("found {} fields, the header has {}", len, expected_len),
+        _ => anyhow!("{}", err),
+    }
+}
+
+/// A YAML inventory entry, a plain address or a mapping. Entries are checked while they
+/// are parsed, so the parser puts its position on their errors.
+struct YamlEntry(Entry);
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct YamlTarget {
+    address: String,
+    name: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+impl<'de> Deserialize<'de> for YamlEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntryVisitor;
+
+        impl<'de> Visitor<'de> for EntryVisitor {
+            type Value = Entry;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an address or a mapping of address, name and tags")
+            }
+
+            fn visit_str<E: de::Error>(self, address: &str) -> Result<Entry, E> {
+                Entry::new(address.to_string(), None, Vec::new()).map_err(|err| E::custom(format!("{:#}", err)))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Entry, A::Error> {
+                let target = YamlTarget::deserialize(MapAccessDeserializer::new(map))?;
+                Entry::new(target.address, target.name, target.tags).map_err(|err| de::Error::custom(format!("{:#}", err)))
+            }
+        }
+
+        deserializer.deserialize_any(EntryVisitor).map(YamlEntry)
+    }
+}
+
+// a top level list of entries. The parser stops at the first error, syntax or entry.
+fn parse_yaml(text: &str) -> Vec<(usize, anyhow::Result<Entry>)> {
+    match serde_yaml::from_str::<Option<Vec<YamlEntry>>>(text) {
+        // the line only names errors
+        Ok(entries) => entries.unwrap_or_default().into_iter().map(|YamlEntry(entry)| (0, Ok(entry))).collect(),
+        Err(err) => {
+            let line = err.location().map_or(0, |location| location.line());
+            // the message repeats the position, the caller prefixes the line already
+            let msg = err.to_string();
+            let msg = msg.split(" at line ").next().unwrap_or(&msg).to_string();
+            vec![(line, Err(anyhow!(msg)))]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an entry as (address, name, tags), an error as (line, message)
+    type Parsed = Result<(String, Option<String>, Vec<String>), (usize, String)>;
+
+    fn parsed(entries: Vec<(usize, anyhow::Result<Entry>)>) -> Vec<Parsed> {
+        entries
+            .into_iter()
+            .map(|(line, entry)| match entry {
+                Ok(entry) => Ok((entry.address, entry.name, entry.tags)),
+                Err(err) => Err((line, format!("{:#}", err))),
+            })
+            .collect()
+    }
+
+    fn target(address: &str, name: Option<&str>, tags: &[&str]) -> Parsed {
+        Ok((address.to_string(), name.map(String::from), tags.iter().map(|tag| tag.to_string()).collect()))
+    }
+
+    #[test]
+    fn reads_hosts_files() {
+        let text = "# lab\n\n10.0.0.1   db01 db01.lab  # primary\n::1 localhost\nnot-an-ip web\n10.0.0.2\n";
+        assert_eq!(parsed(parse_hosts(text)), vec![
+            target("10.0.0.1", Some("db01"), &[]),
+            target("::1", Some("localhost"), &[]),
+            Err((5, "not an IP address: not-an-ip".to_string())),
+            Err((6, "no hostname after 10.0.0.2".to_string())),
+        ]);
+    }
+
+    #[test]
+    fn reads_csv_files() {
+        let text = "address,name,tags\n# spare\n10.0.0.1,db01,dc=fra;role=db\ntcp://10.0.0.2:5432, ,dc=fra role=db\n10.0.0.3,web,\n10.0.0.4,web,dc=fra,extra\n10.0.0.5,web,ip=1\n";
+        let entries = parsed(parse_csv(text));
+        assert_eq!(entries[0], target("10.0.0.1", Some("db01"), &["dc=fra", "role=db"]));
+        assert_eq!(entries[1], target("tcp://10.0.0.2:5432", None, &["dc=fra", "role=db"]));
+        assert_eq!(entries[2], target("10.0.0.3", Some("web"), &[]));
+        assert_eq!(entries[3], Err((6, "found 4 fields, the header has 3".to_string())));
+        assert_eq!(entries[4], Err((7, "tag key is reserved: ip".to_string())));
+        assert_eq!(entries.len(), 5);
+    }
+
+    #[test]
+    fn csv_needs_an_address_column() {
+        assert_eq!(parsed(parse_csv("host,name\n10.0.0.1,db01\n")), vec![Err((1, "header must name an address column".to_string()))]);
+    }
+
+    #[test]
+    fn reads_yaml_inventories() {
+        let text = "# lab\n- 10.0.0.1\n- address: tcp://db01:5432\n  name: db01\n  tags: [dc=fra, role=db]\n";
+        assert_eq!(parsed(parse_yaml(text)), vec![
+            target("10.0.0.1", None, &[]),
+            target("tcp://db01:5432", Some("db01"), &["dc=fra", "role=db"]),
+        ]);
+        assert!(parse_yaml("# nothing yet\n").is_empty());
+    }
+
+    #[test]
+    fn yaml_errors_keep_the_parser_position_and_message() {
+        // an indented list with the misspelt key of its third entry on line 6
+        let text = "  - 10.0.0.1\n  - address: 10.0.0.2\n    name: db02\n  -\n    address: 10.0.0.3\n    nmae: db03\n";
+        let entries = parsed(parse_yaml(text));
+        assert_eq!(entries.len(), 1);
+        let (line, msg) = entries[0].clone().unwrap_err();
+        assert_eq!(line, 6);
+        assert!(msg.contains("unknown field `nmae`"), "{}", msg);
+
+        let entries = parsed(parse_yaml("- 10.0.0.1\n- address: 10.0.0.2\n  tags: [ip=1]\n"));
+        assert_eq!(entries, vec![Err((2, ".[1]: tag key is reserved: ip".to_string()))]);
+
+        let (line, msg) = parsed(parse_yaml("- 10.0.0.1\n- [10.0.0.2\n")).remove(0).unwrap_err();
+        assert_eq!(line, 2);
+        assert!(!msg.contains(" at line "), "{}", msg);
+    }
+
+    #[test]
+    fn errors_name_the_file_and_line() {
+        let path = std::env::temp_dir().join(format!("pingwatch-{}-hosts", std::process::id()));
+        std::fs::write(&path, "10.0.0.1 db01\n10.0.0.2\n").unwrap();
+        let result = load(path.to_str().unwrap(), Thresholds::default());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(result.unwrap_err().to_string(), format!("{}:2: no hostname after 10.0.0.2", path.display()));
+    }
+}
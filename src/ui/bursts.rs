@@ -0,0 +1,75 @@
+use ratatui::Frame;
+use ratatui::layout::Constraint;
+use ratatui::prelude::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, Clear, Row, Table};
+use ratatui::crossterm::event::KeyCode;
+use crate::ip_data::IpData;
+use crate::stats::StatsWindow;
+use crate::ui::utils::pane_area;
+use crate::i18n;
+
+/// Loss burst details of every row over the lower part of the screen, toggled with `b`
+#[derive(Debug, Default)]
+pub struct BurstPane {
+    pub shown: bool,
+}
+
+impl BurstPane {
+    /// Returns whether the screen has to be redrawn
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char('b') => self.shown = !self.shown,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Draws the bursts of every row since it was started, the burstiest first
+pub fn draw_bursts(f: &mut Frame, pane: &BurstPane, ip_data: &[IpData], lang: &str) {
+    if !pane.shown {
+        return;
+    }
+    let area = pane_area(f.area());
+
+    let mut rows: Vec<&IpData> = ip_data.iter().collect();
+    rows.sort_by(|a, b| {
+        b.bursts.ratio().unwrap_or_default().total_cmp(&a.bursts.ratio().unwrap_or_default())
+            .then(b.bursts.longest.cmp(&a.bursts.longest))
+    });
+
+    let header = Row::new(vec![
+        i18n::t(lang, "label-target"),
+        i18n::t(lang, "label-ip"),
+        i18n::t(lang, "label-loss"),
+        i18n::t(lang, "burst-count"),
+        i18n::t(lang, "burst-longest"),
+        i18n::t(lang, "burst-mean"),
+        i18n::t(lang, "label-burst-ratio"),
+    ]).style(Style::default().add_modifier(Modifier::BOLD));
+    let rows = rows.into_iter().map(|data| {
+        let bursts = &data.bursts;
+        let row = Row::new(vec![
+            data.label().to_string(),
+            data.display_ip(),
+            format!("{:.2}{}", data.summary(StatsWindow::Lifetime).loss(), i18n::t(lang, "unit-percent")),
+            bursts.count().to_string(),
+            bursts.longest.to_string(),
+            format!("{:.2}", bursts.mean()),
+            bursts.ratio().map_or(String::from("-"), |ratio| format!("{:.2}", ratio)),
+        ]);
+        // losses that come in runs hurt more than the percentage says
+        if bursts.ratio().is_some_and(|ratio| ratio > 1.5) {
+            row.style(Style::default().fg(Color::Yellow))
+        } else {
+            row
+        }
+    });
+
+    let block = Block::default()
+        .title(format!("{} ({})  b: close", i18n::t(lang, "bursts-title"), i18n::t(lang, "bursts-note")))
+        .borders(Borders::ALL);
+    let widths = [20, 25, 10, 10, 10, 10, 15].map(Constraint::Percentage);
+    f.render_widget(Clear, area);
+    f.render_widget(Table::new(rows, widths).header(header).block(block), area);
+}
@@ -4,14 +4,19 @@ use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::prelude::{Color, Line, Span, Style};
 use ratatui::widgets::{Axis, Block, Chart, Dataset, Paragraph, Wrap};
 use crate::ip_data::IpData;
-use crate::ui::utils::{calculate_avg_rtt, calculate_jitter, draw_errors_section};
+use crate::stats::StatsWindow;
+use crate::ui::utils::draw_errors_section;
 
 pub fn draw_graph_view<B: Backend>(
     f: &mut Frame,
     ip_data: &[IpData],
+    window: StatsWindow,
     errs: &[String]) {
     let size = f.area();
-    let rows = (ip_data.len() as f64 / 5.0).ceil() as usize;
+    // an even number of charts per row keeps the v4 and v6 chart of a --dual-stack target side by side
+    let paired = ip_data.iter().any(|a| ip_data.iter().any(|b| a.is_pair_of(b)));
+    let per_row = if paired { 4 } else { 5 };
+    let rows = ip_data.len().div_ceil(per_row);
     let mut chunks = Vec::new();
 
     // compute the constraints
@@ -29,17 +34,17 @@ pub fn draw_graph_view<B: Backend>(
         .split(size);
 
     for (row, vertical_chunk) in vertical_chunks.iter().enumerate().take(rows) {
-        let start = row * 5;
-        let end = (start + 5).min(ip_data.len());
+        let start = row * per_row;
+        let end = (start + per_row).min(ip_data.len());
         let row_data = &ip_data[start..end];
 
-        let horizontal_constraints: Vec<Constraint> = if row_data.len() == 5 {
-            row_data.iter().map(|_| Constraint::Percentage(20)).collect()
+        let horizontal_constraints: Vec<Constraint> = if row_data.len() == per_row {
+            row_data.iter().map(|_| Constraint::Percentage(100 / per_row as u16)).collect()
         } else {
-            // when the number of targets is less than 5, we need to adjust the size of each target
+            // when the number of targets is less than a full row, we need to adjust the size of each target
             let mut size = 100;
-            if ip_data.len() > 5 {
-                size = row_data.len() * 20;
+            if ip_data.len() > per_row {
+                size = row_data.len() * (100 / per_row);
             }
             row_data.iter().map(|_| Constraint::Percentage(size as u16 / row_data.len() as u16)).collect()
         };
@@ -51,12 +56,9 @@ pub fn draw_graph_view<B: Backend>(
             .split(*vertical_chunk);
 
         for (i, data) in row_data.iter().enumerate() {
-            // compute the loss package rate for each target
-            let loss_pkg = if data.timeout > 0 {
-                (data.timeout as f64 / (data.received as f64 + data.timeout as f64)) * 100.0
-            } else {
-                0.0
-            };
+            // refused probes and failed checks count as lost, the target did not answer the way we asked
+            let summary = data.summary(window);
+            let loss_pkg = summary.loss();
 
             let loss_pkg_color = if loss_pkg > 50.0 {
                 Color::Red
@@ -83,19 +85,31 @@ pub fn draw_graph_view<B: Backend>(
                     )
                     .split(area);
 
-                // render the content of each target
-                let avg_rtt = calculate_avg_rtt(&data.rtts);
-
-
-                // calculate the jitter
-                let jitter = calculate_jitter(&data.rtts);
-
-
                 // render the target text
-                let target_text = Line::from(vec![
+                let mut target_spans = vec![
                     Span::styled("Target: ", Style::default()),
-                    Span::styled(&data.addr, Style::default().fg(Color::Green)),
-                ]);
+                    Span::styled(data.label(), Style::default().fg(Color::Green)),
+                ];
+                if paired {
+                    target_spans.push(Span::styled(format!(" {}", data.family()), Style::default().fg(Color::Cyan)));
+                }
+                if !data.tags.is_empty() {
+                    target_spans.push(Span::styled(format!(" [{}]", data.tags.join(", ")), Style::default().fg(Color::DarkGray)));
+                }
+                // estimated call quality, what voice users look at first
+                if let Some(quality) = summary.quality() {
+                    let mos_color = if quality.is_good() {
+                        Color::Green
+                    } else if quality.is_poor() {
+                        Color::Red
+                    } else {
+                        Color::Yellow
+                    };
+                    target_spans.push(Span::raw("  MOS: "));
+                    target_spans.push(Span::styled(format!("{:.2}", quality.mos), Style::default().fg(mos_color)));
+                    target_spans.push(Span::styled(format!(" (R {:.1})", quality.r_factor), Style::default().fg(Color::DarkGray)));
+                }
+                let target_text = Line::from(target_spans);
 
                 let base_metric_text = Line::from(vec![
                     Span::styled("Last: ", Style::default()),
@@ -111,16 +125,16 @@ pub fn draw_graph_view<B: Backend>(
                     ),
                     Span::raw("  "),
                     Span::styled("Avg Rtt : ", Style::default()),
-                    Span::styled(format!("{:.2} ms", avg_rtt), Style::default().fg(Color::Green)),
+                    Span::styled(format!("{:.2} ms", summary.avg_rtt()), Style::default().fg(Color::Green)),
                     Span::raw("  "),
                     Span::styled("Jitter: ", Style::default()),
-                    Span::styled(format!("{:.2} ms", jitter), Style::default().fg(Color::Green)),
+                    Span::styled(format!("{:.2} ms", summary.jitter()), Style::default().fg(Color::Green)),
                     Span::raw("  "),
                     Span::styled("Max: ", Style::default()),
-                    Span::styled(format!("{:.2} ms", data.max_rtt), Style::default().fg(Color::Green)),
+                    Span::styled(format!("{:.2} ms", summary.max_rtt), Style::default().fg(Color::Green)),
                     Span::raw("  "),
                     Span::styled("Min: ", Style::default()),
-                    Span::styled(format!("{:.2} ms", data.min_rtt), Style::default().fg(Color::Green)),
+                    Span::styled(format!("{:.2} ms", summary.min_rtt), Style::default().fg(Color::Green)),
                     Span::raw("  "),
                     Span::styled("Loss: ", Style::default()),
                     Span::styled(format!("{:.2}%", loss_pkg), Style::default().fg(loss_pkg_color)),
@@ -147,7 +161,8 @@ pub fn draw_graph_view<B: Backend>(
                     .graph_type(ratatui::widgets::GraphType::Line)
                     .data(&data_points)];
 
-                let y_bounds = [0.0, data.max_rtt * 1.2];
+                // scaled to the plotted replies, the window may have seen larger ones
+                let y_bounds = [0.0, data.rtts.iter().copied().fold(0.0, f64::max) * 1.2];
 
                 let x_range = data
                     .rtts
@@ -196,7 +211,7 @@ pub fn draw_graph_view<B: Backend>(
                             Color::Green
                         };
                         Line::from(vec![
-                            Span::styled(&data.ip, Style::default()),
+                            Span::styled(data.display_ip(), Style::default()),
                             Span::raw(" "),
                             Span::styled(display_text, Style::default().fg(display_color)),
                         ])
@@ -0,0 +1,127 @@
+use ratatui::backend::Backend;
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::prelude::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Paragraph, Row, Table};
+use crate::ip_data::{IpData, SILENT_HOP};
+use crate::stats::StatsWindow;
+use crate::ui::utils::draw_errors_section;
+use crate::i18n;
+
+
+pub fn draw_hops_view<B: Backend>(
+    f: &mut Frame,
+    ip_data: &[IpData],
+    window: StatsWindow,
+    errs: &[String],
+    area: Rect,
+    lang: &str,
+) {
+    let header_style = Style::default()
+        .add_modifier(Modifier::BOLD);
+
+    // create header
+    let header = Row::new(vec![
+        i18n::t(lang, "label-target"),
+        i18n::t(lang, "label-hop"),
+        i18n::t(lang, "label-host"),
+        i18n::t(lang, "label-loss"),
+        i18n::t(lang, "label-sent"),
+        i18n::t(lang, "label-last-rtt"),
+        i18n::t(lang, "label-avg-rtt"),
+        i18n::t(lang, "label-best"),
+        i18n::t(lang, "label-worst"),
+        i18n::t(lang, "label-jitter"),
+    ])
+        .style(header_style)
+        .height(1);
+
+    // rows keep the path order, the target is only named on its first hop
+    let rows = ip_data.iter().enumerate().map(|(index, data)| {
+        let first_hop = index == 0
+            || ip_data[index - 1].addr != data.addr
+            || ip_data[index - 1].source != data.source;
+        let target = if first_hop {
+            match &data.source {
+                Some(source) => format!("{} via {}", data.label(), source),
+                None => data.label().to_string(),
+            }
+        } else {
+            String::new()
+        };
+        let hop = data.hop.map(|hop| hop.to_string()).unwrap_or_default();
+
+        if data.ip == SILENT_HOP {
+            let mut cells = vec![target, hop, "???".to_string()];
+            cells.extend(std::iter::repeat_n(String::from("-"), 7));
+            return Row::new(cells).height(1).style(Style::default().fg(Color::DarkGray));
+        }
+
+        let summary = data.summary(window);
+        let (avg_rtt, jitter, loss_pkg) = (summary.avg_rtt(), summary.jitter(), summary.loss());
+
+        let row = Row::new(vec![
+            target,
+            hop,
+            data.ip.clone(),
+            format!("{:.2}{}", loss_pkg, i18n::t(lang, "unit-percent")),
+            summary.sent.to_string(),
+            if data.last_attr == 0.0 {
+                i18n::t(lang, "metric-less-than")
+            } else if data.last_attr == -1.0 {
+                i18n::t(lang, "metric-zero")
+            } else {
+                format!("{:.2}{}", data.last_attr, i18n::t(lang, "unit-ms"))
+            },
+            format!("{:.2}{}", avg_rtt, i18n::t(lang, "unit-ms")),
+            format!("{:.2}{}", summary.min_rtt, i18n::t(lang, "unit-ms")),
+            format!("{:.2}{}", summary.max_rtt, i18n::t(lang, "unit-ms")),
+            format!("{:.2}{}", jitter, i18n::t(lang, "unit-ms")),
+        ]).height(1);
+
+        // routers often rate limit ICMP, so loss is only highlighted, not sorted on
+        if loss_pkg > 50.0 {
+            row.style(Style::default().fg(Color::Red))
+        } else if loss_pkg > 0.0 {
+            row.style(Style::default().fg(Color::Yellow))
+        } else {
+            row
+        }
+    });
+
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Percentage(15),
+            Constraint::Percentage(4),
+            Constraint::Percentage(17),
+            Constraint::Percentage(8),
+            Constraint::Percentage(6),
+            Constraint::Percentage(10),
+            Constraint::Percentage(10),
+            Constraint::Percentage(10),
+            Constraint::Percentage(10),
+            Constraint::Percentage(10),
+        ],
+    )
+        .header(header)
+        .block(Block::default()
+            .title(format!("🏎  {}", i18n::t(lang, "hops-view-title"))));
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(5),
+            Constraint::Length(6),
+        ].as_ref())
+        .split(area);
+
+    // black line
+    let blank = Paragraph::new("");
+    f.render_widget(blank, chunks[0]);
+    f.render_widget(table, chunks[1]);
+
+    let errors_chunk = chunks.last().unwrap();
+    draw_errors_section::<B>(f, errs, *errors_chunk);
+}
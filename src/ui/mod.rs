@@ -3,8 +3,20 @@ mod utils;
 mod table;
 mod point;
 mod sparkline;
+mod hops;
+mod sweep;
+mod prompt;
+mod outages;
+mod bursts;
 
 pub use graph::draw_graph_view;
 pub use table::draw_table_view;
 pub use point::draw_point_view;
 pub use sparkline::draw_sparkline_view;
+pub use hops::draw_hops_view;
+pub use sweep::{draw_sweep_view, Sweep};
+pub use table::{GroupView, ExtraColumns, TargetSelection};
+pub use prompt::{draw_prompt, TargetPrompt};
+pub use outages::{draw_outages, OutagePane};
+pub use bursts::{draw_bursts, BurstPane};
+pub use utils::draw_window;
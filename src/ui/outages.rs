@@ -0,0 +1,92 @@
+use std::time::Duration;
+use ratatui::Frame;
+use ratatui::layout::Constraint;
+use ratatui::prelude::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Row, Table};
+use ratatui::crossterm::event::KeyCode;
+use crate::ip_data::IpData;
+use crate::network::clock;
+use crate::ui::utils::pane_area;
+use crate::i18n;
+
+/// The outage log over the lower part of the screen, toggled with `o`
+#[derive(Debug, Default)]
+pub struct OutagePane {
+    pub shown: bool,
+}
+
+impl OutagePane {
+    /// Returns whether the screen has to be redrawn
+    pub fn handle_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char('o') => self.shown = !self.shown,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Draws the outages of every row, most recent first
+pub fn draw_outages(f: &mut Frame, pane: &OutagePane, ip_data: &[IpData], lang: &str) {
+    if !pane.shown {
+        return;
+    }
+    let area = pane_area(f.area());
+
+    let mut outages: Vec<_> = ip_data
+        .iter()
+        .flat_map(|data| data.outages.recent().map(move |outage| (data, outage)))
+        .collect();
+    outages.sort_by(|(_, a), (_, b)| b.start.cmp(&a.start));
+
+    let total: usize = ip_data.iter().map(|data| data.outages.total()).sum();
+    let ongoing = ip_data.iter().filter(|data| data.outages.ongoing().is_some()).count();
+    let mut title = format!("{} ({})", i18n::t(lang, "outages-title"), total);
+    if ongoing > 0 {
+        title.push_str(&format!(" {}: {}", i18n::t(lang, "outage-ongoing"), ongoing));
+    }
+    title.push_str("  o: close");
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL);
+    f.render_widget(Clear, area);
+    if outages.is_empty() {
+        f.render_widget(Paragraph::new(i18n::t(lang, "outages-none")).block(block), area);
+        return;
+    }
+
+    let header = Row::new(vec![
+        i18n::t(lang, "label-target"),
+        i18n::t(lang, "label-ip"),
+        i18n::t(lang, "outage-start"),
+        i18n::t(lang, "outage-end"),
+        i18n::t(lang, "outage-duration"),
+        i18n::t(lang, "outage-lost"),
+    ]).style(Style::default().add_modifier(Modifier::BOLD));
+    let rows = outages.into_iter().map(|(data, outage)| {
+        let row = Row::new(vec![
+            data.label().to_string(),
+            data.display_ip(),
+            clock(outage.start),
+            outage.end.map_or_else(|| i18n::t(lang, "outage-ongoing"), clock),
+            format_duration(outage.duration()),
+            outage.lost.to_string(),
+        ]);
+        if outage.end.is_none() {
+            row.style(Style::default().fg(Color::Red))
+        } else {
+            row
+        }
+    });
+    let widths = [20, 25, 15, 15, 12, 10].map(Constraint::Percentage);
+    f.render_widget(Table::new(rows, widths).header(header).block(block), area);
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..60 => format!("{:.1}s", duration.as_secs_f64()),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
@@ -4,7 +4,8 @@ use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::prelude::{Color, Line, Span, Style};
 use ratatui::widgets::{Block, Paragraph, Wrap};
 use crate::ip_data::IpData;
-use crate::ui::utils::{calculate_avg_rtt, calculate_jitter, calculate_loss_pkg, draw_errors_section};
+use crate::stats::StatsWindow;
+use crate::ui::utils::draw_errors_section;
 use crate::i18n;
 
 pub fn get_loss_color_and_emoji(loss_rate: f64) -> Color {
@@ -20,6 +21,7 @@ pub fn get_loss_color_and_emoji(loss_rate: f64) -> Color {
 pub fn draw_point_view<B: Backend>(
     f: &mut Frame,
     ip_data: &[IpData],
+    window: StatsWindow,
     errs: &[String],
     area: Rect,
     lang: &str,
@@ -64,18 +66,17 @@ pub fn draw_point_view<B: Backend>(
 
 
     for (i, ip) in data.iter().enumerate() {
-        let avg_rtt = calculate_avg_rtt(&ip.rtts);
-        let jitter = calculate_jitter(&ip.rtts);
-        let loss_pkg = calculate_loss_pkg(ip.timeout, ip.received);
+        let summary = ip.summary(window);
+        let (avg_rtt, jitter, loss_pkg) = (summary.avg_rtt(), summary.jitter(), summary.loss());
         let loss_pkg_color = get_loss_color_and_emoji(loss_pkg);
 
 
         // Create the info line (row 1) with all metrics from table view
         let info_line = Line::from(vec![
             Span::raw(format!("{}: ", i18n::t(lang, "label-target"))),
-            Span::styled(format!("{} ", ip.addr), Style::default().fg(Color::Green)),
+            Span::styled(format!("{} ", ip.label()), Style::default().fg(Color::Green)),
             Span::raw(format!("{}: ", i18n::t(lang, "label-ip"))),
-            Span::styled(format!("{} ", ip.ip), Style::default().fg(Color::Green)),
+            Span::styled(format!("{} ", ip.display_ip()), Style::default().fg(Color::Green)),
             Span::raw(format!("{}: ", i18n::t(lang, "label-last-rtt"))),
             Span::styled(
                 if ip.last_attr == 0.0 {
@@ -90,11 +91,13 @@ pub fn draw_point_view<B: Backend>(
             Span::raw(format!(" {}: ", i18n::t(lang, "label-avg-rtt"))),
             Span::styled(format!("{:.2}{}", avg_rtt, i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
             Span::raw(format!(" {}: ", i18n::t(lang, "label-max"))),
-            Span::styled(format!("{:.2}{}", ip.max_rtt, i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
+            Span::styled(format!("{:.2}{}", summary.max_rtt, i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
             Span::raw(format!(" {}: ", i18n::t(lang, "label-min"))),
-            Span::styled(format!("{:.2}{}", ip.min_rtt, i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
+            Span::styled(format!("{:.2}{}", summary.min_rtt, i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
             Span::raw(format!(" {}: ", i18n::t(lang, "label-jitter"))),
             Span::styled(format!("{:.2}{}", jitter, i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
+            Span::raw(format!(" {}: ", i18n::t(lang, "label-stddev"))),
+            Span::styled(format!("{:.2}{}", summary.stddev(), i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
             Span::raw(format!(" {}: ", i18n::t(lang, "label-loss"))),
             Span::styled(format!("{:.2}{}", loss_pkg, i18n::t(lang, "unit-percent")), Style::default().fg(loss_pkg_color)), 
         ]);
@@ -102,7 +105,7 @@ pub fn draw_point_view<B: Backend>(
             if rtt < 0.0 {
                 // Timeout/packet loss - red X
                 points_spans.push(Span::styled("✗", Style::default().fg(Color::Red)));
-            } else if rtt > ip.max_rtt * 0.8 {
+            } else if rtt > summary.max_rtt * 0.8 {
                 // High latency () - yellow dot
                 points_spans.push(Span::styled("↑", Style::default().fg(Color::Yellow)));
             } else {
@@ -0,0 +1,58 @@
+use ratatui::Frame;
+use ratatui::layout::Rect;
+use ratatui::prelude::{Color, Line, Modifier, Span, Style};
+use ratatui::widgets::{Clear, Paragraph};
+use ratatui::crossterm::event::KeyCode;
+use crate::i18n;
+
+/// Input line for a target to add while running, opened with `a`
+#[derive(Debug, Default)]
+pub struct TargetPrompt {
+    /// text typed so far, `None` while the prompt is closed
+    pub input: Option<String>,
+}
+
+impl TargetPrompt {
+    pub fn open(&mut self) {
+        self.input = Some(String::new());
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.input.is_some()
+    }
+
+    /// Edits the input, esc closes the prompt. Returns the entered target on enter.
+    pub fn handle_key(&mut self, code: KeyCode) -> Option<String> {
+        let input = self.input.as_mut()?;
+        match code {
+            KeyCode::Char(c) if !c.is_whitespace() => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.input = None,
+            KeyCode::Enter => return self.input.take().filter(|target| !target.is_empty()),
+            _ => {}
+        }
+        None
+    }
+}
+
+/// Draws the open prompt over the last line of the screen
+pub fn draw_prompt(f: &mut Frame, prompt: &TargetPrompt, lang: &str) {
+    let Some(input) = &prompt.input else {
+        return;
+    };
+    let size = f.area();
+    if size.height == 0 {
+        return;
+    }
+    let area = Rect::new(size.x, size.y + size.height - 1, size.width, 1);
+    let line = Line::from(vec![
+        Span::styled(format!("{} ", i18n::t(lang, "prompt-add-target")), Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)),
+        Span::raw(input.clone()),
+        Span::styled("█", Style::default().fg(Color::Cyan)),
+        Span::styled("  enter: add, esc: cancel", Style::default().fg(Color::DarkGray)),
+    ]);
+    f.render_widget(Clear, area);
+    f.render_widget(Paragraph::new(line), area);
+}
@@ -5,12 +5,14 @@ use ratatui::prelude::{Color, Style, Span, Line};
 use ratatui::widgets::{Block, Borders, Paragraph, Sparkline, Wrap};
 use crate::ip_data::IpData;
 use crate::ui::point::get_loss_color_and_emoji;
-use crate::ui::utils::{calculate_avg_rtt, calculate_jitter, calculate_loss_pkg, draw_errors_section};
+use crate::stats::StatsWindow;
+use crate::ui::utils::draw_errors_section;
 use crate::i18n;
 
 pub fn draw_sparkline_view<B: Backend>(
     f: &mut Frame,
     ip_data: &[IpData],
+    window: StatsWindow,
     errs: &[String],
     area: Rect,
     lang: &str,
@@ -40,16 +42,15 @@ pub fn draw_sparkline_view<B: Backend>(
     f.render_widget(desc_para, chunks[1]);
 
     for (i, ip) in data.iter().enumerate() {
-        let avg_rtt = calculate_avg_rtt(&ip.rtts);
-        let jitter = calculate_jitter(&ip.rtts);
-        let loss_pkg = calculate_loss_pkg(ip.timeout, ip.received);
+        let summary = ip.summary(window);
+        let (avg_rtt, jitter, loss_pkg) = (summary.avg_rtt(), summary.jitter(), summary.loss());
         let loss_pkg_color = get_loss_color_and_emoji(loss_pkg);
 
         let info_line = Line::from(vec![
             Span::raw(format!("{}: ", i18n::t(lang, "label-target"))),
-            Span::styled(format!("{} ", ip.addr), Style::default().fg(Color::Green)),
+            Span::styled(format!("{} ", ip.label()), Style::default().fg(Color::Green)),
             Span::raw(format!("{}: ", i18n::t(lang, "label-ip"))),
-            Span::styled(format!("{} ", ip.ip), Style::default().fg(Color::Green)),
+            Span::styled(format!("{} ", ip.display_ip()), Style::default().fg(Color::Green)),
             Span::raw(format!("{}: ", i18n::t(lang, "label-last-rtt"))),
             Span::styled(
                 if ip.last_attr == 0.0 {
@@ -64,11 +65,13 @@ pub fn draw_sparkline_view<B: Backend>(
             Span::raw(format!(" {}: ", i18n::t(lang, "label-avg-rtt"))),
             Span::styled(format!("{:.2}{}", avg_rtt, i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
             Span::raw(format!(" {}: ", i18n::t(lang, "label-max"))),
-            Span::styled(format!("{:.2}{}", ip.max_rtt, i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
+            Span::styled(format!("{:.2}{}", summary.max_rtt, i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
             Span::raw(format!(" {}: ", i18n::t(lang, "label-min"))),
-            Span::styled(format!("{:.2}{}", ip.min_rtt, i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
+            Span::styled(format!("{:.2}{}", summary.min_rtt, i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
             Span::raw(format!(" {}: ", i18n::t(lang, "label-jitter"))),
             Span::styled(format!("{:.2}{}", jitter, i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
+            Span::raw(format!(" {}: ", i18n::t(lang, "label-stddev"))),
+            Span::styled(format!("{:.2}{}", summary.stddev(), i18n::t(lang, "unit-ms")), Style::default().fg(Color::Green)),
             Span::raw(format!(" {}: ", i18n::t(lang, "label-loss"))),
             Span::styled(format!("{:.2}{}", loss_pkg, i18n::t(lang, "unit-percent")), Style::default().fg(loss_pkg_color)),
         ]);
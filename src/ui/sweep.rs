@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use ratatui::backend::Backend;
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::prelude::{Color, Line, Span, Style};
+use ratatui::widgets::{Block, Borders, Paragraph};
+use crate::ip_data::IpData;
+use crate::stats::StatsWindow;
+use crate::ui::utils::draw_errors_section;
+use crate::i18n;
+
+/// Targets of a sweep split by whether they answered, in address order
+pub struct Sweep<'a> {
+    /// answered at least once
+    pub answered: Vec<&'a IpData>,
+    /// every probe so far went unanswered
+    pub silent: Vec<&'a IpData>,
+    /// nothing sent yet
+    pub waiting: Vec<&'a IpData>,
+}
+
+impl<'a> Sweep<'a> {
+    pub fn new(ip_data: &'a [IpData]) -> Self {
+        let mut data: Vec<&IpData> = ip_data.iter().collect();
+        data.sort_by_key(|d| d.ip.parse::<IpAddr>().ok());
+
+        let mut sweep = Sweep { answered: Vec::new(), silent: Vec::new(), waiting: Vec::new() };
+        for d in data {
+            if d.received > 0 {
+                sweep.answered.push(d);
+            } else if d.failed() > 0 {
+                sweep.silent.push(d);
+            } else {
+                sweep.waiting.push(d);
+            }
+        }
+        sweep
+    }
+
+    pub fn total(&self) -> usize {
+        self.answered.len() + self.silent.len() + self.waiting.len()
+    }
+}
+
+pub fn draw_sweep_view<B: Backend>(
+    f: &mut Frame,
+    ip_data: &[IpData],
+    window: StatsWindow,
+    errs: &[String],
+    area: Rect,
+    lang: &str,
+) {
+    let sweep = Sweep::new(ip_data);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Min(5),
+            Constraint::Length(6),
+        ].as_ref())
+        .split(area);
+
+    // live counter of the hosts that answered
+    let mut counter = vec![
+        Span::styled(format!(" 🏎  {}  ", i18n::t(lang, "sweep-view-title")), Style::default().fg(Color::Cyan)),
+        Span::raw(format!("{}: ", i18n::t(lang, "sweep-hosts-up"))),
+        Span::styled(format!("{}/{}", sweep.answered.len(), sweep.total()), Style::default().fg(Color::Green)),
+        Span::raw(format!("  {}: ", i18n::t(lang, "sweep-silent"))),
+        Span::styled(sweep.silent.len().to_string(), Style::default().fg(Color::Red)),
+    ];
+    if !sweep.waiting.is_empty() {
+        counter.push(Span::raw(format!("  {}: {}", i18n::t(lang, "sweep-waiting"), sweep.waiting.len())));
+    }
+    f.render_widget(Paragraph::new(Line::from(counter)), chunks[1]);
+
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(chunks[2]);
+
+    let answered: Vec<Line> = sweep.answered
+        .iter()
+        .map(|d| {
+            let summary = d.summary(window);
+            Line::from(vec![
+                Span::styled(format!("{:<40}", d.display_ip()), Style::default().fg(Color::Green)),
+                Span::raw(format!("{:>10.2}{}", summary.avg_rtt(), i18n::t(lang, "unit-ms"))),
+                Span::raw(format!("{:>10.2}{}", summary.loss(), i18n::t(lang, "unit-percent"))),
+            ])
+        })
+        .collect();
+    let answered_block = Block::default()
+        .title(format!("{} ({})", i18n::t(lang, "sweep-answered"), sweep.answered.len()))
+        .borders(Borders::ALL);
+    f.render_widget(Paragraph::new(answered).block(answered_block), columns[0]);
+
+    // silent hosts are listed side by side, there are usually many of them. Those that
+    // don't fit the panel are only counted.
+    let silent_block = Block::default()
+        .title(format!("{} ({})", i18n::t(lang, "sweep-silent"), sweep.silent.len()))
+        .borders(Borders::ALL);
+    let inner = silent_block.inner(columns[1]);
+    let cell = sweep.silent.iter().map(|d| d.display_ip().chars().count()).max().unwrap_or_default() + 2;
+    let per_row = (inner.width as usize / cell).max(1);
+    let rows = inner.height as usize;
+    let shown = if sweep.silent.len() > per_row * rows {
+        per_row * rows.saturating_sub(1)
+    } else {
+        sweep.silent.len()
+    };
+    let cells: Vec<Span> = sweep.silent[..shown]
+        .iter()
+        .map(|d| Span::styled(format!("{:<width$}", d.display_ip(), width = cell), Style::default().fg(Color::Red)))
+        .collect();
+    let mut silent: Vec<Line> = cells.chunks(per_row).map(|row| Line::from(row.to_vec())).collect();
+    if shown < sweep.silent.len() {
+        let mut args = HashMap::new();
+        args.insert("count".to_string(), (sweep.silent.len() - shown).to_string());
+        silent.push(Line::from(Span::raw(i18n::t_with_args(lang, "sweep-more", &args))));
+    }
+    f.render_widget(Paragraph::new(silent).block(silent_block), columns[1]);
+
+    draw_errors_section::<B>(f, errs, chunks[3]);
+}
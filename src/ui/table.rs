@@ -1,37 +1,110 @@
+use std::collections::HashSet;
 use ratatui::backend::Backend;
 use ratatui::Frame;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::prelude::{Color, Modifier, Style};
-use ratatui::widgets::{Block, Paragraph, Row, Table};
-use crate::ip_data::IpData;
-use crate::ui::utils::{calculate_avg_rtt, calculate_jitter, calculate_loss_pkg, draw_errors_section};
+use ratatui::widgets::{Block, Cell, Paragraph, Row, Table, TableState};
+use ratatui::crossterm::event::KeyCode;
+use crate::ip_data::{GroupStats, IpData};
+use crate::ui::utils::draw_errors_section;
 use crate::i18n;
+use crate::probe::{parse_target, rcode_name, ProbeKind, Source};
+use crate::stats::{StatsWindow, PERCENTILES};
 
+/// Folding state of the target groups, changed from the keyboard
+#[derive(Debug, Default)]
+pub struct GroupView {
+    /// one row per group instead of one per target
+    pub collapsed: bool,
+    /// groups that list their members while collapsed
+    pub expanded: HashSet<String>,
+    /// highlighted group row
+    pub selected: usize,
+}
+
+impl GroupView {
+    /// `g` folds and unfolds the groups, arrows pick a group and enter/space expands it.
+    /// Returns whether the table has to be redrawn.
+    pub fn handle_key(&mut self, code: KeyCode, groups: &[GroupStats]) -> bool {
+        match code {
+            KeyCode::Char('g') if !groups.is_empty() => self.collapsed = !self.collapsed,
+            KeyCode::Up if self.collapsed => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down if self.collapsed => self.selected = (self.selected + 1).min(groups.len().saturating_sub(1)),
+            KeyCode::Enter | KeyCode::Char(' ') if self.collapsed => {
+                if let Some(group) = groups.get(self.selected) {
+                    if !self.expanded.remove(&group.name) {
+                        self.expanded.insert(group.name.clone());
+                    }
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Optional columns of the table. Percentiles are picked with `--percentiles` and cover the
+/// selected statistics window like the other columns.
+#[derive(Debug, Default, Clone)]
+pub struct ExtraColumns {
+    /// positions in [`PERCENTILES`]
+    pub percentiles: Vec<usize>,
+    /// the loss burst ratio, `--burst-column`
+    pub burst_ratio: bool,
+}
+
+/// Target row highlighted in the ungrouped table, the one `d` removes
+#[derive(Debug, Default)]
+pub struct TargetSelection {
+    /// addr, ip and source of the row, it keeps the highlight while the ranking changes
+    selected: Option<(String, String, Option<Source>)>,
+}
+
+impl TargetSelection {
+    /// Arrows move the highlight along the ranked rows. Returns whether the table has to be redrawn.
+    pub fn handle_key(&mut self, code: KeyCode, ip_data: &[IpData], window: StatsWindow) -> bool {
+        let rows = sort_rows(ip_data, window);
+        if rows.is_empty() {
+            return false;
+        }
+        let current = self.index(&rows);
+        let index = match (code, current) {
+            (KeyCode::Up, Some(index)) => index.saturating_sub(1),
+            (KeyCode::Down, Some(index)) => (index + 1).min(rows.len() - 1),
+            (KeyCode::Up | KeyCode::Down, None) => 0,
+            _ => return false,
+        };
+        let row = rows[index];
+        self.selected = Some((row.addr.clone(), row.ip.clone(), row.source.clone()));
+        true
+    }
+
+    /// The highlighted row, if it is still there
+    pub fn selected<'a>(&self, ip_data: &'a [IpData]) -> Option<&'a IpData> {
+        let (addr, ip, source) = self.selected.as_ref()?;
+        ip_data.iter().find(|d| &d.addr == addr && &d.ip == ip && &d.source == source)
+    }
+
+    fn index(&self, rows: &[&IpData]) -> Option<usize> {
+        let (addr, ip, source) = self.selected.as_ref()?;
+        rows.iter().position(|d| &d.addr == addr && &d.ip == ip && &d.source == source)
+    }
+}
 
 pub fn draw_table_view<B: Backend>(
     f: &mut Frame,
     ip_data: &[IpData],
+    groups: &[GroupStats],
+    group_view: &GroupView,
+    selection: &TargetSelection,
+    columns: &ExtraColumns,
+    window: StatsWindow,
     errs: &[String],
     area: Rect,
     lang: &str,
 ) {
-    let mut data = ip_data.to_vec();
-
-    data.sort_by(|a, b| {
-        let loss_a = calculate_loss_pkg(a.timeout, a.received);
-        let loss_b = calculate_loss_pkg(b.timeout, b.received);
-
-        // sort by loss rate first, then by latency
-        match loss_a.partial_cmp(&loss_b) {
-            Some(std::cmp::Ordering::Equal) => {
-                let avg_a = calculate_avg_rtt(&a.rtts);
-                let avg_b = calculate_avg_rtt(&b.rtts);
-                avg_a.partial_cmp(&avg_b).unwrap_or(std::cmp::Ordering::Equal)
-            }
-            Some(ordering) => ordering,
-            None => std::cmp::Ordering::Equal
-        }
-    });
+    let data = sort_rows(ip_data, window);
+    let paired = ip_data.iter().any(|a| ip_data.iter().any(|b| a.is_pair_of(b)));
 
 
     let header_style = Style::default()
@@ -40,8 +113,21 @@ pub fn draw_table_view<B: Backend>(
     let selected_style = Style::default()
         .add_modifier(Modifier::REVERSED);
 
+    // http(s) targets get their request phases as extra columns, dns targets their answer
+    let show_http = data.iter().any(|d| d.http.is_some());
+    let show_dns = data.iter().any(|d| d.dns.is_some());
+    // stray replies of icmp targets only get columns once there are some
+    let show_duplicates = data.iter().any(|d| matches!(parse_target(&d.addr), Ok((_, ProbeKind::Udp { .. }))) || d.duplicates + d.reordered > 0);
+    let show_late = data.iter().any(|d| d.late > 0);
+    let show_unreachable = data.iter().any(|d| d.unreachable > 0);
+    let show_pmtu = data.iter().any(|d| d.pmtu.is_some());
+    let show_source = data.iter().any(|d| d.source.is_some());
+    // packet settings shared by every target go into the title, differing ones get a column
+    let shared_packet = data.first().map(|d| d.packet).filter(|p| data.iter().all(|d| d.packet == *p));
+    let show_packet = shared_packet.is_none();
+
     // create header
-    let header = Row::new(vec![
+    let mut header_cells = vec![
         i18n::t(lang, "label-rank"),
         i18n::t(lang, "label-target"),
         i18n::t(lang, "label-ip"),
@@ -50,17 +136,72 @@ pub fn draw_table_view<B: Backend>(
         i18n::t(lang, "label-max"),
         i18n::t(lang, "label-min"),
         i18n::t(lang, "label-jitter"),
+        i18n::t(lang, "label-stddev"),
         i18n::t(lang, "label-loss"),
-    ])
+        i18n::t(lang, "label-mos"),
+    ];
+    // moves right with every column inserted before it
+    let mut mos_column = header_cells.len() - 1;
+    // path mtu sits next to the rtt columns
+    if show_pmtu {
+        header_cells.insert(5, i18n::t(lang, "label-pmtu"));
+        mos_column += 1;
+    }
+    // the source follows the ip so rows of the same target through different uplinks can be told apart
+    if show_source {
+        header_cells.insert(3, i18n::t(lang, "label-source"));
+        mos_column += 1;
+    }
+    // address family of the row, right after the ip
+    if paired {
+        header_cells.insert(3, i18n::t(lang, "label-af"));
+        mos_column += 1;
+    }
+    header_cells.extend(columns.percentiles.iter().map(|&index| PERCENTILES[index].0.to_string()));
+    if columns.burst_ratio {
+        header_cells.push(i18n::t(lang, "label-burst-ratio"));
+    }
+    if show_packet {
+        header_cells.push(i18n::t(lang, "label-packet"));
+    }
+    if show_http {
+        header_cells.extend([
+            i18n::t(lang, "label-dns"),
+            i18n::t(lang, "label-connect"),
+            i18n::t(lang, "label-tls"),
+            i18n::t(lang, "label-ttfb"),
+            i18n::t(lang, "label-status"),
+        ]);
+    }
+    if show_dns {
+        header_cells.extend([
+            i18n::t(lang, "label-rcode"),
+            i18n::t(lang, "label-answers"),
+        ]);
+    }
+    if show_duplicates {
+        header_cells.extend([
+            i18n::t(lang, "label-duplicates"),
+            i18n::t(lang, "label-reordered"),
+        ]);
+    }
+    if show_late {
+        header_cells.push(i18n::t(lang, "label-late"));
+    }
+    if show_unreachable {
+        header_cells.push(i18n::t(lang, "label-unreachable"));
+    }
+    let column_count = header_cells.len();
+    let header = Row::new(header_cells)
         .style(header_style)
         .height(1);
 
 
     // create rows
+    let collapsed = group_view.collapsed && !groups.is_empty();
     let rows = data.iter().enumerate().map(|(index, data)| {
-        let avg_rtt = calculate_avg_rtt(&data.rtts);
-        let jitter = calculate_jitter(&data.rtts);
-        let loss_pkg = calculate_loss_pkg(data.timeout, data.received);
+        let summary = data.summary(window);
+        let (avg_rtt, loss_pkg) = (summary.avg_rtt(), summary.loss());
 
         let rank = match index {
             0 => i18n::t(lang, "rank-first"),
@@ -70,10 +211,15 @@ pub fn draw_table_view<B: Backend>(
             _ => i18n::t(lang, "rank-slow"),
         };
 
-        let row = Row::new(vec![
+        let mut cells = vec![
             rank,
-            data.addr.clone(),
-            data.ip.clone(),
+            // members listed under their group row are indented
+            if collapsed && data.group.is_some() {
+                format!("  {}", data.label())
+            } else {
+                data.label().to_string()
+            },
+            data.noted_ip(),
             if data.last_attr == 0.0 {
                 i18n::t(lang, "metric-less-than")
             } else if data.last_attr == -1.0 {
@@ -82,40 +228,225 @@ pub fn draw_table_view<B: Backend>(
                 format!("{:.2}{}", data.last_attr, i18n::t(lang, "unit-ms"))
             },
             format!("{:.2}{}", avg_rtt, i18n::t(lang, "unit-ms")),
-            format!("{:.2}{}", data.max_rtt, i18n::t(lang, "unit-ms")),
-            format!("{:.2}{}", data.min_rtt, i18n::t(lang, "unit-ms")),
-            format!("{:.2}{}", jitter, i18n::t(lang, "unit-ms")),
+            format!("{:.2}{}", summary.max_rtt, i18n::t(lang, "unit-ms")),
+            format!("{:.2}{}", summary.min_rtt, i18n::t(lang, "unit-ms")),
+            format!("{:.2}{}", summary.jitter(), i18n::t(lang, "unit-ms")),
+            format!("{:.2}{}", summary.stddev(), i18n::t(lang, "unit-ms")),
             format!("{:.2}{}", loss_pkg, i18n::t(lang, "unit-percent")),
-        ]).height(1);
+            summary.quality().map_or(String::from("-"), |quality| format!("{:.2}", quality.mos)),
+        ];
+        if show_pmtu {
+            cells.insert(5, data.pmtu.map_or(String::from("-"), |mtu| mtu.to_string()));
+        }
+        if show_source {
+            cells.insert(3, data.source.as_ref().map_or(String::from("-"), |source| source.to_string()));
+        }
+        if paired {
+            cells.insert(3, data.family().to_string());
+        }
+        for &index in &columns.percentiles {
+            let value = data.stats.percentile(window, index);
+            cells.push(value.map_or(String::from("-"), |value| format!("{:.2}{}", value, i18n::t(lang, "unit-ms"))));
+        }
+        if columns.burst_ratio {
+            cells.push(data.bursts.ratio().map_or(String::from("-"), |ratio| format!("{:.2}", ratio)));
+        }
+        if show_packet {
+            cells.push(data.packet.to_string());
+        }
+        if show_http {
+            match &data.http {
+                Some(timing) => {
+                    let ms = |d: std::time::Duration| format!("{:.2}{}", d.as_secs_f64() * 1000.0, i18n::t(lang, "unit-ms"));
+                    cells.extend([
+                        ms(timing.dns),
+                        ms(timing.connect),
+                        ms(timing.tls),
+                        ms(timing.ttfb),
+                        timing.status.to_string(),
+                    ]);
+                }
+                None => cells.extend(std::iter::repeat_n(String::from("-"), 5)),
+            }
+        }
+        if show_dns {
+            match &data.dns {
+                Some(answer) => cells.extend([rcode_name(answer.rcode), answer.answers.to_string()]),
+                None => cells.extend(std::iter::repeat_n(String::from("-"), 2)),
+            }
+        }
+        if show_duplicates {
+            let duplicates = match data.duplicate_seq {
+                Some(seq) => format!("{} (#{})", data.duplicates, seq),
+                None => data.duplicates.to_string(),
+            };
+            cells.extend([duplicates, data.reordered.to_string()]);
+        }
+        if show_late {
+            cells.push(match data.late_rtt {
+                Some(rtt) => format!("{} ({:.2}{})", data.late, rtt, i18n::t(lang, "unit-ms")),
+                None => data.late.to_string(),
+            });
+        }
+        if show_unreachable {
+            cells.push(match data.unreachable_reason {
+                Some((icmp_type, code)) => format!("{} ({}/{})", data.unreachable, icmp_type, code),
+                None => data.unreachable.to_string(),
+            });
+        }
+        // the mos is colored on its own, calls suffer before the thresholds are reached
+        let mos_style = match summary.quality() {
+            Some(quality) if quality.is_good() => Style::default().bg(Color::Green).fg(Color::Black),
+            Some(quality) if quality.is_poor() => Style::default().bg(Color::Red).fg(Color::White),
+            Some(_) => Style::default().bg(Color::Yellow).fg(Color::Black),
+            None => Style::default(),
+        };
+        let row = Row::new(cells.into_iter().enumerate().map(|(column, cell)| {
+            if column == mos_column { Cell::from(cell).style(mos_style) } else { Cell::from(cell) }
+        })).height(1);
 
         // highlight the row with different colors
-        if loss_pkg > 50.0 {
+        if data.thresholds.is_critical(loss_pkg, avg_rtt) {
             row.style(Style::default().bg(Color::Red).fg(Color::White)) // Light red color
-        } else if loss_pkg > 0.0 {
+        } else if data.thresholds.is_warning(loss_pkg, avg_rtt) {
             row.style(Style::default().bg(Color::Yellow).fg(Color::White)) // Light yellow color
         } else {
             row
         }
     });
 
+    // folded: a row per group, members only below expanded groups, ungrouped targets as usual
+    let rows: Vec<Row> = if collapsed {
+        let target_rows: Vec<(&IpData, Row)> = data.iter().copied().zip(rows).collect();
+        let mut rows = Vec::new();
+        for (index, group) in groups.iter().enumerate() {
+            let expanded = group_view.expanded.contains(&group.name);
+            let mut cells = vec![
+                String::from(if expanded { "▾" } else { "▸" }),
+                group.name.clone(),
+                format!("{}/{} {}", group.healthy, group.members, i18n::t(lang, "label-healthy")),
+                String::from("-"),
+                format!("{} {:.2}{}", i18n::t(lang, "label-median"), group.median_rtt, i18n::t(lang, "unit-ms")),
+                String::from("-"),
+                String::from("-"),
+                String::from("-"),
+                String::from("-"),
+                format!("{} {:.2}{}", i18n::t(lang, "label-worst"), group.worst_loss, i18n::t(lang, "unit-percent")),
+                String::from("-"),
+            ];
+            if show_pmtu {
+                cells.insert(5, String::from("-"));
+            }
+            if show_source {
+                cells.insert(3, String::from("-"));
+            }
+            if paired {
+                cells.insert(3, String::from("-"));
+            }
+            cells.resize(column_count, String::from("-"));
+
+            let mut style = if group.healthy == 0 {
+                Style::default().bg(Color::Red).fg(Color::White)
+            } else if group.healthy < group.members {
+                Style::default().bg(Color::Yellow).fg(Color::White)
+            } else {
+                Style::default()
+            };
+            style = style.add_modifier(Modifier::BOLD);
+            if index == group_view.selected {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            rows.push(Row::new(cells).height(1).style(style));
 
-    let table = Table::new(
-        rows,
-        [
+            if expanded {
+                rows.extend(target_rows.iter()
+                    .filter(|(data, _)| data.group.as_deref() == Some(group.name.as_str()))
+                    .map(|(_, row)| row.clone()));
+            }
+        }
+        rows.extend(target_rows.into_iter().filter(|(data, _)| data.group.is_none()).map(|(_, row)| row));
+        rows
+    } else {
+        rows.collect()
+    };
+
+
+    let mut widths = if show_http || show_dns || show_duplicates || show_late || show_unreachable || show_packet {
+        vec![
             Constraint::Percentage(3),
-            Constraint::Percentage(15),
-            Constraint::Percentage(15),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
+            Constraint::Percentage(14),
             Constraint::Percentage(10),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
-        ],
-    )
+            Constraint::Percentage(6),
+            Constraint::Percentage(6),
+            Constraint::Percentage(6),
+            Constraint::Percentage(6),
+            Constraint::Percentage(6),
+            Constraint::Percentage(6),
+            Constraint::Percentage(6),
+            Constraint::Percentage(5),
+        ]
+    } else {
+        vec![
+            Constraint::Percentage(3),
+            Constraint::Percentage(15),
+            Constraint::Percentage(14),
+            Constraint::Percentage(9),
+            Constraint::Percentage(9),
+            Constraint::Percentage(8),
+            Constraint::Percentage(8),
+            Constraint::Percentage(8),
+            Constraint::Percentage(8),
+            Constraint::Percentage(9),
+            Constraint::Percentage(6),
+        ]
+    };
+    if show_pmtu {
+        widths.insert(5, Constraint::Percentage(6));
+    }
+    if show_source {
+        widths.insert(3, Constraint::Percentage(10));
+    }
+    if paired {
+        widths.insert(3, Constraint::Percentage(5));
+    }
+    widths.extend(columns.percentiles.iter().map(|_| Constraint::Percentage(6)));
+    if columns.burst_ratio {
+        widths.push(Constraint::Percentage(6));
+    }
+    if show_packet {
+        widths.push(Constraint::Percentage(12));
+    }
+    if show_http {
+        widths.extend([7, 7, 7, 7, 5].map(Constraint::Percentage));
+    }
+    if show_dns {
+        widths.extend([9, 6].map(Constraint::Percentage));
+    }
+    if show_duplicates {
+        widths.extend([8, 6].map(Constraint::Percentage));
+    }
+    if show_late {
+        widths.push(Constraint::Percentage(10));
+    }
+    if show_unreachable {
+        widths.push(Constraint::Percentage(8));
+    }
+
+    let mut title = String::from("🏎  PingWatch Table (Sort by: Loss Rate ↑ then Latency ↑)");
+    if let Some(packet) = shared_packet.filter(|p| !p.is_default()) {
+        title.push_str(&format!(" [{}]", packet));
+    }
+    if collapsed {
+        title.push_str("  g: ungroup, ↑↓ enter: expand");
+    } else if !groups.is_empty() {
+        title.push_str("  g: group");
+    }
+    title.push_str(if collapsed { "  a: add" } else { "  a: add, ↑↓ d: remove" });
+
+    let table = Table::new(rows, widths)
         .header(header)
         .block(Block::default()
-            .title("🏎  PingWatch Table (Sort by: Loss Rate ↑ then Latency ↑)"))
+            .title(title))
         .row_highlight_style(selected_style)
         .highlight_symbol(">> ");
 
@@ -131,8 +462,45 @@ pub fn draw_table_view<B: Backend>(
     // black line
     let blank = Paragraph::new("");
     f.render_widget(blank, chunks[0]);
-    f.render_widget(table, chunks[1]);
+    // the highlighted target, only while every target has a row of its own
+    let mut state = TableState::default();
+    if !collapsed {
+        state.select(selection.index(&data));
+    }
+    f.render_stateful_widget(table, chunks[1], &mut state);
 
     let errors_chunk = chunks.last().unwrap();
     draw_errors_section::<B>(f, errs, *errors_chunk);
-}
\ No newline at end of file
+}
+
+// ranked by loss rate, then latency. The v4 and v6 rows of a --dual-stack target are ranked
+// by the worse of the two and stay together
+fn sort_rows(ip_data: &[IpData], window: StatsWindow) -> Vec<&IpData> {
+    let paired = ip_data.iter().any(|a| ip_data.iter().any(|b| a.is_pair_of(b)));
+    let mut data: Vec<(f64, f64, &IpData)> = ip_data.iter().map(|d| {
+        let (loss, avg) = ip_data.iter()
+            .filter(|other| other.is_pair_of(d))
+            .chain([d])
+            .map(|other| other.summary(window))
+            .map(|summary| (summary.loss(), summary.avg_rtt()))
+            .fold((0.0f64, 0.0f64), |(loss, avg), (l, a)| (loss.max(l), avg.max(a)));
+        (loss, avg, d)
+    }).collect();
+
+    data.sort_by(|(loss_a, avg_a, a), (loss_b, avg_b, b)| {
+        // sort by loss rate first, then by latency
+        match loss_a.partial_cmp(loss_b) {
+            Some(std::cmp::Ordering::Equal) => {
+                avg_a.partial_cmp(avg_b).unwrap_or(std::cmp::Ordering::Equal)
+            }
+            Some(ordering) => ordering,
+            None => std::cmp::Ordering::Equal
+        }.then_with(|| if paired {
+            (&a.addr, a.source.as_ref().map(|s| s.to_string()), a.family())
+                .cmp(&(&b.addr, b.source.as_ref().map(|s| s.to_string()), b.family()))
+        } else {
+            std::cmp::Ordering::Equal
+        })
+    });
+    data.into_iter().map(|(_, _, d)| d).collect()
+}
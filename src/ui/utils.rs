@@ -1,41 +1,9 @@
-use std::collections::VecDeque;
 use ratatui::backend::Backend;
 use ratatui::Frame;
 use ratatui::layout::Rect;
 use ratatui::prelude::{Color, Line, Span, Style};
-use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
-
-pub fn calculate_avg_rtt(rtt: &VecDeque<f64>) -> f64 {
-    if !rtt.is_empty() {
-        let valid_rtt: Vec<f64> = rtt.iter().cloned().filter(|&rtt| rtt >= 0.0).collect();
-        if !valid_rtt.is_empty() {
-            let sum: f64 = valid_rtt.iter().sum();
-            sum / valid_rtt.len() as f64
-        } else {
-            0.0
-        }
-    } else {
-        0.0
-    }
-}
-
-pub fn calculate_jitter(rtt: &VecDeque<f64>) -> f64 {
-    if rtt.len() > 1 {
-        let diffs: Vec<f64> = rtt.iter().zip(rtt.iter().skip(1)).map(|(y1, y2)| (y2 - y1).abs()).collect();
-        let sum: f64 = diffs.iter().sum();
-        sum / diffs.len() as f64
-    } else {
-        0.0
-    }
-}
-
-pub fn calculate_loss_pkg(timeout: usize, received: usize) -> f64 {
-    if timeout > 0 {
-        (timeout as f64 / (received as f64 + timeout as f64)) * 100.0
-    } else {
-        0.0
-    }
-}
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+use crate::stats::StatsWindow;
 
 pub fn draw_errors_section<B: Backend>(
     f: &mut Frame,
@@ -66,4 +34,23 @@ pub fn draw_errors_section<B: Backend>(
             .wrap(Wrap { trim: true });
         f.render_widget(errors_paragraph, area);
     }
-}
\ No newline at end of file
+}
+/// The statistics window every view summarizes, in the top right corner
+pub fn draw_window(f: &mut Frame, window: StatsWindow) {
+    let line = Line::from(vec![
+        Span::raw("stats: "),
+        Span::styled(window.label(), Style::default().fg(Color::Cyan)),
+        Span::styled("  w: window, r: reset, o: outages, b: bursts ", Style::default().fg(Color::DarkGray)),
+    ]);
+    let area = f.area();
+    let width = (line.width() as u16).min(area.width);
+    let corner = Rect { x: area.right() - width, y: area.y, width, height: 1.min(area.height) };
+    f.render_widget(Clear, corner);
+    f.render_widget(Paragraph::new(line), corner);
+}
+
+/// Lower half of the screen, where the detail panes open
+pub fn pane_area(size: Rect) -> Rect {
+    let height = (size.height / 2).max(5).min(size.height);
+    Rect::new(size.x, size.y + size.height - height, size.width, height)
+}